@@ -1,71 +1,109 @@
-#![deny(clippy::all)]
-#![warn(clippy::pedantic)]
-#![deny(clippy::as_conversions)]
-#![warn(clippy::nursery)]
-#![warn(clippy::cargo)]
-
 use anyhow::{Context, Ok, Result};
 use bevy::{
-    diagnostic::FrameTimeDiagnosticsPlugin, input::keyboard::KeyboardInput, prelude::*,
+    app::AppExit,
+    diagnostic::FrameTimeDiagnosticsPlugin,
+    input::keyboard::KeyboardInput,
+    prelude::*,
+    render::{
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::ImageSampler,
+    },
     window::PresentMode,
 };
-use log::info;
-use rusty_chip8::{Audio, Chip8, Graphics, FPS, TERMINAL_HEIGHT, TERMINAL_WIDTH};
+use log::{error, info};
+use rusty_chip8::{
+    debugger::Debugger, octo, Audio, Chip8, Framebuffer, Graphics, Platform, Quirks, SeededRandom,
+    Speed, FPS, TERMINAL_HEIGHT, TERMINAL_WIDTH,
+};
 use std::{
     collections::HashMap,
-    fs::File,
-    io::Read,
+    fs::{self, File},
+    io::{self, Read},
     path::{Path, PathBuf},
     time::Duration,
     vec,
 };
 use structopt::StructOpt;
 
+#[cfg(feature = "gdb")]
+use rusty_chip8::gdb;
+
 #[derive(Resource)]
 struct Chip8Resource(Chip8);
 
-struct BevyGraphics<'w, 's> {
-    commands: Commands<'w, 's>,
-}
+/// RGBA colours of the background, the two XO-CHIP bitplanes and their overlap.
+const PALETTE: [[u8; 4]; 4] = [
+    [0, 0, 0, 255],
+    [255, 255, 255, 255],
+    [255, 165, 0, 255],
+    [64, 64, 64, 255],
+];
 
-impl BevyGraphics<'_, '_> {
-    /// Draws/turns on a pixel on a specific coordinate.
-    ///
-    /// If the coordinates is out of the screen area it returns an Error.
-    fn draw_pixel(&mut self, x: usize, y: usize, color: Option<Color>) {
-        let x =
-            i16::try_from(x).unwrap_or_else(|_| panic!("Invalid x coordinate for the sprite: {x}"));
-        let y =
-            i16::try_from(y).unwrap_or_else(|_| panic!("Invalid y coordinate for the sprite: {y}"));
-        let x = f32::from((x - 32) * 10);
-        let y = f32::from((16 - y) * 10);
-        let rectangle = SpriteBundle {
-            sprite: Sprite {
-                color: color.unwrap_or(Color::WHITE),
-                custom_size: Some(Vec2::new(10.0, 10.0)),
-                ..default()
-            },
-            transform: Transform::from_xyz(x, y, 0.),
-            ..default()
-        };
-        self.commands.spawn(rectangle);
-    }
-}
+/// The texture the Chip8 display is presented to.
+#[derive(Resource)]
+struct Screen(Handle<Image>);
 
-impl Graphics for BevyGraphics<'_, '_> {
-    fn clear_pixel(&mut self, x: usize, y: usize) {
-        self.draw_pixel(x, y, Some(Color::BLACK));
-    }
+struct BevyGraphics<'a> {
+    image: &'a mut Image,
+}
 
-    fn draw_pixel(&mut self, x: usize, y: usize) {
-        self.draw_pixel(x, y, None);
+impl Graphics for BevyGraphics<'_> {
+    fn present(&mut self, framebuffer: &Framebuffer) {
+        if !framebuffer.is_dirty() {
+            return;
+        }
+        let size = Extent3d {
+            width: u32::try_from(framebuffer.width()).unwrap_or_default(),
+            height: u32::try_from(framebuffer.height()).unwrap_or_default(),
+            depth_or_array_layers: 1,
+        };
+        if self.image.texture_descriptor.size != size {
+            self.image.resize(size);
+        }
+        for (pixel, color) in self
+            .image
+            .data
+            .chunks_exact_mut(4)
+            .zip(framebuffer.pixels())
+        {
+            pixel.copy_from_slice(&PALETTE[usize::from(color & 0b11)]);
+        }
     }
 }
 
-fn setup(mut commands: Commands) {
+fn setup(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
     commands.spawn(Camera2dBundle::default());
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: u32::try_from(TERMINAL_WIDTH).unwrap_or_default(),
+            height: u32::try_from(TERMINAL_HEIGHT).unwrap_or_default(),
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &PALETTE[0],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    image.sampler_descriptor = ImageSampler::nearest();
+    let image = images.add(image);
+    commands.spawn(SpriteBundle {
+        sprite: Sprite {
+            custom_size: Some(Vec2::new(
+                f32::from(u16::try_from(TERMINAL_WIDTH * 10).unwrap_or_default()),
+                f32::from(u16::try_from(TERMINAL_HEIGHT * 10).unwrap_or_default()),
+            )),
+            ..default()
+        },
+        texture: image.clone(),
+        ..default()
+    });
+    commands.insert_resource(Screen(image));
 }
 
+/// Graphics of the debugger, which shows no display.
+struct Headless;
+
+impl Graphics for Headless {}
+
 struct AudioEmulator;
 
 impl Audio for AudioEmulator {
@@ -78,26 +116,86 @@ impl Audio for AudioEmulator {
     }
 }
 
-#[derive(Resource)]
-struct CPUClock(Timer);
-
 #[derive(Resource)]
 struct TimerClock(Timer);
 
+/// Paths of the reports rewritten every second.
+#[derive(Resource, Default)]
+struct Reports {
+    /// Path of the profile report.
+    profile: Option<PathBuf>,
+    /// Path of the memory heatmap image.
+    heatmap: Option<PathBuf>,
+}
+
 #[allow(clippy::needless_pass_by_value)]
 fn tick(
-    commands: Commands,
+    screen: Res<Screen>,
+    mut images: ResMut<Assets<Image>>,
     time: Res<Time>,
     mut timer_clock: ResMut<TimerClock>,
     mut ch8: ResMut<Chip8Resource>,
+    mut exit: EventWriter<AppExit>,
+    reports: Res<Reports>,
 ) {
-    let mut graphics = BevyGraphics { commands };
+    let Some(image) = images.get_mut(&screen.0) else {
+        return;
+    };
+    let mut graphics = BevyGraphics { image };
     let mut audio = AudioEmulator;
     if timer_clock.0.tick(time.delta()).just_finished() {
-        ch8.0.tick(&mut graphics, &mut audio);
+        if let Err(err) = ch8.0.run_frame(&mut graphics, &mut audio) {
+            error!("Chip8 crashed: {err}");
+            exit.send(AppExit);
+        }
+        if ch8.0.frame_count().is_multiple_of(FPS) {
+            if let Some(path) = &reports.profile {
+                if let Err(err) = write_profile(&ch8.0, path) {
+                    error!("Failed to write the profile: {err:#}");
+                }
+            }
+            if let Some(path) = &reports.heatmap {
+                if let Err(err) = write_heatmap(&ch8.0, path) {
+                    error!("Failed to write the memory heatmap: {err:#}");
+                }
+            }
+        }
     }
 }
 
+/// Writes the profile report to `path`, and the folded call stacks for
+/// flame graphs next to it with the `.folded` extension.
+fn write_profile(ch8: &Chip8, path: &Path) -> Result<()> {
+    let Some(profile) = ch8.profile() else {
+        return Ok(());
+    };
+    fs::write(path, profile.to_string()).context("failed to write the report")?;
+    let folded = File::create(path.with_extension("folded"))?;
+    profile
+        .write_folded(io::BufWriter::new(folded))
+        .context("failed to write the folded stacks")?;
+    Ok(())
+}
+
+/// Writes the memory heatmap image to `path`, and next to it the table of
+/// the accesses with the `.csv` extension and the writes to code with `.txt`.
+fn write_heatmap(ch8: &Chip8, path: &Path) -> Result<()> {
+    let Some(heatmap) = ch8.memory_heatmap() else {
+        return Ok(());
+    };
+    let image = File::create(path)?;
+    heatmap
+        .write_ppm(io::BufWriter::new(image))
+        .context("failed to write the image")?;
+    let table = File::create(path.with_extension("csv"))?;
+    heatmap
+        .write_csv(io::BufWriter::new(table))
+        .context("failed to write the table")?;
+    fs::write(path.with_extension("txt"), heatmap.to_string())
+        .context("failed to write the self-modifications")?;
+    Ok(())
+}
+
 fn keyboard_events(mut key_evr: EventReader<KeyboardInput>, mut ch8: ResMut<Chip8Resource>) {
     use bevy::input::ButtonState;
 
@@ -125,53 +223,83 @@ fn keyboard_events(mut key_evr: EventReader<KeyboardInput>, mut ch8: ResMut<Chip
     .collect::<HashMap<_, _>>();
 
     for ev in key_evr.iter() {
-        match ev.state {
-            ButtonState::Pressed => {
-                if let k @ Some(
-                    KeyCode::Key1
-                    | KeyCode::Key2
-                    | KeyCode::Key3
-                    | KeyCode::Key4
-                    | KeyCode::Q
-                    | KeyCode::W
-                    | KeyCode::E
-                    | KeyCode::R
-                    | KeyCode::A
-                    | KeyCode::S
-                    | KeyCode::D
-                    | KeyCode::F
-                    | KeyCode::Z
-                    | KeyCode::X
-                    | KeyCode::C
-                    | KeyCode::V,
-                ) = ev.key_code
-                {
-                    ch8.0
-                        .handle_key_pressed(keymap.get(&k.unwrap()).copied().unwrap());
-                }
-            }
-            ButtonState::Released => ch8.0.handle_key_released(),
+        let Some(&key) = ev.key_code.and_then(|k| keymap.get(&k)) else {
+            continue;
+        };
+        let result = match ev.state {
+            ButtonState::Pressed => ch8.0.handle_key_pressed(key),
+            ButtonState::Released => ch8.0.handle_key_released(key),
+        };
+        if let Err(err) = result {
+            error!("{err}");
         }
     }
 }
 
-/// Reads rom into the buffer
-fn read_rom(path: &impl AsRef<Path>) -> Result<Vec<u8>> {
+/// Reads rom into the buffer, compiling it first if it is an Octo source
+fn read_rom(path: &impl AsRef<Path>, platform: Platform) -> Result<Vec<u8>> {
+    if path.as_ref().extension().is_some_and(|ext| ext == "8o") {
+        let source = fs::read_to_string(path).context("Failed to read octo source file")?;
+        let program = octo::compile(&source, platform).context("Failed to compile octo source")?;
+        info!("rom size = {}", program.rom().len());
+        return Ok(program.into_rom());
+    }
     let mut data = vec![];
     let rom_size = File::open(path)
         .context("Failed to open rom file")?
         .read_to_end(&mut data)
         .context("Failed to read rom file")?;
-    info!("rom size = {}", rom_size);
+    info!("rom size = {rom_size}");
     Ok(data)
 }
 
 #[derive(Debug, StructOpt)]
 struct Opt {
+    /// The ROM to run, or an Octo source file with the `.8o` extension.
     #[structopt(name = "ROM_FILE_PATH", parse(from_os_str))]
     rom: PathBuf,
     #[structopt(short, long, default_value = "700")]
     clock: u64,
+    /// Runs this many instructions per frame instead of `clock` per second.
+    #[structopt(long)]
+    cycles_per_frame: Option<u64>,
+    /// Emulates the timing and quirks of the original COSMAC VIP interpreter.
+    #[structopt(long)]
+    cosmac_vip: bool,
+    /// Seeds the random numbers, to make runs reproducible.
+    #[structopt(long)]
+    seed: Option<u64>,
+    /// The instruction set to emulate: `chip8`, `schip` or `xochip`.
+    #[structopt(short, long, default_value = "chip8", parse(try_from_str = parse_platform))]
+    platform: Platform,
+    /// Profiles the execution, writing a report to this file and the call
+    /// stacks for flame graphs next to it, every second.
+    #[structopt(long, parse(from_os_str))]
+    profile: Option<PathBuf>,
+    /// Records the accesses to RAM, writing a PPM heatmap to this file, and
+    /// a CSV table and the writes to code next to it, every second.
+    #[structopt(long, parse(from_os_str))]
+    heatmap: Option<PathBuf>,
+    /// Writes a line per executed instruction to this file, to compare runs
+    /// with the `trace-diff` example.
+    #[structopt(long, parse(from_os_str))]
+    trace: Option<PathBuf>,
+    /// Runs the ROM in a line-oriented debugger on the terminal instead of a window.
+    #[structopt(long)]
+    debug: bool,
+    /// Serves the GDB remote protocol on this local port instead of opening a window.
+    #[cfg(feature = "gdb")]
+    #[structopt(long)]
+    gdb: Option<u16>,
+}
+
+fn parse_platform(platform: &str) -> Result<Platform> {
+    match platform {
+        "chip8" => Ok(Platform::Chip8),
+        "schip" => Ok(Platform::SuperChip),
+        "xochip" => Ok(Platform::XoChip),
+        _ => anyhow::bail!("unknown platform: {platform}"),
+    }
 }
 
 fn main() -> Result<()> {
@@ -179,12 +307,55 @@ fn main() -> Result<()> {
 
     let opt = Opt::from_args();
 
-    let rom = read_rom(&opt.rom)?;
+    let rom = read_rom(&opt.rom, opt.platform)?;
 
-    let mut ch8 = Chip8::new(opt.clock);
+    let quirks = if opt.cosmac_vip {
+        Quirks::cosmac_vip()
+    } else {
+        Quirks::default()
+    };
+    let mut ch8 = Chip8::with_platform(opt.clock, opt.platform, quirks);
+    if opt.cosmac_vip {
+        ch8.set_speed(Speed::CosmacVip);
+    } else if let Some(cycles) = opt.cycles_per_frame {
+        ch8.set_speed(Speed::CyclesPerFrame(cycles));
+    }
+    ch8.set_random(
+        opt.seed
+            .map_or_else(SeededRandom::from_entropy, SeededRandom::new),
+    );
 
     ch8.store_in_ram(rom)
         .context("failed to store rom into the ram")?;
+    if let Some(path) = &opt.trace {
+        let file = File::create(path).context("failed to create the trace")?;
+        ch8.start_trace(io::BufWriter::new(file))?;
+    }
+
+    #[cfg(feature = "gdb")]
+    if let Some(port) = opt.gdb {
+        info!("Waiting for a GDB client on port {port}");
+        let mut debugger = Debugger::new(ch8);
+        gdb::listen(&mut debugger, port, &mut Headless, &mut AudioEmulator)?;
+        return Ok(());
+    }
+
+    if opt.debug {
+        Debugger::new(ch8).repl(
+            io::stdin().lock(),
+            io::stdout(),
+            &mut Headless,
+            &mut AudioEmulator,
+        )?;
+        return Ok(());
+    }
+
+    ch8.set_profiling(opt.profile.is_some());
+    ch8.set_memory_heatmap(opt.heatmap.is_some());
+    let reports = Reports {
+        profile: opt.profile,
+        heatmap: opt.heatmap,
+    };
 
     App::new()
         .add_plugins(DefaultPlugins.set(WindowPlugin {
@@ -198,9 +369,10 @@ fn main() -> Result<()> {
             },
             ..default()
         }))
-        .add_plugin(FrameTimeDiagnosticsPlugin::default())
+        .add_plugin(FrameTimeDiagnosticsPlugin)
         .insert_resource(Chip8Resource(ch8))
         .insert_resource(ClearColor(Color::BLACK))
+        .insert_resource(reports)
         .insert_resource(TimerClock(Timer::new(
             Duration::from_millis(1000 / FPS),
             TimerMode::Repeating,
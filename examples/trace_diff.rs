@@ -0,0 +1,41 @@
+use anyhow::{Context, Result};
+use rusty_chip8::trace;
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+use structopt::StructOpt;
+
+/// Finds the first line where two instruction traces differ.
+#[derive(Debug, StructOpt)]
+struct Opt {
+    /// The first trace.
+    #[structopt(parse(from_os_str))]
+    left: PathBuf,
+    /// The second trace.
+    #[structopt(parse(from_os_str))]
+    right: PathBuf,
+    /// Ignores the fields with this key, e.g. `CY`.
+    #[structopt(long, number_of_values = 1)]
+    ignore: Vec<String>,
+}
+
+fn open(path: &Path) -> Result<BufReader<File>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    Ok(BufReader::new(file))
+}
+
+fn main() -> Result<ExitCode> {
+    let opt = Opt::from_args();
+    let ignored: Vec<&str> = opt.ignore.iter().map(String::as_str).collect();
+    let divergence = trace::first_divergence(open(&opt.left)?, open(&opt.right)?, &ignored)
+        .context("Failed to read the traces")?;
+    let Some(divergence) = divergence else {
+        println!("The traces are the same");
+        return Ok(ExitCode::SUCCESS);
+    };
+    println!("{divergence}");
+    Ok(ExitCode::FAILURE)
+}
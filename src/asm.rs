@@ -0,0 +1,819 @@
+//! Assembler of Chip8 programs.
+//!
+//! The syntax is the one of the [`disasm`](crate::disasm) listings:
+//!
+//! ```text
+//! define SPEED 2          ; constant
+//! start:                  ; label
+//!     LD I, sprite
+//!     LD V0, SPEED + 1
+//!     DRW V0, V1, 2
+//!     JP start
+//! sprite:
+//!     DB 0b11000011, 0x3C ; bytes
+//!     DW 0x1234           ; big-endian words
+//! include "font.asm"      ; source file, relative to the including one
+//! ```
+//!
+//! Mnemonics, registers and directives are case-insensitive, symbols are
+//! not. Numbers are decimal, or hexadecimal and binary with the `0x` and
+//! `0b` prefixes. Operands can add and subtract numbers, labels and the
+//! constants defined above them. Comments start with `;`.
+//!
+//! A listing printed with `{:#}` leaves out the addresses and opcodes, so it
+//! assembles back to the same program:
+//!
+//! ```
+//! use rusty_chip8::{asm, disasm, Platform};
+//!
+//! let rom = asm::assemble("loop:\n    ADD V0, 1\n    JP loop\n", Platform::Chip8)?;
+//! assert_eq!(rom, [0x70, 0x01, 0x12, 0x00]);
+//!
+//! let listing = format!("{:#}", disasm::disassemble(&rom, Platform::Chip8));
+//! assert_eq!(asm::assemble(&listing, Platform::Chip8)?, rom);
+//! # Ok::<(), asm::AsmError>(())
+//! ```
+
+use std::{
+    collections::HashMap,
+    error, fmt, fs,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use crate::{Instruction, Platform, PROGRAM_START};
+
+/// Mnemonics of the instructions.
+const MNEMONICS: [&str; 32] = [
+    "CLS", "RET", "SYS", "JP", "CALL", "SE", "SNE", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SHR",
+    "SUBN", "SHL", "RND", "DRW", "SKP", "SKNP", "SCD", "SCR", "SCL", "EXIT", "LOW", "HIGH", "SCU",
+    "SAVE", "LOAD", "PLANE", "AUDIO", "PITCH",
+];
+/// Operand keywords, which can not be used as symbols, like the registers.
+const KEYWORDS: [&str; 9] = ["I", "DT", "ST", "K", "F", "HF", "B", "R", "LONG"];
+
+/// Assembles `source` into a ROM to be loaded with [`Chip8::store_in_ram`](crate::Chip8::store_in_ram).
+///
+/// Included files are looked up relative to the current directory.
+///
+/// # Errors
+///
+/// Returns an [`AsmError`] locating the first error found in the source.
+pub fn assemble(source: &str, platform: Platform) -> Result<Vec<u8>, AsmError> {
+    let mut assembler = Assembler::new(platform);
+    assembler.read(source, None)?;
+    assembler.emit()
+}
+
+/// Assembles the source file at `path`, see [`assemble`].
+///
+/// # Errors
+///
+/// Returns an [`AsmError`] locating the first error found in the source.
+pub fn assemble_file(path: impl AsRef<Path>, platform: Platform) -> Result<Vec<u8>, AsmError> {
+    let mut assembler = Assembler::new(platform);
+    assembler.include(path.as_ref(), None)?;
+    assembler.emit()
+}
+
+/// Error found in an assembly or [Octo](crate::octo) source, with its location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    file: Option<PathBuf>,
+    line: usize,
+    column: usize,
+    kind: AsmErrorKind,
+}
+
+impl AsmError {
+    /// Returns an error at `line` and `column` of the source given to the compiler.
+    pub(crate) const fn new(line: usize, column: usize, kind: AsmErrorKind) -> Self {
+        Self {
+            file: None,
+            line,
+            column,
+            kind,
+        }
+    }
+
+    /// Returns the source file of the error, or `None` if it is in the
+    /// source given to [`assemble`].
+    #[must_use]
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// Returns the line of the error, starting at 1, or 0 if the file could not be read.
+    #[must_use]
+    pub const fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the column of the error, starting at 1, or 0 if the file could not be read.
+    #[must_use]
+    pub const fn column(&self) -> usize {
+        self.column
+    }
+
+    /// Returns what went wrong.
+    #[must_use]
+    pub const fn kind(&self) -> &AsmErrorKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) if self.line == 0 => write!(f, "{}: {}", file.display(), self.kind),
+            Some(file) => write!(
+                f,
+                "{}:{}:{}: {}",
+                file.display(),
+                self.line,
+                self.column,
+                self.kind
+            ),
+            None => write!(f, "{}:{}: {}", self.line, self.column, self.kind),
+        }
+    }
+}
+
+impl error::Error for AsmError {}
+
+/// Kinds of [`AsmError`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    /// The mnemonic or directive is unknown.
+    UnknownMnemonic {
+        /// The offending mnemonic.
+        mnemonic: String,
+    },
+    /// The operands do not fit the instruction or directive.
+    InvalidOperands {
+        /// The mnemonic of the instruction or directive.
+        mnemonic: String,
+    },
+    /// The operand is not a valid expression.
+    InvalidExpression,
+    /// The label or constant name is not an identifier, or is reserved for
+    /// registers and operand keywords.
+    InvalidSymbol {
+        /// The offending name.
+        name: String,
+    },
+    /// The symbol is not defined, or is a constant defined further down.
+    UndefinedSymbol {
+        /// The offending name.
+        name: String,
+    },
+    /// The label or constant is already defined.
+    DuplicateSymbol {
+        /// The offending name.
+        name: String,
+    },
+    /// The value does not fit the operand.
+    OutOfRange {
+        /// The value of the operand.
+        value: i64,
+        /// The smallest valid value.
+        min: i64,
+        /// The largest valid value.
+        max: i64,
+    },
+    /// The instruction does not exist on the platform.
+    UnsupportedInstruction {
+        /// The mnemonic of the instruction.
+        mnemonic: String,
+        /// The target platform.
+        platform: Platform,
+    },
+    /// The program does not fit into the RAM.
+    RomTooLarge {
+        /// Available space in bytes.
+        capacity: usize,
+    },
+    /// The source file could not be read.
+    ReadFailed {
+        /// Path of the file.
+        path: PathBuf,
+        /// Description of the I/O error.
+        message: String,
+    },
+    /// The file includes itself, directly or not.
+    IncludeCycle {
+        /// Path of the file.
+        path: PathBuf,
+    },
+    /// The source ends in the middle of a statement.
+    UnexpectedEnd,
+    /// The block keyword has no matching opening or closing keyword, e.g.
+    /// an `else` outside of an `if`, or a `loop` without `again`.
+    UnbalancedBlock {
+        /// The offending keyword.
+        keyword: String,
+    },
+    /// The macro keeps expanding into itself.
+    RecursiveMacro {
+        /// Name of the macro.
+        name: String,
+    },
+}
+
+impl fmt::Display for AsmErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownMnemonic { mnemonic } => write!(f, "unknown mnemonic `{mnemonic}`"),
+            Self::InvalidOperands { mnemonic } => write!(f, "invalid operands for `{mnemonic}`"),
+            Self::InvalidExpression => write!(f, "invalid expression"),
+            Self::InvalidSymbol { name } => write!(f, "`{name}` is not a valid symbol name"),
+            Self::UndefinedSymbol { name } => write!(f, "undefined symbol `{name}`"),
+            Self::DuplicateSymbol { name } => write!(f, "symbol `{name}` is already defined"),
+            Self::OutOfRange { value, min, max } => {
+                write!(f, "value {value} is out of range {min}..={max}")
+            }
+            Self::UnsupportedInstruction { mnemonic, platform } => {
+                write!(
+                    f,
+                    "`{mnemonic}` with these operands is not supported on {platform}"
+                )
+            }
+            Self::RomTooLarge { capacity } => {
+                write!(
+                    f,
+                    "program is too big to fit into the ram: {capacity} bytes available"
+                )
+            }
+            Self::ReadFailed { path, message } => {
+                write!(f, "failed to read {}: {message}", path.display())
+            }
+            Self::IncludeCycle { path } => write!(f, "{} includes itself", path.display()),
+            Self::UnexpectedEnd => write!(f, "unexpected end of source"),
+            Self::UnbalancedBlock { keyword } => write!(f, "unbalanced `{keyword}`"),
+            Self::RecursiveMacro { name } => write!(f, "macro `{name}` expands endlessly"),
+        }
+    }
+}
+
+/// Two-pass assembler: [`Assembler::read`] lays out the statements and
+/// defines the symbols, then [`Assembler::emit`] encodes them.
+#[derive(Debug)]
+struct Assembler {
+    platform: Platform,
+    statements: Vec<Statement>,
+    symbols: HashMap<String, i64>,
+    /// Address of the next statement.
+    address: usize,
+    /// Files being read, the innermost last.
+    files: Vec<PathBuf>,
+}
+
+#[derive(Debug)]
+struct Statement {
+    location: Location,
+    mnemonic: Token,
+    operands: Vec<Token>,
+}
+
+#[derive(Debug, Clone)]
+struct Location {
+    file: Option<PathBuf>,
+    line: usize,
+}
+
+impl Location {
+    fn error(&self, column: usize, kind: AsmErrorKind) -> AsmError {
+        AsmError {
+            file: self.file.clone(),
+            line: self.line,
+            column,
+            kind,
+        }
+    }
+}
+
+/// Piece of a source line, with the column it starts at.
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    column: usize,
+}
+
+/// Decoded operand of an instruction.
+#[derive(Debug)]
+enum Operand {
+    Register(usize),
+    /// `VX - VY`
+    Range(usize, usize),
+    I,
+    /// `[I]`
+    IndirectI,
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+    /// `LONG NNNN`
+    Long(Token),
+    Value(Token),
+}
+
+impl Assembler {
+    fn new(platform: Platform) -> Self {
+        Self {
+            platform,
+            statements: Vec::new(),
+            symbols: HashMap::new(),
+            address: PROGRAM_START,
+            files: Vec::new(),
+        }
+    }
+
+    /// Reads the source file at `path`, included from `from` if it is not the root file.
+    fn include(&mut self, path: &Path, from: Option<(&Location, usize)>) -> Result<(), AsmError> {
+        let error = |kind| match from {
+            Some((location, column)) => location.error(column, kind),
+            None => AsmError {
+                file: Some(path.to_path_buf()),
+                line: 0,
+                column: 0,
+                kind,
+            },
+        };
+        let source = fs::read_to_string(path).map_err(|e| {
+            error(AsmErrorKind::ReadFailed {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            })
+        })?;
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if self.files.contains(&canonical) {
+            return Err(error(AsmErrorKind::IncludeCycle {
+                path: path.to_path_buf(),
+            }));
+        }
+        self.files.push(canonical);
+        self.read(&source, Some(path))?;
+        self.files.pop();
+        Ok(())
+    }
+
+    fn read(&mut self, source: &str, file: Option<&Path>) -> Result<(), AsmError> {
+        for (index, line) in source.lines().enumerate() {
+            let location = Location {
+                file: file.map(Path::to_path_buf),
+                line: index + 1,
+            };
+            self.read_line(strip_comment(line), location)?;
+        }
+        Ok(())
+    }
+
+    fn read_line(&mut self, code: &str, location: Location) -> Result<(), AsmError> {
+        let Some((mut word, mut rest)) = next_word(code, 0) else {
+            return Ok(());
+        };
+        if let Some(name) = word.text.strip_suffix(':') {
+            let label = Token {
+                text: name.to_string(),
+                column: word.column,
+            };
+            let address = i64::try_from(self.address).unwrap_or(i64::MAX);
+            self.define(&location, &label, address)?;
+            let Some(next) = next_word(code, rest) else {
+                return Ok(());
+            };
+            (word, rest) = next;
+        }
+        let mnemonic = word.text.to_ascii_uppercase();
+        let invalid_operands = || {
+            location.error(
+                word.column,
+                AsmErrorKind::InvalidOperands {
+                    mnemonic: word.text.clone(),
+                },
+            )
+        };
+        match mnemonic.as_str() {
+            "DEFINE" => {
+                let (name, rest) = next_word(code, rest).ok_or_else(invalid_operands)?;
+                let value = trimmed(code, rest).ok_or_else(invalid_operands)?;
+                let value = self.eval(&location, &value, i64::MIN..=i64::MAX)?;
+                self.define(&location, &name, value)
+            }
+            "INCLUDE" => {
+                let name = trimmed(code, rest).ok_or_else(invalid_operands)?;
+                let path = name
+                    .text
+                    .strip_prefix('"')
+                    .and_then(|path| path.strip_suffix('"'))
+                    .ok_or_else(invalid_operands)?;
+                let path = location
+                    .file
+                    .as_deref()
+                    .and_then(Path::parent)
+                    .map_or_else(|| PathBuf::from(path), |dir| dir.join(path));
+                self.include(&path, Some((&location, name.column)))
+            }
+            _ => {
+                let operands = split_operands(code, rest).map_err(|column| {
+                    location.error(
+                        column,
+                        AsmErrorKind::InvalidOperands {
+                            mnemonic: word.text.clone(),
+                        },
+                    )
+                })?;
+                let size = match mnemonic.as_str() {
+                    "DB" => operands.len(),
+                    "DW" => operands.len() * 2,
+                    "LD" if operands.get(1).is_some_and(|op| long(op).is_some()) => 4,
+                    mnemonic if MNEMONICS.contains(&mnemonic) => 2,
+                    _ => {
+                        return Err(location.error(
+                            word.column,
+                            AsmErrorKind::UnknownMnemonic {
+                                mnemonic: word.text,
+                            },
+                        ))
+                    }
+                };
+                self.address += size;
+                if self.address > self.platform.ram_size() {
+                    return Err(location.error(
+                        word.column,
+                        AsmErrorKind::RomTooLarge {
+                            capacity: self.platform.ram_size() - PROGRAM_START,
+                        },
+                    ));
+                }
+                self.statements.push(Statement {
+                    location,
+                    mnemonic: word,
+                    operands,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn define(&mut self, location: &Location, name: &Token, value: i64) -> Result<(), AsmError> {
+        if !is_symbol(&name.text) {
+            return Err(location.error(
+                name.column,
+                AsmErrorKind::InvalidSymbol {
+                    name: name.text.clone(),
+                },
+            ));
+        }
+        if self.symbols.insert(name.text.clone(), value).is_some() {
+            return Err(location.error(
+                name.column,
+                AsmErrorKind::DuplicateSymbol {
+                    name: name.text.clone(),
+                },
+            ));
+        }
+        Ok(())
+    }
+
+    fn emit(&self) -> Result<Vec<u8>, AsmError> {
+        let mut rom = Vec::with_capacity(self.address - PROGRAM_START);
+        for statement in &self.statements {
+            let location = &statement.location;
+            match statement.mnemonic.text.to_ascii_uppercase().as_str() {
+                "DB" => {
+                    for value in &statement.operands {
+                        let value = self.eval(location, value, -0x80..=0xFF)?;
+                        rom.push(u8::try_from(value & 0xFF).unwrap_or_default());
+                    }
+                }
+                "DW" => {
+                    for value in &statement.operands {
+                        let value = self.eval(location, value, -0x8000..=0xFFFF)?;
+                        let word = u16::try_from(value & 0xFFFF).unwrap_or_default();
+                        rom.extend_from_slice(&word.to_be_bytes());
+                    }
+                }
+                mnemonic => {
+                    let inst = self.instruction(statement, mnemonic)?;
+                    let bytes = inst.to_bytes();
+                    if Instruction::decode(&bytes, self.platform) != Some(inst) {
+                        return Err(location.error(
+                            statement.mnemonic.column,
+                            AsmErrorKind::UnsupportedInstruction {
+                                mnemonic: statement.mnemonic.text.clone(),
+                                platform: self.platform,
+                            },
+                        ));
+                    }
+                    rom.extend_from_slice(&bytes);
+                }
+            }
+        }
+        Ok(rom)
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn instruction(&self, statement: &Statement, mnemonic: &str) -> Result<Instruction, AsmError> {
+        use Operand::{Long, Range, Register, Value, B, F, I, R};
+
+        let location = &statement.location;
+        let address = |token| {
+            self.eval(location, token, 0..=0xFFF)
+                .map(|value| u16::try_from(value).unwrap_or_default())
+        };
+        let byte = |token| {
+            self.eval(location, token, -0x80..=0xFF)
+                .map(|value| u8::try_from(value & 0xFF).unwrap_or_default())
+        };
+        let nibble = |token| {
+            self.eval(location, token, 0..=0xF)
+                .map(|value| u8::try_from(value).unwrap_or_default())
+        };
+        let operands: Vec<Operand> = statement.operands.iter().map(operand).collect();
+        let inst = match (mnemonic, operands.as_slice()) {
+            ("CLS", []) => Instruction::Cls00E0,
+            ("RET", []) => Instruction::SubroutineReturn00EE,
+            ("SYS", [Value(nnn)]) => Instruction::MachineCodeCall0NNN(address(nnn)?),
+            ("JP", [Value(nnn)]) => Instruction::Jump1NNN(address(nnn)?),
+            ("JP", [Register(0), Value(nnn)]) => Instruction::JumpWithOffsetBNNN(address(nnn)?),
+            ("CALL", [Value(nnn)]) => Instruction::SubroutineCall2NNN(address(nnn)?),
+            ("SE", [Register(x), Register(y)]) => Instruction::SkipIfEqual5XY0(*x, *y),
+            ("SE", [Register(x), Value(nn)]) => Instruction::SkipEqual3XNN(*x, byte(nn)?),
+            ("SNE", [Register(x), Register(y)]) => Instruction::SkipIfNotEqual9XY0(*x, *y),
+            ("SNE", [Register(x), Value(nn)]) => Instruction::SkipNotEqual4XNN(*x, byte(nn)?),
+            ("LD", [Register(x), Register(y)]) => Instruction::RegisterSet8XY0(*x, *y),
+            ("LD", [Register(x), Value(nn)]) => Instruction::SetVRegister6XNN(*x, byte(nn)?),
+            ("LD", [I, Value(nnn)]) => Instruction::SetIndexRegisterANNN(address(nnn)?.into()),
+            ("LD", [I, Long(nnnn)]) => {
+                let nnnn = self.eval(location, nnnn, 0..=0xFFFF)?;
+                Instruction::LongIndexF000NNNN(u16::try_from(nnnn).unwrap_or_default())
+            }
+            ("LD", [Register(x), Operand::Dt]) => Instruction::ReadDelayTimerFX07(*x),
+            ("LD", [Register(x), Operand::K]) => Instruction::GetKeyFX0A(*x),
+            ("LD", [Operand::Dt, Register(x)]) => Instruction::SetDelayTimerFX15(*x),
+            ("LD", [Operand::St, Register(x)]) => Instruction::SetSoundTimerFX18(*x),
+            ("LD", [F, Register(x)]) => Instruction::FontCharacterFX29(*x),
+            ("LD", [Operand::Hf, Register(x)]) => Instruction::BigFontCharacterFX30(*x),
+            ("LD", [B, Register(x)]) => Instruction::BinaryCodedDecimalConversionFX33(*x),
+            ("LD", [Operand::IndirectI, Register(x)]) => {
+                Instruction::StoreRegistersToMemoryFX55(*x)
+            }
+            ("LD", [Register(x), Operand::IndirectI]) => {
+                Instruction::LoadRegistersFromMemoryFX65(*x)
+            }
+            ("LD", [R, Register(x)]) => Instruction::StoreFlagsFX75(*x),
+            ("LD", [Register(x), R]) => Instruction::LoadFlagsFX85(*x),
+            ("ADD", [Register(x), Register(y)]) => Instruction::RegisterAdd8XY4(*x, *y),
+            ("ADD", [Register(x), Value(nn)]) => Instruction::Add7XNN(*x, byte(nn)?),
+            ("ADD", [I, Register(x)]) => Instruction::AddToIndexFX1E(*x),
+            ("OR", [Register(x), Register(y)]) => Instruction::Or8XY1(*x, *y),
+            ("AND", [Register(x), Register(y)]) => Instruction::BinaryAnd8XY2(*x, *y),
+            ("XOR", [Register(x), Register(y)]) => Instruction::Xor8XY3(*x, *y),
+            ("SUB", [Register(x), Register(y)]) => Instruction::RegisterSub8XY5(*x, *y),
+            ("SUBN", [Register(x), Register(y)]) => Instruction::RegisterSubRev8XY7(*x, *y),
+            ("SHR", [Register(x)]) => Instruction::ShiftRight8XY6(*x, *x),
+            ("SHR", [Register(x), Register(y)]) => Instruction::ShiftRight8XY6(*x, *y),
+            ("SHL", [Register(x)]) => Instruction::ShiftLeft8XYE(*x, *x),
+            ("SHL", [Register(x), Register(y)]) => Instruction::ShiftLeft8XYE(*x, *y),
+            ("RND", [Register(x), Value(nn)]) => Instruction::RandomCXNN(*x, byte(nn)?),
+            ("DRW", [Register(x), Register(y), Value(n)]) => match nibble(n)? {
+                0 if self.platform >= Platform::SuperChip => Instruction::BigSpriteDXY0(*x, *y),
+                n => Instruction::Dxyn(*x, *y, n.into()),
+            },
+            ("SKP", [Register(x)]) => Instruction::SkipIfKeyPressedEX9E(*x),
+            ("SKNP", [Register(x)]) => Instruction::SkipIfKeyNotPressedEXA1(*x),
+            ("SCD", [Value(n)]) => Instruction::ScrollDown00CN(nibble(n)?),
+            ("SCR", []) => Instruction::ScrollRight00FB,
+            ("SCL", []) => Instruction::ScrollLeft00FC,
+            ("EXIT", []) => Instruction::Exit00FD,
+            ("LOW", []) => Instruction::LowResolution00FE,
+            ("HIGH", []) => Instruction::HighResolution00FF,
+            ("SCU", [Value(n)]) => Instruction::ScrollUp00DN(nibble(n)?),
+            ("SAVE", [Range(x, y)]) => Instruction::StoreRangeToMemory5XY2(*x, *y),
+            ("LOAD", [Range(x, y)]) => Instruction::LoadRangeFromMemory5XY3(*x, *y),
+            ("PLANE", [Value(n)]) => Instruction::SelectPlanesFN01(nibble(n)?),
+            ("AUDIO", []) => Instruction::LoadAudioPatternF002,
+            ("PITCH", [Register(x)]) => Instruction::SetPitchFX3A(*x),
+            _ => {
+                return Err(location.error(
+                    statement.mnemonic.column,
+                    AsmErrorKind::InvalidOperands {
+                        mnemonic: statement.mnemonic.text.clone(),
+                    },
+                ))
+            }
+        };
+        Ok(inst)
+    }
+
+    /// Evaluates the sum or difference of numbers and symbols in `token`.
+    fn eval(
+        &self,
+        location: &Location,
+        token: &Token,
+        range: RangeInclusive<i64>,
+    ) -> Result<i64, AsmError> {
+        let text = token.text.as_str();
+        let error = |offset: usize, kind| {
+            location.error(token.column + text[..offset].chars().count(), kind)
+        };
+        let skip_whitespace = |pos: usize| {
+            pos + text[pos..]
+                .find(|c: char| !c.is_whitespace())
+                .unwrap_or(text.len() - pos)
+        };
+        let mut value: i64 = 0;
+        let mut subtract = false;
+        let mut pos = skip_whitespace(0);
+        if let Some(rest) = text[pos..].strip_prefix('-') {
+            subtract = true;
+            pos = skip_whitespace(text.len() - rest.len());
+        }
+        loop {
+            let len = text[pos..]
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(text.len() - pos);
+            let term = self
+                .term(&text[pos..pos + len])
+                .map_err(|kind| error(pos, kind))?;
+            value = if subtract {
+                value.saturating_sub(term)
+            } else {
+                value.saturating_add(term)
+            };
+            pos = skip_whitespace(pos + len);
+            subtract = match text[pos..].chars().next() {
+                None => break,
+                Some('+') => false,
+                Some('-') => true,
+                Some(_) => return Err(error(pos, AsmErrorKind::InvalidExpression)),
+            };
+            pos = skip_whitespace(pos + 1);
+        }
+        if !range.contains(&value) {
+            return Err(error(
+                0,
+                AsmErrorKind::OutOfRange {
+                    value,
+                    min: *range.start(),
+                    max: *range.end(),
+                },
+            ));
+        }
+        Ok(value)
+    }
+
+    fn term(&self, term: &str) -> Result<i64, AsmErrorKind> {
+        if term.starts_with(|c: char| c.is_ascii_digit()) {
+            let (digits, radix) = match term.get(..2) {
+                Some("0x" | "0X") => (&term[2..], 16),
+                Some("0b" | "0B") => (&term[2..], 2),
+                _ => (term, 10),
+            };
+            i64::from_str_radix(digits, radix).map_err(|_| AsmErrorKind::InvalidExpression)
+        } else if term.is_empty() {
+            Err(AsmErrorKind::InvalidExpression)
+        } else {
+            self.symbols
+                .get(term)
+                .copied()
+                .ok_or_else(|| AsmErrorKind::UndefinedSymbol {
+                    name: term.to_string(),
+                })
+        }
+    }
+}
+
+/// Removes the comment at the end of the line, if any.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Returns the 1-based column of the byte `offset` of `code`.
+fn column(code: &str, offset: usize) -> usize {
+    code[..offset].chars().count() + 1
+}
+
+/// Returns the first word of `code` from the byte `offset` on, and the byte offset after it.
+fn next_word(code: &str, offset: usize) -> Option<(Token, usize)> {
+    let start = offset + code[offset..].find(|c: char| !c.is_whitespace())?;
+    let end = code[start..]
+        .find(char::is_whitespace)
+        .map_or(code.len(), |len| start + len);
+    let word = Token {
+        text: code[start..end].to_string(),
+        column: column(code, start),
+    };
+    Some((word, end))
+}
+
+/// Returns the rest of `code` from the byte `offset` on without the
+/// surrounding whitespace, or `None` if it is blank.
+fn trimmed(code: &str, offset: usize) -> Option<Token> {
+    let text = code[offset..].trim();
+    let start = offset + code[offset..].find(|c: char| !c.is_whitespace())?;
+    Some(Token {
+        text: text.to_string(),
+        column: column(code, start),
+    })
+}
+
+/// Splits the comma-separated operands of `code` from the byte `offset` on.
+///
+/// Fails with the column of an empty operand.
+fn split_operands(code: &str, offset: usize) -> Result<Vec<Token>, usize> {
+    if code[offset..].trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut operands = Vec::new();
+    let mut start = offset;
+    for part in code[offset..].split(',') {
+        let column = column(code, start + part.len() - part.trim_start().len());
+        let text = part.trim();
+        if text.is_empty() {
+            return Err(column);
+        }
+        operands.push(Token {
+            text: text.to_string(),
+            column,
+        });
+        start += part.len() + 1;
+    }
+    Ok(operands)
+}
+
+/// Returns the register `VX` named by `text`, in either case.
+pub(crate) fn register(text: &str) -> Option<usize> {
+    let digit = text.strip_prefix(['V', 'v'])?;
+    let mut chars = digit.chars();
+    let x = chars.next()?.to_digit(16)?;
+    if chars.next().is_some() {
+        return None;
+    }
+    usize::try_from(x).ok()
+}
+
+/// Returns the address of a `LONG NNNN` operand.
+fn long(token: &Token) -> Option<Token> {
+    let (keyword, rest) = token.text.split_once(char::is_whitespace)?;
+    if !keyword.eq_ignore_ascii_case("LONG") {
+        return None;
+    }
+    let rest = rest.trim_start();
+    Some(Token {
+        text: rest.to_string(),
+        column: token.column + token.text[..token.text.len() - rest.len()].chars().count(),
+    })
+}
+
+fn operand(token: &Token) -> Operand {
+    let text = token.text.as_str();
+    if let Some(x) = register(text) {
+        return Operand::Register(x);
+    }
+    if let Some((x, y)) = text.split_once('-') {
+        if let (Some(x), Some(y)) = (register(x.trim()), register(y.trim())) {
+            return Operand::Range(x, y);
+        }
+    }
+    if let Some(nnnn) = long(token) {
+        return Operand::Long(nnnn);
+    }
+    match text.to_ascii_uppercase().as_str() {
+        "I" => Operand::I,
+        "[I]" => Operand::IndirectI,
+        "DT" => Operand::Dt,
+        "ST" => Operand::St,
+        "K" => Operand::K,
+        "F" => Operand::F,
+        "HF" => Operand::Hf,
+        "B" => Operand::B,
+        "R" => Operand::R,
+        _ => Operand::Value(token.clone()),
+    }
+}
+
+/// Returns true if `name` can be used as a label or a constant.
+fn is_symbol(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        && register(name).is_none()
+        && !KEYWORDS
+            .iter()
+            .any(|keyword| keyword.eq_ignore_ascii_case(name))
+}
@@ -0,0 +1,1150 @@
+//! Interactive debugging of Chip8 programs.
+//!
+//! A [`Debugger`] runs a [`Chip8`] instruction by instruction, stopping at
+//! breakpoints and watchpoints, and [`Debugger::repl`] drives it with
+//! line-oriented commands.
+//!
+//! ```
+//! use rusty_chip8::{
+//!     debugger::{Breakpoint, Debugger, Location, Stop, Watchpoint, Access},
+//!     Audio, Chip8, Graphics,
+//! };
+//!
+//! # struct Headless;
+//! # impl Graphics for Headless {}
+//! # impl Audio for Headless {
+//! #     fn start_beep(&mut self) {}
+//! #     fn stop_beep(&mut self) {}
+//! # }
+//! # fn main() -> rusty_chip8::Result<()> {
+//! # let (graphics, audio) = (&mut Headless, &mut Headless);
+//! // LD V0, 5; CALL 0x208; JP 0x204; ADD V1, 1; RET
+//! let rom = [0x60, 0x05, 0x22, 0x08, 0x12, 0x04, 0x00, 0x00, 0x71, 0x01, 0x00, 0xEE];
+//! let mut chip8 = Chip8::new(700);
+//! chip8.store_in_ram(rom)?;
+//! let mut debugger = Debugger::new(chip8);
+//!
+//! let breakpoint = debugger.add_breakpoint(Breakpoint::Address(0x208), None);
+//! assert_eq!(debugger.run_to_frame(10, graphics, audio)?, Stop::Breakpoint { id: breakpoint });
+//! assert_eq!(debugger.step_out(graphics, audio)?, Stop::Step);
+//! assert_eq!(debugger.chip8().pc(), 0x204);
+//!
+//! let watchpoint = debugger.add_watchpoint(Watchpoint {
+//!     location: Location::Register(1),
+//!     access: Access::Write,
+//! });
+//! debugger.set_pc(0x208)?;
+//! let stop = debugger.step_over(graphics, audio)?;
+//! assert_eq!(stop, Stop::Watchpoint { id: watchpoint, access: Access::ReadWrite });
+//! assert_eq!(debugger.chip8().registers()[1], 2);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{self, BufRead, Write},
+    ops::{Range, RangeInclusive},
+    str::FromStr,
+};
+
+use crate::{
+    asm::register, disasm, octo::number, Audio, Chip8, Graphics, Instruction, Result, FPS,
+};
+
+/// Number of frames the `continue` command of the REPL runs at most.
+const CONTINUE_FRAMES: u64 = 60 * FPS;
+/// Number of frames a run of the debugger lasts at most by default.
+const DEFAULT_FRAME_LIMIT: u64 = 60 * FPS;
+/// Number of bytes on a line of the memory dumps of the REPL.
+const DUMP_BYTES_PER_LINE: usize = 16;
+
+/// Place where the execution stops before running an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Stops when the program counter is at the address.
+    Address(usize),
+    /// Stops before the instructions whose opcode, with the bits of `mask`
+    /// only, equals `pattern`. See [`Breakpoint::opcode`].
+    Opcode {
+        /// The bits of the opcode to match.
+        pattern: u16,
+        /// The bits of the opcode compared to the pattern.
+        mask: u16,
+    },
+    /// Stops before every instruction, or with a condition, when the
+    /// condition becomes true.
+    Any,
+}
+
+impl Breakpoint {
+    /// Returns a breakpoint on the opcodes matching `pattern`, such as `DXYN`
+    /// or `F?33`.
+    ///
+    /// The pattern has 4 characters: the hex digits must equal the nibbles of
+    /// the opcode and any other character matches any nibble. Returns `None`
+    /// if the pattern is not 4 characters long.
+    #[must_use]
+    pub fn opcode(pattern: &str) -> Option<Self> {
+        if pattern.chars().count() != 4 {
+            return None;
+        }
+        let (pattern, mask) = pattern.chars().fold((0, 0), |(pattern, mask), c| {
+            c.to_digit(16)
+                .and_then(|digit| u16::try_from(digit).ok())
+                .map_or((pattern << 4, mask << 4), |digit| {
+                    (pattern << 4 | digit, mask << 4 | 0xF)
+                })
+        });
+        Some(Self::Opcode { pattern, mask })
+    }
+
+    fn matches(self, pc: usize, opcode: Option<u16>) -> bool {
+        match self {
+            Self::Address(address) => pc == address,
+            Self::Opcode { pattern, mask } => opcode.is_some_and(|opcode| opcode & mask == pattern),
+            Self::Any => true,
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Address(address) => write!(f, "{address:#05X}"),
+            Self::Opcode { pattern, mask } => {
+                f.write_str("op ")?;
+                for shift in [12, 8, 4, 0] {
+                    let nibble = |value: u16| (value >> shift) & 0xF;
+                    if nibble(*mask) == 0 {
+                        f.write_str("?")?;
+                    } else {
+                        write!(f, "{:X}", nibble(*pattern))?;
+                    }
+                }
+                Ok(())
+            }
+            Self::Any => f.write_str("any instruction"),
+        }
+    }
+}
+
+/// Location watched by a [`Watchpoint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    /// `len` bytes of RAM starting at `address`.
+    Memory {
+        /// The first watched byte.
+        address: usize,
+        /// Number of the watched bytes.
+        len: usize,
+    },
+    /// The register `VX`.
+    Register(usize),
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Memory { address, len: 1 } => write!(f, "[{address:#05X}]"),
+            Self::Memory { address, len } => {
+                write!(f, "[{address:#05X}..{:#05X}]", address + len)
+            }
+            Self::Register(x) => write!(f, "V{x:X}"),
+        }
+    }
+}
+
+/// Kind of access to a watched location.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// The location is read.
+    Read,
+    /// The location is written.
+    Write,
+    /// The location is read, written, or both.
+    ReadWrite,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::ReadWrite => "read/write",
+        })
+    }
+}
+
+/// Stops the execution after an instruction accesses a location.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    /// The watched location.
+    pub location: Location,
+    /// The accesses that stop the execution.
+    pub access: Access,
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.access, self.location)
+    }
+}
+
+/// Value compared by a [`Condition`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    /// The register `VX`.
+    Register(usize),
+    /// The index register.
+    Index,
+    /// The program counter.
+    Pc,
+    /// The number of return addresses on the stack.
+    StackDepth,
+    /// The delay timer.
+    DelayTimer,
+    /// The sound timer.
+    SoundTimer,
+    /// The byte of RAM at the address.
+    Memory(usize),
+    /// A constant.
+    Value(usize),
+}
+
+impl Operand {
+    /// Returns the value of the operand, or `None` for memory outside of the RAM.
+    fn value(self, chip8: &Chip8) -> Option<usize> {
+        match self {
+            Self::Register(x) => chip8.registers().get(x).copied().map(usize::from),
+            Self::Index => Some(chip8.index()),
+            Self::Pc => Some(chip8.pc()),
+            Self::StackDepth => Some(chip8.stack().len()),
+            Self::DelayTimer => Some(chip8.delay_timer().into()),
+            Self::SoundTimer => Some(chip8.sound_timer().into()),
+            Self::Memory(address) => chip8.ram().get(address).copied().map(usize::from),
+            Self::Value(value) => Some(value),
+        }
+    }
+}
+
+impl FromStr for Operand {
+    type Err = InvalidCondition;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let text = s.trim();
+        let operand = match text.to_ascii_uppercase().as_str() {
+            "I" => Some(Self::Index),
+            "PC" => Some(Self::Pc),
+            "SP" => Some(Self::StackDepth),
+            "DT" => Some(Self::DelayTimer),
+            "ST" => Some(Self::SoundTimer),
+            _ => text
+                .strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+                .and_then(|address| usize::try_from(number(address.trim())?).ok())
+                .map(Self::Memory)
+                .or_else(|| register(text).map(Self::Register))
+                .or_else(|| usize::try_from(number(text)?).ok().map(Self::Value)),
+        };
+        operand.ok_or_else(|| InvalidCondition {
+            condition: text.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Register(x) => write!(f, "V{x:X}"),
+            Self::Index => f.write_str("I"),
+            Self::Pc => f.write_str("PC"),
+            Self::StackDepth => f.write_str("SP"),
+            Self::DelayTimer => f.write_str("DT"),
+            Self::SoundTimer => f.write_str("ST"),
+            Self::Memory(address) => write!(f, "[{address:#05X}]"),
+            Self::Value(value) => write!(f, "{value:#X}"),
+        }
+    }
+}
+
+/// Comparison of the operands of a [`Condition`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    /// `==`
+    Equal,
+    /// `!=`
+    NotEqual,
+    /// `<`
+    Less,
+    /// `<=`
+    LessOrEqual,
+    /// `>`
+    Greater,
+    /// `>=`
+    GreaterOrEqual,
+}
+
+/// The comparisons with their symbols, the longer symbols first.
+const COMPARISONS: [(&str, Comparison); 6] = [
+    ("==", Comparison::Equal),
+    ("!=", Comparison::NotEqual),
+    ("<=", Comparison::LessOrEqual),
+    (">=", Comparison::GreaterOrEqual),
+    ("<", Comparison::Less),
+    (">", Comparison::Greater),
+];
+
+/// Comparison of registers, timers or memory, such as `V3 == 0x10`.
+///
+/// It is parsed from `LHS OP RHS`, where the operator is one of `==`, `!=`,
+/// `<`, `<=`, `>` and `>=`, and the operands are registers (`V0` to `VF`,
+/// `I`, `PC`, `SP` for the stack depth, `DT`, `ST`), bytes of RAM
+/// (`[0x300]`) or numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    /// The left-hand side.
+    pub lhs: Operand,
+    /// The comparison.
+    pub comparison: Comparison,
+    /// The right-hand side.
+    pub rhs: Operand,
+}
+
+impl Condition {
+    /// Returns true if the condition holds for the machine.
+    ///
+    /// Comparisons of memory outside of the RAM never hold.
+    #[must_use]
+    pub fn holds(&self, chip8: &Chip8) -> bool {
+        let (Some(lhs), Some(rhs)) = (self.lhs.value(chip8), self.rhs.value(chip8)) else {
+            return false;
+        };
+        match self.comparison {
+            Comparison::Equal => lhs == rhs,
+            Comparison::NotEqual => lhs != rhs,
+            Comparison::Less => lhs < rhs,
+            Comparison::LessOrEqual => lhs <= rhs,
+            Comparison::Greater => lhs > rhs,
+            Comparison::GreaterOrEqual => lhs >= rhs,
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = InvalidCondition;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (lhs, comparison, rhs) = COMPARISONS
+            .iter()
+            .find_map(|(symbol, comparison)| {
+                let (lhs, rhs) = s.split_once(symbol)?;
+                Some((lhs, *comparison, rhs))
+            })
+            .ok_or_else(|| InvalidCondition {
+                condition: s.trim().to_string(),
+            })?;
+        Ok(Self {
+            lhs: lhs.parse()?,
+            comparison,
+            rhs: rhs.parse()?,
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = COMPARISONS
+            .iter()
+            .find(|(_, comparison)| *comparison == self.comparison)
+            .map_or("", |(symbol, _)| symbol);
+        write!(f, "{} {symbol} {}", self.lhs, self.rhs)
+    }
+}
+
+/// Error returned when a [`Condition`] can not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidCondition {
+    condition: String,
+}
+
+impl fmt::Display for InvalidCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid condition `{}`", self.condition)
+    }
+}
+
+impl std::error::Error for InvalidCondition {}
+
+/// Reason why the execution stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// The requested step finished.
+    Step,
+    /// The breakpoint matched the instruction at the program counter, which
+    /// is not executed yet.
+    Breakpoint {
+        /// Identifier of the breakpoint.
+        id: usize,
+    },
+    /// The instruction just executed accessed the watched location.
+    Watchpoint {
+        /// Identifier of the watchpoint.
+        id: usize,
+        /// The access made by the instruction.
+        access: Access,
+    },
+    /// The requested frame was reached.
+    Frame,
+    /// The program waits for a key press with `FX0A`.
+    Key,
+    /// The program exited with `00FD`.
+    Exit,
+    /// The run lasted the frame limit, see [`Debugger::set_frame_limit`].
+    Limit,
+}
+
+#[derive(Debug)]
+struct BreakpointEntry {
+    breakpoint: Breakpoint,
+    condition: Option<Condition>,
+    /// Whether the condition held before the previous instruction.
+    held: bool,
+}
+
+/// Registers and memory accessed by the instructions, recorded by the
+/// machine for the watchpoints.
+#[derive(Debug, Default)]
+pub(crate) struct Accesses {
+    /// Bit X is set if `VX` is read.
+    read_registers: u16,
+    /// Bit X is set if `VX` is written.
+    written_registers: u16,
+    read_memory: Range<usize>,
+    written_memory: Range<usize>,
+}
+
+impl Accesses {
+    /// Records a read of the registers `VX` for X in `registers`.
+    pub(crate) fn read_registers(&mut self, registers: RangeInclusive<usize>) {
+        self.read_registers |= register_mask(registers);
+    }
+
+    /// Records a write to the registers `VX` for X in `registers`.
+    pub(crate) fn write_registers(&mut self, registers: RangeInclusive<usize>) {
+        self.written_registers |= register_mask(registers);
+    }
+
+    /// Records a read of the memory in `range`.
+    pub(crate) fn read_memory(&mut self, range: Range<usize>) {
+        self.read_memory = span(&self.read_memory, range);
+    }
+
+    /// Records a write to the memory in `range`.
+    pub(crate) fn write_memory(&mut self, range: Range<usize>) {
+        self.written_memory = span(&self.written_memory, range);
+    }
+
+    /// Returns the access to `location`, if any.
+    fn of(&self, location: Location) -> Option<Access> {
+        let (read, written) = match location {
+            Location::Memory { address, len } => {
+                let overlaps = |range: &Range<usize>| {
+                    !range.is_empty() && range.start < address + len && address < range.end
+                };
+                (overlaps(&self.read_memory), overlaps(&self.written_memory))
+            }
+            Location::Register(x) => (
+                self.read_registers >> x & 1 != 0,
+                self.written_registers >> x & 1 != 0,
+            ),
+        };
+        match (read, written) {
+            (true, true) => Some(Access::ReadWrite),
+            (true, false) => Some(Access::Read),
+            (false, true) => Some(Access::Write),
+            (false, false) => None,
+        }
+    }
+}
+
+/// Returns the mask with bit X set for X in `registers`.
+fn register_mask(registers: RangeInclusive<usize>) -> u16 {
+    registers.fold(0, |mask, x| mask | 1 << x)
+}
+
+/// Returns the smallest range containing both `range` and `other`.
+fn span(range: &Range<usize>, other: Range<usize>) -> Range<usize> {
+    if range.is_empty() {
+        other
+    } else if other.is_empty() {
+        range.clone()
+    } else {
+        range.start.min(other.start)..range.end.max(other.end)
+    }
+}
+
+/// Wrapper around a [`Chip8`] running it until breakpoints and watchpoints.
+///
+/// Breakpoints are checked before every instruction, except the first one
+/// of each run, so that the execution can go on from a breakpoint.
+/// Watchpoints are checked after every instruction, against the registers
+/// and memory it accessed. Breakpoints and watchpoints share the same
+/// identifiers.
+#[derive(Debug)]
+pub struct Debugger {
+    chip8: Chip8,
+    breakpoints: BTreeMap<usize, BreakpointEntry>,
+    watchpoints: BTreeMap<usize, Watchpoint>,
+    next_id: usize,
+    frame_limit: Option<u64>,
+}
+
+impl Debugger {
+    /// Returns a debugger without breakpoints and watchpoints.
+    #[must_use]
+    pub const fn new(chip8: Chip8) -> Self {
+        Self {
+            chip8,
+            breakpoints: BTreeMap::new(),
+            watchpoints: BTreeMap::new(),
+            next_id: 1,
+            frame_limit: Some(DEFAULT_FRAME_LIMIT),
+        }
+    }
+
+    /// Returns the debugged machine.
+    #[must_use]
+    pub const fn chip8(&self) -> &Chip8 {
+        &self.chip8
+    }
+
+    /// Returns the debugged machine for modification.
+    pub const fn chip8_mut(&mut self) -> &mut Chip8 {
+        &mut self.chip8
+    }
+
+    /// Returns the debugged machine, dropping the debugger.
+    #[must_use]
+    pub fn into_inner(mut self) -> Chip8 {
+        self.chip8.record_accesses(false);
+        self.chip8
+    }
+
+    /// Sets the program counter of the machine, see [`Chip8::set_pc`].
+    ///
+    /// # Errors
+    ///
+    /// See [`Chip8::set_pc`].
+    pub fn set_pc(&mut self, pc: usize) -> Result<()> {
+        self.chip8.set_pc(pc)
+    }
+
+    /// Returns the number of frames a run lasts at most, see
+    /// [`Debugger::set_frame_limit`].
+    #[must_use]
+    pub const fn frame_limit(&self) -> Option<u64> {
+        self.frame_limit
+    }
+
+    /// Sets the number of frames a step over, a step out or a run to a
+    /// frame lasts at most before stopping with [`Stop::Limit`], or `None`
+    /// to never stop for it. It defaults to a minute of emulated time, so
+    /// that a subroutine never returning does not hang the debugger.
+    pub const fn set_frame_limit(&mut self, limit: Option<u64>) {
+        self.frame_limit = limit;
+    }
+
+    /// Adds a breakpoint and returns its identifier.
+    ///
+    /// With a condition, it only stops while the condition holds, or for
+    /// [`Breakpoint::Any`], when the condition becomes true.
+    pub fn add_breakpoint(
+        &mut self,
+        breakpoint: Breakpoint,
+        condition: Option<Condition>,
+    ) -> usize {
+        let id = self.next_id();
+        self.breakpoints.insert(
+            id,
+            BreakpointEntry {
+                breakpoint,
+                condition,
+                held: false,
+            },
+        );
+        id
+    }
+
+    /// Adds a watchpoint and returns its identifier.
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> usize {
+        let id = self.next_id();
+        self.watchpoints.insert(id, watchpoint);
+        id
+    }
+
+    /// Removes the breakpoint or watchpoint. Returns false if there is none
+    /// with the identifier.
+    pub fn remove(&mut self, id: usize) -> bool {
+        self.breakpoints.remove(&id).is_some() || self.watchpoints.remove(&id).is_some()
+    }
+
+    /// Returns the breakpoints with their identifiers and conditions.
+    pub fn breakpoints(&self) -> impl Iterator<Item = (usize, Breakpoint, Option<Condition>)> + '_ {
+        self.breakpoints
+            .iter()
+            .map(|(id, entry)| (*id, entry.breakpoint, entry.condition))
+    }
+
+    /// Returns the watchpoints with their identifiers.
+    pub fn watchpoints(&self) -> impl Iterator<Item = (usize, Watchpoint)> + '_ {
+        self.watchpoints
+            .iter()
+            .map(|(id, watchpoint)| (*id, *watchpoint))
+    }
+
+    /// Executes one instruction, stepping into subroutine calls.
+    ///
+    /// # Errors
+    ///
+    /// See [`Chip8::step`].
+    pub fn step(&mut self, graphics: &mut impl Graphics, audio: &mut impl Audio) -> Result<Stop> {
+        self.run(graphics, audio, |_| Some(Stop::Step))
+    }
+
+    /// Executes one instruction, running `2NNN` subroutine calls until they
+    /// return or the [frame limit](Debugger::set_frame_limit) is reached.
+    ///
+    /// # Errors
+    ///
+    /// See [`Chip8::step`].
+    pub fn step_over(
+        &mut self,
+        graphics: &mut impl Graphics,
+        audio: &mut impl Audio,
+    ) -> Result<Stop> {
+        let pc = self.chip8.pc();
+        if !matches!(
+            self.next_instruction(),
+            Some(Instruction::SubroutineCall2NNN(_))
+        ) {
+            return self.step(graphics, audio);
+        }
+        let depth = self.chip8.stack().len();
+        self.run(graphics, audio, |chip8| {
+            if chip8.is_waiting_for_key() {
+                Some(Stop::Key)
+            } else if chip8.stack().len() < depth
+                || chip8.stack().len() == depth && chip8.pc() == pc + 2
+            {
+                Some(Stop::Step)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Runs until the current subroutine returns or the
+    /// [frame limit](Debugger::set_frame_limit) is reached. Outside of
+    /// subroutines, it executes one instruction.
+    ///
+    /// # Errors
+    ///
+    /// See [`Chip8::step`].
+    pub fn step_out(
+        &mut self,
+        graphics: &mut impl Graphics,
+        audio: &mut impl Audio,
+    ) -> Result<Stop> {
+        let depth = self.chip8.stack().len();
+        if depth == 0 {
+            return self.step(graphics, audio);
+        }
+        self.run(graphics, audio, |chip8| {
+            if chip8.is_waiting_for_key() {
+                Some(Stop::Key)
+            } else {
+                (chip8.stack().len() < depth).then_some(Stop::Step)
+            }
+        })
+    }
+
+    /// Runs until the start of frame `frame`, see [`Chip8::frame_count`],
+    /// or until the [frame limit](Debugger::set_frame_limit) is reached.
+    ///
+    /// Unlike the steps, it keeps running while the program waits for a key,
+    /// since the timers still run.
+    ///
+    /// # Errors
+    ///
+    /// See [`Chip8::step`].
+    pub fn run_to_frame(
+        &mut self,
+        frame: u64,
+        graphics: &mut impl Graphics,
+        audio: &mut impl Audio,
+    ) -> Result<Stop> {
+        if self.chip8.frame_count() >= frame {
+            return Ok(Stop::Frame);
+        }
+        self.run(graphics, audio, |chip8| {
+            (chip8.frame_count() >= frame).then_some(Stop::Frame)
+        })
+    }
+
+    /// Executes instructions until a breakpoint, a watchpoint or `done`
+    /// returns the reason to stop, or the frame limit is reached.
+    pub(crate) fn run(
+        &mut self,
+        graphics: &mut impl Graphics,
+        audio: &mut impl Audio,
+        mut done: impl FnMut(&Chip8) -> Option<Stop>,
+    ) -> Result<Stop> {
+        let end = self
+            .frame_limit
+            .map(|limit| self.chip8.frame_count().saturating_add(limit));
+        let mut first = true;
+        loop {
+            if self.chip8.has_exited() {
+                return Ok(Stop::Exit);
+            }
+            if let Some(id) = self.check_breakpoints(first) {
+                return Ok(Stop::Breakpoint { id });
+            }
+            first = false;
+            self.chip8.record_accesses(true);
+            let stepped = self.chip8.step(graphics, audio);
+            let accesses = self.chip8.take_accesses();
+            stepped?;
+            let watchpoint = self.watchpoints.iter().find_map(|(id, watchpoint)| {
+                let access = accesses.of(watchpoint.location)?;
+                let watched = match watchpoint.access {
+                    Access::Read => access != Access::Write,
+                    Access::Write => access != Access::Read,
+                    Access::ReadWrite => true,
+                };
+                watched.then_some((*id, access))
+            });
+            if let Some((id, access)) = watchpoint {
+                return Ok(Stop::Watchpoint { id, access });
+            }
+            if let Some(stop) = done(&self.chip8) {
+                return Ok(stop);
+            }
+            if end.is_some_and(|end| self.chip8.frame_count() >= end) {
+                return Ok(Stop::Limit);
+            }
+        }
+    }
+
+    /// Returns the first breakpoint matching the next instruction, unless
+    /// `skip` is set. The conditions are evaluated either way.
+    fn check_breakpoints(&mut self, skip: bool) -> Option<usize> {
+        if self.chip8.is_waiting_for_key() {
+            return None;
+        }
+        let pc = self.chip8.pc();
+        let opcode = self
+            .chip8
+            .read_memory(pc, 2)
+            .ok()
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+        let mut hit = None;
+        for (id, entry) in &mut self.breakpoints {
+            let holds = entry
+                .condition
+                .is_none_or(|condition| condition.holds(&self.chip8));
+            let edge = entry.breakpoint == Breakpoint::Any && entry.condition.is_some();
+            let stops = entry.breakpoint.matches(pc, opcode) && holds && !(edge && entry.held);
+            entry.held = holds;
+            if stops && !skip && hit.is_none() {
+                hit = Some(*id);
+            }
+        }
+        hit
+    }
+
+    /// Returns the instruction at the program counter.
+    fn next_instruction(&self) -> Option<Instruction> {
+        let ram = self.chip8.ram();
+        let pc = self.chip8.pc();
+        Instruction::decode(ram.get(pc..ram.len().min(pc + 4))?, self.chip8.platform())
+    }
+
+    const fn next_id(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Runs a line-oriented debugger front-end until `quit` or the end of `input`.
+    ///
+    /// Commands are read from `input`, one per line, and their results are
+    /// written to `output`; `help` lists them. An empty line repeats the
+    /// previous command.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of reading `input` or writing `output`. The errors
+    /// of the machine and of the commands are written to `output`.
+    pub fn repl(
+        &mut self,
+        mut input: impl BufRead,
+        mut output: impl Write,
+        graphics: &mut impl Graphics,
+        audio: &mut impl Audio,
+    ) -> io::Result<()> {
+        self.write_location(&mut output)?;
+        let mut previous = String::new();
+        loop {
+            write!(output, "(chip8) ")?;
+            output.flush()?;
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let line = line.trim();
+            let command = if line.is_empty() {
+                previous.clone()
+            } else {
+                line.to_string()
+            };
+            if command.is_empty() {
+                continue;
+            }
+            if !self.command(&command, &mut output, graphics, audio)? {
+                return Ok(());
+            }
+            previous = command;
+        }
+    }
+
+    /// Executes a command of the REPL. Returns false on `quit`.
+    #[allow(clippy::too_many_lines)]
+    fn command(
+        &mut self,
+        line: &str,
+        output: &mut impl Write,
+        graphics: &mut impl Graphics,
+        audio: &mut impl Audio,
+    ) -> io::Result<bool> {
+        let (name, args) = line
+            .split_once(char::is_whitespace)
+            .map_or((line, ""), |(name, args)| (name, args.trim()));
+        let invalid =
+            |output: &mut dyn Write| writeln!(output, "invalid command `{line}`, see `help`");
+        match name {
+            "s" | "step" | "n" | "next" | "finish" | "c" | "continue" | "frame" => {
+                let count = match name {
+                    "s" | "step" => argument(args, Some(1)),
+                    "c" | "continue" => argument(args, Some(CONTINUE_FRAMES)),
+                    "frame" => argument(args, None),
+                    _ => args.is_empty().then_some(0),
+                };
+                let Some(count) = count else {
+                    invalid(output)?;
+                    return Ok(true);
+                };
+                let result = match name {
+                    "s" | "step" => (0..count).try_fold(Stop::Step, |stop, _| {
+                        if stop == Stop::Step {
+                            self.step(graphics, audio)
+                        } else {
+                            Ok(stop)
+                        }
+                    }),
+                    "n" | "next" => self.step_over(graphics, audio),
+                    "finish" => self.step_out(graphics, audio),
+                    "frame" => self.run_to_frame(count, graphics, audio),
+                    _ => {
+                        let frame = self.chip8.frame_count().saturating_add(count);
+                        self.run_to_frame(frame, graphics, audio)
+                    }
+                };
+                match result {
+                    Ok(stop) => self.write_stop(output, stop)?,
+                    Err(err) => writeln!(output, "error: {err}")?,
+                }
+                self.write_location(output)?;
+            }
+            "b" | "break" => match parse_breakpoint(args) {
+                Some((breakpoint, condition)) => {
+                    let id = self.add_breakpoint(breakpoint, condition);
+                    writeln!(output, "breakpoint {id} at {breakpoint}")?;
+                }
+                None => invalid(output)?,
+            },
+            "watch" | "rwatch" | "awatch" => match parse_location(args) {
+                Some(location) => {
+                    let access = match name {
+                        "watch" => Access::Write,
+                        "rwatch" => Access::Read,
+                        _ => Access::ReadWrite,
+                    };
+                    let watchpoint = Watchpoint { location, access };
+                    let id = self.add_watchpoint(watchpoint);
+                    writeln!(output, "watchpoint {id} on {watchpoint}")?;
+                }
+                None => invalid(output)?,
+            },
+            "d" | "delete" => match argument(args, None) {
+                Some(id) => {
+                    let removed = usize::try_from(id).is_ok_and(|id| self.remove(id));
+                    if !removed {
+                        writeln!(output, "no breakpoint or watchpoint {id}")?;
+                    }
+                }
+                None => invalid(output)?,
+            },
+            "info" => self.write_breakpoints(output)?,
+            "r" | "regs" => self.write_registers(output)?,
+            "bt" | "stack" => {
+                for (depth, address) in self.chip8.stack().iter().rev().enumerate() {
+                    writeln!(output, "#{depth} {address:#05X}")?;
+                }
+            }
+            "x" => match parse_range(args, self.chip8.index(), DUMP_BYTES_PER_LINE) {
+                Some((address, len)) => self.write_memory(output, address, len)?,
+                None => invalid(output)?,
+            },
+            "disas" => match parse_range(args, self.chip8.pc(), 8) {
+                Some((address, count)) => self.write_disassembly(output, address, count)?,
+                None => invalid(output)?,
+            },
+            "set" => {
+                let assigned = args
+                    .rsplit_once(char::is_whitespace)
+                    .and_then(|(target, value)| {
+                        let target = target.parse().ok()?;
+                        let value = usize::try_from(number(value)?).ok()?;
+                        self.assign(target, value)
+                    });
+                match assigned {
+                    Some(Ok(())) => {}
+                    Some(Err(err)) => writeln!(output, "error: {err}")?,
+                    None => invalid(output)?,
+                }
+            }
+            "press" | "release" => {
+                let key = argument(args, None).and_then(|key| u8::try_from(key).ok());
+                let result = key.map(|key| {
+                    if name == "press" {
+                        self.chip8.handle_key_pressed(key)
+                    } else {
+                        self.chip8.handle_key_released(key)
+                    }
+                });
+                match result {
+                    Some(Ok(())) => {}
+                    Some(Err(err)) => writeln!(output, "error: {err}")?,
+                    None => invalid(output)?,
+                }
+            }
+            "h" | "help" => output.write_all(HELP.as_bytes())?,
+            "q" | "quit" => return Ok(false),
+            _ => invalid(output)?,
+        }
+        Ok(true)
+    }
+
+    /// Sets a register or a byte of RAM. Returns `None` if the operand can
+    /// not be set.
+    fn assign(&mut self, target: Operand, value: usize) -> Option<Result<()>> {
+        let byte = u8::try_from(value & 0xFF).unwrap_or_default();
+        let result = match target {
+            Operand::Register(x) => {
+                self.chip8.registers_mut()[x] = byte;
+                Ok(())
+            }
+            Operand::Index => self.chip8.set_index(value),
+            Operand::Pc => self.chip8.set_pc(value),
+            Operand::DelayTimer => {
+                self.chip8.set_delay_timer(byte);
+                Ok(())
+            }
+            Operand::SoundTimer => {
+                self.chip8.set_sound_timer(byte);
+                Ok(())
+            }
+            Operand::Memory(address) => self.chip8.write_memory(address, &[byte]),
+            Operand::StackDepth | Operand::Value(_) => return None,
+        };
+        Some(result)
+    }
+
+    fn write_stop(&self, output: &mut impl Write, stop: Stop) -> io::Result<()> {
+        match stop {
+            Stop::Step => Ok(()),
+            Stop::Breakpoint { id } => writeln!(output, "breakpoint {id}"),
+            Stop::Watchpoint { id, access } => writeln!(output, "watchpoint {id}: {access}"),
+            Stop::Frame => writeln!(output, "frame {}", self.chip8.frame_count()),
+            Stop::Key => writeln!(output, "waiting for a key"),
+            Stop::Exit => writeln!(output, "program exited"),
+            Stop::Limit => writeln!(
+                output,
+                "stopped at the frame limit, frame {}",
+                self.chip8.frame_count()
+            ),
+        }
+    }
+
+    /// Writes the instruction at the program counter.
+    fn write_location(&self, output: &mut impl Write) -> io::Result<()> {
+        self.write_disassembly(output, self.chip8.pc(), 1)
+    }
+
+    fn write_disassembly(
+        &self,
+        output: &mut impl Write,
+        address: usize,
+        count: usize,
+    ) -> io::Result<()> {
+        let ram = self.chip8.ram();
+        let bytes = ram.get(address..).unwrap_or_default();
+        for (offset, inst) in disasm::decode(bytes, self.chip8.platform()).take(count) {
+            let address = address + offset;
+            let marker = if address == self.chip8.pc() {
+                "=>"
+            } else {
+                "  "
+            };
+            if let Some(inst) = inst {
+                writeln!(output, "{marker} {address:#05X}  {inst}")?;
+            } else {
+                let data: Vec<String> = bytes[offset..bytes.len().min(offset + 2)]
+                    .iter()
+                    .map(|byte| format!("{byte:#04X}"))
+                    .collect();
+                writeln!(output, "{marker} {address:#05X}  DB {}", data.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_registers(&self, output: &mut impl Write) -> io::Result<()> {
+        let chip8 = &self.chip8;
+        for (row, values) in chip8.registers().chunks(8).enumerate() {
+            let line: Vec<String> = values
+                .iter()
+                .enumerate()
+                .map(|(x, value)| format!("V{:X}={value:02X}", row * 8 + x))
+                .collect();
+            writeln!(output, "{}", line.join(" "))?;
+        }
+        writeln!(
+            output,
+            "I={:#05X} PC={:#05X} SP={} DT={} ST={} frame={}",
+            chip8.index(),
+            chip8.pc(),
+            chip8.stack().len(),
+            chip8.delay_timer(),
+            chip8.sound_timer(),
+            chip8.frame_count()
+        )
+    }
+
+    fn write_memory(&self, output: &mut impl Write, address: usize, len: usize) -> io::Result<()> {
+        let ram = self.chip8.ram();
+        let end = ram.len().min(address.saturating_add(len));
+        let bytes = ram.get(address..end).unwrap_or_default();
+        for (line, chunk) in bytes.chunks(DUMP_BYTES_PER_LINE).enumerate() {
+            let data: Vec<String> = chunk.iter().map(|byte| format!("{byte:02X}")).collect();
+            let start = address + line * DUMP_BYTES_PER_LINE;
+            writeln!(output, "{start:#05X}  {}", data.join(" "))?;
+        }
+        Ok(())
+    }
+
+    fn write_breakpoints(&self, output: &mut impl Write) -> io::Result<()> {
+        for (id, breakpoint, condition) in self.breakpoints() {
+            match condition {
+                Some(condition) => writeln!(output, "{id}  break {breakpoint} if {condition}")?,
+                None => writeln!(output, "{id}  break {breakpoint}")?,
+            }
+        }
+        for (id, watchpoint) in self.watchpoints() {
+            writeln!(output, "{id}  watch {watchpoint}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Usage of the REPL commands.
+const HELP: &str = "\
+step [N]          s  execute N instructions, stepping into subroutines
+next              n  execute an instruction, stepping over subroutine calls
+finish               run until the current subroutine returns
+continue [F]      c  run until a breakpoint or watchpoint, at most F frames
+frame F              run until frame F
+break ADDR [if C] b  stop at an address
+break op PATTERN [if C]
+                     stop at the opcodes matching e.g. DXYN or F?33
+break if C           stop when the condition, e.g. V3 == 0x10, becomes true
+watch LOC            stop after writes to e.g. V3, 0x300 or 0x300 16
+rwatch LOC           stop after reads
+awatch LOC           stop after reads and writes
+delete ID         d  remove a breakpoint or watchpoint
+info                 list the breakpoints and watchpoints
+regs              r  show the registers
+stack             bt show the return addresses, the innermost first
+x [ADDR [LEN]]       dump memory, from the index register by default
+disas [ADDR [N]]     disassemble, from the program counter by default
+set TARGET VALUE     set V0-VF, I, PC, DT, ST or a byte of memory, e.g. [0x300]
+press KEY            press a key of the keypad
+release KEY          release a key
+quit              q  leave the debugger
+";
+
+/// Parses a number argument, or returns `default` if there is none.
+fn argument(text: &str, default: Option<u64>) -> Option<u64> {
+    if text.is_empty() {
+        return default;
+    }
+    u64::try_from(number(text)?).ok()
+}
+
+/// Parses `[op] LOCATION [if CONDITION]` or `if CONDITION`.
+fn parse_breakpoint(text: &str) -> Option<(Breakpoint, Option<Condition>)> {
+    let (location, condition) = text.strip_prefix("if ").map_or_else(
+        || {
+            text.split_once(" if ")
+                .map_or((text, None), |(location, condition)| {
+                    (location.trim(), Some(condition))
+                })
+        },
+        |condition| ("", Some(condition)),
+    );
+    let condition = condition.map(str::parse).transpose().ok()?;
+    let breakpoint = if location.is_empty() {
+        Breakpoint::Any
+    } else if let Some(pattern) = location.strip_prefix("op ") {
+        Breakpoint::opcode(pattern.trim())?
+    } else {
+        Breakpoint::Address(usize::try_from(number(location)?).ok()?)
+    };
+    Some((breakpoint, condition))
+}
+
+/// Parses a register or `ADDR [LEN]`.
+fn parse_location(text: &str) -> Option<Location> {
+    if text.is_empty() {
+        return None;
+    }
+    register(text).map(Location::Register).or_else(|| {
+        let (address, len) = parse_range(text, 0, 1)?;
+        (len > 0).then_some(Location::Memory { address, len })
+    })
+}
+
+/// Parses `[START [LEN]]`, defaulting to `start` and `len`.
+fn parse_range(text: &str, start: usize, len: usize) -> Option<(usize, usize)> {
+    let mut parts = text.split_whitespace();
+    let mut next = |default: usize| {
+        parts
+            .next()
+            .map_or(Some(default), |part| usize::try_from(number(part)?).ok())
+    };
+    let range = (next(start)?, next(len)?);
+    parts.next().is_none().then_some(range)
+}
@@ -0,0 +1,201 @@
+//! Decoding of Chip8 programs into instructions and assembly listings.
+//!
+//! The decoding is the one the emulator executes, so the listings always
+//! agree with what [`Chip8`](crate::Chip8) does.
+//!
+//! ```
+//! use rusty_chip8::{disasm, Platform};
+//!
+//! let rom = [0x63, 0x12, 0xD0, 0x15, 0x12, 0x02];
+//! let listing = disasm::disassemble(&rom, Platform::Chip8);
+//! let text = listing.to_string();
+//! assert!(text.contains("LD V3, 0x12"));
+//! assert!(text.contains("DRW V0, V1, 5"));
+//! assert!(text.contains("JP L202"));
+//! ```
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use crate::{Instruction, Platform, PROGRAM_START};
+
+/// Maximum number of data bytes on a listing line.
+const DATA_BYTES_PER_LINE: usize = 8;
+
+/// Decodes `bytes` from start to end, as if they were all instructions.
+///
+/// Yields the offset of each instruction, and `None` for the 2 bytes long
+/// words that are not an instruction of the platform. Use [`disassemble`]
+/// to tell code from data.
+pub fn decode(
+    bytes: &[u8],
+    platform: Platform,
+) -> impl Iterator<Item = (usize, Option<Instruction>)> + '_ {
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        if offset >= bytes.len() {
+            return None;
+        }
+        let inst = Instruction::decode(&bytes[offset..], platform);
+        let item = (offset, inst);
+        offset += inst.map_or(2, Instruction::size);
+        Some(item)
+    })
+}
+
+/// Disassembles a program loaded at [`PROGRAM_START`].
+///
+/// Only the instructions reachable from the start of the program, following
+/// the jumps, calls and skips, are decoded as code; the rest is data.
+#[must_use]
+pub fn disassemble(rom: &[u8], platform: Platform) -> Disassembly {
+    let mut disassembly = Disassembly {
+        origin: PROGRAM_START,
+        rom: rom.to_vec(),
+        code: BTreeMap::new(),
+        labels: BTreeSet::new(),
+    };
+    let mut pending = vec![PROGRAM_START];
+    while let Some(address) = pending.pop() {
+        if disassembly.code.contains_key(&address) {
+            continue;
+        }
+        let Some(inst) = disassembly.decode_at(address, platform) else {
+            continue;
+        };
+        disassembly.code.insert(address, inst);
+        let next = address + inst.size();
+        if let Some(target) = inst.target() {
+            disassembly.labels.insert(target);
+        }
+        match inst {
+            Instruction::Jump1NNN(target) => pending.push(target.into()),
+            Instruction::SubroutineCall2NNN(target) => {
+                pending.push(next);
+                pending.push(target.into());
+            }
+            Instruction::SubroutineReturn00EE
+            | Instruction::Exit00FD
+            | Instruction::JumpWithOffsetBNNN(_) => {}
+            Instruction::SkipEqual3XNN(..)
+            | Instruction::SkipNotEqual4XNN(..)
+            | Instruction::SkipIfEqual5XY0(..)
+            | Instruction::SkipIfNotEqual9XY0(..)
+            | Instruction::SkipIfKeyPressedEX9E(_)
+            | Instruction::SkipIfKeyNotPressedEXA1(_) => {
+                pending.push(next);
+                let skipped = disassembly
+                    .decode_at(next, platform)
+                    .map_or(2, Instruction::size);
+                pending.push(next + skipped);
+            }
+            _ => pending.push(next),
+        }
+    }
+    // Only the addresses starting a line of the listing can be labelled.
+    let mut inside = BTreeSet::new();
+    let mut end = 0;
+    for (address, inst) in &disassembly.code {
+        if *address >= end {
+            end = address + inst.size();
+            inside.extend(address + 1..end);
+        }
+    }
+    let end = disassembly.origin + rom.len();
+    disassembly
+        .labels
+        .retain(|address| (disassembly.origin..end).contains(address) && !inside.contains(address));
+    disassembly
+}
+
+/// Program split into code and data, printed as a labelled assembly listing.
+///
+/// The alternate format `{:#}` leaves out the addresses and opcodes, giving
+/// a source that [`asm::assemble`](crate::asm::assemble) turns back into the
+/// same program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Disassembly {
+    origin: usize,
+    rom: Vec<u8>,
+    code: BTreeMap<usize, Instruction>,
+    labels: BTreeSet<usize>,
+}
+
+impl Disassembly {
+    /// Returns the reachable instructions by address.
+    pub fn instructions(&self) -> impl Iterator<Item = (usize, Instruction)> + '_ {
+        self.code.iter().map(|(address, inst)| (*address, *inst))
+    }
+
+    /// Returns the instruction at `address`, if it is reachable code.
+    #[must_use]
+    pub fn instruction_at(&self, address: usize) -> Option<Instruction> {
+        self.code.get(&address).copied()
+    }
+
+    /// Returns the addresses jumped to, called or pointed to by the index
+    /// register, which are labelled in the listing.
+    pub fn labels(&self) -> impl Iterator<Item = usize> + '_ {
+        self.labels.iter().copied()
+    }
+
+    fn decode_at(&self, address: usize, platform: Platform) -> Option<Instruction> {
+        let offset = address.checked_sub(self.origin)?;
+        Instruction::decode(self.rom.get(offset..)?, platform)
+    }
+
+    fn label(&self, address: usize) -> String {
+        if self.labels.contains(&address) {
+            format!("L{address:03X}")
+        } else {
+            format!("{address:#05X}")
+        }
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let end = self.origin + self.rom.len();
+        let mut address = self.origin;
+        while address < end {
+            if self.labels.contains(&address) {
+                writeln!(f, "{}:", self.label(address))?;
+            }
+            let bytes = &self.rom[address - self.origin..];
+            if let Some(inst) = self.code.get(&address) {
+                if f.alternate() {
+                    write!(f, "    ")?;
+                } else {
+                    let opcode = bytes[..inst.size()]
+                        .iter()
+                        .fold(0, |opcode, byte| opcode << 8 | u32::from(*byte));
+                    let opcode = format!("{opcode:0width$X}", width = inst.size() * 2);
+                    write!(f, "    {address:#05X}  {opcode:<8}  ")?;
+                }
+                inst.write_mnemonic(f, |target| self.label(target))?;
+                writeln!(f)?;
+                address += inst.size();
+                continue;
+            }
+            let len = (1..bytes.len().min(DATA_BYTES_PER_LINE))
+                .find(|len| {
+                    let next = address + len;
+                    self.code.contains_key(&next) || self.labels.contains(&next)
+                })
+                .unwrap_or_else(|| bytes.len().min(DATA_BYTES_PER_LINE));
+            let data: Vec<String> = bytes[..len]
+                .iter()
+                .map(|byte| format!("{byte:#04X}"))
+                .collect();
+            if f.alternate() {
+                writeln!(f, "    DB {}", data.join(", "))?;
+            } else {
+                writeln!(f, "    {address:#05X}  {:<8}  DB {}", "", data.join(", "))?;
+            }
+            address += len;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+use std::mem;
+
+use crate::state::{Reader, Writer};
+use crate::{Framebuffer, Graphics, HIRES_HEIGHT, HIRES_WIDTH, TERMINAL_HEIGHT, TERMINAL_WIDTH};
+
+/// Number of XO-CHIP bitplanes.
+pub const PLANES: usize = 2;
+
+/// Display with switchable resolution and up to two bitplanes.
+///
+/// Changed pixels are both stored in the framebuffer and emitted to the
+/// per-pixel [`Graphics`] callbacks.
+#[derive(Debug, Default)]
+pub struct Display {
+    hires: bool,
+    framebuffer: Framebuffer,
+    selected_planes: u8,
+}
+
+impl Display {
+    /// Returns a blank low resolution display drawing to the first plane.
+    pub fn new() -> Self {
+        Self {
+            hires: false,
+            framebuffer: Framebuffer::new(TERMINAL_WIDTH, TERMINAL_HEIGHT),
+            selected_planes: 1,
+        }
+    }
+
+    /// Returns the number of horizontal pixels.
+    pub const fn width(&self) -> usize {
+        if self.hires {
+            HIRES_WIDTH
+        } else {
+            TERMINAL_WIDTH
+        }
+    }
+
+    /// Returns the number of vertical pixels.
+    pub const fn height(&self) -> usize {
+        if self.hires {
+            HIRES_HEIGHT
+        } else {
+            TERMINAL_HEIGHT
+        }
+    }
+
+    /// Returns the displayed pixels.
+    pub const fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    /// Calls [`Graphics::present`] with the framebuffer and forgets its changes.
+    pub fn present(&mut self, graphics: &mut impl Graphics) {
+        graphics.present(&self.framebuffer);
+        self.framebuffer.clear_dirty();
+    }
+
+    /// Returns the number of planes drawing, clearing and scrolling apply to.
+    pub const fn selected_plane_count(&self) -> usize {
+        match self.selected_planes {
+            0 => 0,
+            1 | 2 => 1,
+            _ => 2,
+        }
+    }
+
+    /// Selects the planes drawing, clearing and scrolling apply to.
+    ///
+    /// # Arguments
+    ///
+    /// * `planes` - bitmask of the planes, `0..4`.
+    pub const fn select_planes(&mut self, planes: u8) {
+        self.selected_planes = planes & 0b11;
+    }
+
+    /// Turns off all the pixels in the selected planes.
+    pub fn clear(&mut self, graphics: &mut impl Graphics) {
+        let mask = self.selected_planes;
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let color = self.framebuffer.pixel(x, y);
+                if color & mask != 0 {
+                    self.set_pixel(x, y, color & !mask, graphics);
+                }
+            }
+        }
+    }
+
+    /// Switches between the low (64x32) and high (128x64) resolution.
+    ///
+    /// When the resolution changes, the display is cleared if `clear` is set,
+    /// as on XO-CHIP. Otherwise the pixels are kept, as on SUPER-CHIP 1.1
+    /// whose low resolution draws 2x2 pixels: each low resolution pixel
+    /// becomes 2x2 pixels, and each 2x2 block becomes its top left pixel.
+    pub fn set_hires(&mut self, hires: bool, clear: bool, graphics: &mut impl Graphics) {
+        if self.hires == hires {
+            return;
+        }
+        for (y, row) in self.framebuffer.rows().enumerate() {
+            for (x, color) in row.iter().enumerate() {
+                if *color != 0 {
+                    graphics.draw_color(x, y, 0);
+                }
+            }
+        }
+        self.hires = hires;
+        let framebuffer = Framebuffer::new(self.width(), self.height());
+        let old = mem::replace(&mut self.framebuffer, framebuffer);
+        graphics.resize(self.width(), self.height());
+        if clear {
+            return;
+        }
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let color = if hires {
+                    old.pixel(x / 2, y / 2)
+                } else {
+                    old.pixel(x * 2, y * 2)
+                };
+                if color != 0 {
+                    self.set_pixel(x, y, color, graphics);
+                }
+            }
+        }
+    }
+
+    /// XORs a sprite onto the selected planes and returns the number of the
+    /// pixels turned on and off, in each plane.
+    ///
+    /// # Arguments
+    ///
+    /// * `sprite` - rows of the sprite, each `width / 8` bytes long, for each
+    ///   selected plane in turn.
+    /// * `width` - the sprite width in pixels, either `8` or `16`.
+    /// * `clip` - clips the sprite at the edges instead of wrapping it around.
+    pub fn draw_sprite(
+        &mut self,
+        (x_org, y_org): (usize, usize),
+        sprite: &[u8],
+        width: usize,
+        clip: bool,
+        graphics: &mut impl Graphics,
+    ) -> (usize, usize) {
+        let x_org = x_org % self.width();
+        let y_org = y_org % self.height();
+        let plane_size = sprite.len() / self.selected_plane_count().max(1);
+        let (mut drawn, mut erased) = (0, 0);
+        let selected_planes = self.selected_planes;
+        let planes = (0..PLANES).filter(|plane| selected_planes & (1 << plane) != 0);
+        for (plane, data) in planes.zip(sprite.chunks(plane_size.max(1))) {
+            let bit = 1 << plane;
+            for (dy, row) in data.chunks(width / 8).enumerate() {
+                let mut y = y_org + dy;
+                if y >= self.height() {
+                    if clip {
+                        break;
+                    }
+                    y %= self.height();
+                }
+                for dx in 0..width {
+                    let mut x = x_org + dx;
+                    if x >= self.width() {
+                        if clip {
+                            break;
+                        }
+                        x %= self.width();
+                    }
+                    let pixel = (row[dx / 8] >> (7 - dx % 8)) & 1;
+                    if pixel == 1 {
+                        let color = self.framebuffer.pixel(x, y);
+                        if color & bit == 0 {
+                            drawn += 1;
+                        } else {
+                            erased += 1;
+                        }
+                        self.set_pixel(x, y, color ^ bit, graphics);
+                    }
+                }
+            }
+        }
+        (drawn, erased)
+    }
+
+    /// Scrolls the selected planes, filling the uncovered area with blank pixels.
+    ///
+    /// Positive `dx` scrolls to the right and positive `dy` scrolls down.
+    pub fn scroll(&mut self, dx: isize, dy: isize, graphics: &mut impl Graphics) {
+        let (width, height) = (self.width(), self.height());
+        let mask = self.selected_planes;
+        let old = self.framebuffer.clone();
+        for y in 0..height {
+            for x in 0..width {
+                let source = x
+                    .checked_add_signed(-dx)
+                    .filter(|x| *x < width)
+                    .zip(y.checked_add_signed(-dy).filter(|y| *y < height))
+                    .map_or(0, |(x, y)| old.pixel(x, y));
+                self.set_pixel(x, y, old.pixel(x, y) & !mask | source & mask, graphics);
+            }
+        }
+    }
+
+    /// Writes the display into a save state.
+    pub fn save(&self, w: &mut Writer) {
+        w.bool(self.hires);
+        w.u8(self.selected_planes);
+        w.bytes(self.framebuffer.pixels());
+    }
+
+    /// Reads a display written by [`Display::save`].
+    pub fn load(r: &mut Reader) -> crate::Result<Self> {
+        let hires = r.bool()?;
+        let selected_planes = r.u8()?;
+        let mut display = Self {
+            hires,
+            framebuffer: Framebuffer::default(),
+            selected_planes: selected_planes & 0b11,
+        };
+        display.framebuffer = Framebuffer::new(display.width(), display.height());
+        let pixels = r.bytes(display.width() * display.height())?;
+        for (i, color) in pixels.iter().enumerate() {
+            let (x, y) = (i % display.width(), i / display.width());
+            display.framebuffer.set_pixel(x, y, color & 0b11);
+        }
+        Ok(display)
+    }
+
+    /// Changes the colour of a pixel without emitting it to the graphics.
+    pub fn set_pixel_silently(&mut self, x: usize, y: usize, color: u8) {
+        self.framebuffer.set_pixel(x, y, color);
+    }
+
+    /// Changes the colour of a pixel, emitting it to the graphics if it changed.
+    fn set_pixel(&mut self, x: usize, y: usize, color: u8, graphics: &mut impl Graphics) {
+        if self.framebuffer.pixel(x, y) != color {
+            self.framebuffer.set_pixel(x, y, color);
+            graphics.draw_color(x, y, color);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt;
+
+/// Result type of the fallible Chip8 operations.
+pub type Result<T> = std::result::Result<T, Chip8Error>;
+
+/// Errors raised while loading or running a Chip8 program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Error {
+    /// The opcode is not an instruction of the emulated platform.
+    UnknownOpcode {
+        /// Address of the instruction.
+        pc: usize,
+        /// The offending opcode.
+        opcode: u16,
+    },
+    /// `00EE` was executed with an empty call stack.
+    StackUnderflow {
+        /// Address of the instruction.
+        pc: usize,
+    },
+    /// `2NNN` was executed with a full call stack.
+    StackOverflow {
+        /// Address of the instruction.
+        pc: usize,
+    },
+    /// An instruction accessed memory outside of the RAM.
+    AddressOutOfRange {
+        /// Address of the instruction.
+        pc: usize,
+        /// The instruction, or `0` if it could not be fetched.
+        opcode: u16,
+        /// The first address outside of the RAM.
+        address: usize,
+    },
+    /// `0NNN` was executed while machine code calls are configured as errors.
+    MachineCodeCall {
+        /// Address of the instruction.
+        pc: usize,
+        /// The offending opcode.
+        opcode: u16,
+    },
+    /// The ROM does not fit into the RAM.
+    RomTooLarge {
+        /// Size of the ROM in bytes.
+        size: usize,
+        /// Available space in bytes.
+        capacity: usize,
+    },
+    /// An address outside of the RAM was given.
+    InvalidAddress {
+        /// The first address outside of the RAM.
+        address: usize,
+    },
+    /// More return addresses than [`STACK_SIZE`](crate::STACK_SIZE) were given.
+    StackTooDeep {
+        /// Number of the addresses.
+        depth: usize,
+    },
+    /// A pixel outside of the display or with an invalid colour was given.
+    InvalidPixel {
+        /// Column of the pixel.
+        x: usize,
+        /// Row of the pixel.
+        y: usize,
+        /// The colour.
+        color: u8,
+    },
+    /// The save state was written by a newer version of the crate.
+    UnsupportedSaveState {
+        /// Version of the save state format.
+        version: u8,
+    },
+    /// The save state is truncated or holds invalid values.
+    CorruptSaveState,
+    /// The movie file is truncated, holds invalid values or was written by
+    /// a newer version of the crate.
+    InvalidMovie,
+    /// The ROM is not the one the movie was recorded with.
+    MovieRomMismatch,
+    /// The machine of a movie draws its random numbers from another
+    /// generator than the [`SeededRandom`](crate::SeededRandom) of the movie.
+    MovieRandomMismatch,
+    /// The playback of a movie diverged from the recording.
+    MovieDesync {
+        /// The first movie frame whose state differs from the recording.
+        frame: usize,
+    },
+    /// A key outside of the 16-key keypad was pressed or released.
+    InvalidKey {
+        /// The offending key.
+        key: u8,
+    },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownOpcode { pc, opcode } => {
+                write!(f, "unknown instruction {opcode:04X} at {pc:#05X}")
+            }
+            Self::StackUnderflow { pc } => {
+                write!(
+                    f,
+                    "failed to return from subroutine at {pc:#05X}: stack underflow"
+                )
+            }
+            Self::StackOverflow { pc } => {
+                write!(f, "failed to call subroutine at {pc:#05X}: stack overflow")
+            }
+            Self::AddressOutOfRange {
+                pc,
+                opcode,
+                address,
+            } => write!(
+                f,
+                "invalid memory address {address:#05X} accessed by {opcode:04X} at {pc:#05X}"
+            ),
+            Self::MachineCodeCall { pc, opcode } => write!(
+                f,
+                "machine code routines are not supported: {opcode:04X} at {pc:#05X}"
+            ),
+            Self::RomTooLarge { size, capacity } => write!(
+                f,
+                "data is too big to fit into the ram: {size} bytes, {capacity} available"
+            ),
+            Self::InvalidAddress { address } => {
+                write!(f, "invalid memory address {address:#05X}")
+            }
+            Self::StackTooDeep { depth } => write!(
+                f,
+                "stack of {depth} addresses is deeper than the limit of {}",
+                crate::STACK_SIZE
+            ),
+            Self::InvalidPixel { x, y, color } => {
+                write!(f, "invalid pixel ({x}, {y}) with colour {color}")
+            }
+            Self::UnsupportedSaveState { version } => {
+                write!(f, "unsupported save state format version {version}")
+            }
+            Self::CorruptSaveState => write!(f, "corrupt save state"),
+            Self::InvalidMovie => write!(f, "invalid movie file"),
+            Self::MovieRomMismatch => write!(f, "the rom does not match the movie"),
+            Self::MovieRandomMismatch => {
+                write!(f, "movies only replay the seeded random number generator")
+            }
+            Self::MovieDesync { frame } => {
+                write!(f, "movie playback desynchronized at frame {frame}")
+            }
+            Self::InvalidKey { key } => write!(f, "invalid key {key:#X}: the keypad has 16 keys"),
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
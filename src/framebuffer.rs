@@ -0,0 +1,131 @@
+/// Colours of the displayed pixels.
+///
+/// Each pixel holds its colour in the range `0..4`, with bit N set when the
+/// pixel is on in the XO-CHIP bitplane N. `0` is the background colour.
+///
+/// The framebuffer keeps track of the region changed since it was last
+/// presented with [`Graphics::present`](crate::Graphics::present), so that
+/// frontends can upload only that part to a texture.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+    dirty: Option<Rect>,
+}
+
+/// Rectangular region of the framebuffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rect {
+    /// Left column.
+    pub x: usize,
+    /// Top row.
+    pub y: usize,
+    /// Number of columns.
+    pub width: usize,
+    /// Number of rows.
+    pub height: usize,
+}
+
+impl Rect {
+    /// Returns the smallest rectangle containing both rectangles.
+    fn union(self, other: Self) -> Self {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Self {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+}
+
+impl Framebuffer {
+    /// Returns a blank framebuffer, entirely marked as changed.
+    #[must_use]
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+            dirty: Some(Rect {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            }),
+        }
+    }
+
+    /// Returns the number of horizontal pixels.
+    #[must_use]
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the number of vertical pixels.
+    #[must_use]
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the colour of a pixel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the coordinates are out of the framebuffer.
+    #[must_use]
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        assert!(x < self.width && y < self.height, "pixel out of range");
+        self.pixels[y * self.width + x]
+    }
+
+    /// Returns the colours of all the pixels, row by row.
+    #[must_use]
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Returns the rows of pixels, from top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.pixels.chunks(self.width.max(1))
+    }
+
+    /// Returns true if any pixel changed since the last present.
+    #[must_use]
+    pub const fn is_dirty(&self) -> bool {
+        self.dirty.is_some()
+    }
+
+    /// Returns the smallest region containing the pixels changed since the last present.
+    #[must_use]
+    pub const fn dirty_rect(&self) -> Option<Rect> {
+        self.dirty
+    }
+
+    /// Sets the colour of a pixel and marks it as changed.
+    pub(crate) fn set_pixel(&mut self, x: usize, y: usize, color: u8) {
+        let pixel = &mut self.pixels[y * self.width + x];
+        if *pixel == color {
+            return;
+        }
+        *pixel = color;
+        let changed = Rect {
+            x,
+            y,
+            width: 1,
+            height: 1,
+        };
+        self.dirty = Some(self.dirty.map_or(changed, |dirty| dirty.union(changed)));
+    }
+
+    /// Forgets the changes, after they were presented.
+    pub(crate) const fn clear_dirty(&mut self) {
+        self.dirty = None;
+    }
+}
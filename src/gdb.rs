@@ -0,0 +1,572 @@
+//! GDB remote serial protocol stub, enabled by the `gdb` feature.
+//!
+//! It lets GDB and the other front-ends speaking the protocol debug a
+//! running program: read and write the registers and the RAM, set software
+//! breakpoints and watchpoints, single-step and continue.
+//!
+//! The registers are numbered V0 to VF (`0` to `15`, 8 bits), I (`16`),
+//! PC (`17`), both 16 bits in big-endian order like the opcodes, then SP
+//! (`18`, the stack depth), DT (`19`) and ST (`20`), 8 bits each. They are
+//! described to the client by a `target.xml` feature document.
+//!
+//! ```
+//! use std::{
+//!     io::{Read, Write},
+//!     net::{TcpListener, TcpStream},
+//!     thread,
+//! };
+//!
+//! use rusty_chip8::{debugger::Debugger, gdb, Audio, Chip8, Graphics};
+//!
+//! # struct Headless;
+//! # impl Graphics for Headless {}
+//! # impl Audio for Headless {
+//! #     fn start_beep(&mut self) {}
+//! #     fn stop_beep(&mut self) {}
+//! # }
+//! /// Sends a packet and returns the reply, acknowledging both.
+//! fn request(stream: &mut TcpStream, data: &str) -> std::io::Result<String> {
+//!     let checksum = data.bytes().fold(0u8, u8::wrapping_add);
+//!     write!(stream, "${data}#{checksum:02x}")?;
+//!     let mut reply = Vec::new();
+//!     let mut byte = [0];
+//!     while !reply.ends_with(b"#") {
+//!         stream.read_exact(&mut byte)?;
+//!         if byte[0] != b'+' {
+//!             reply.push(byte[0]);
+//!         }
+//!     }
+//!     stream.read_exact(&mut [0; 2])?;
+//!     stream.write_all(b"+")?;
+//!     Ok(String::from_utf8_lossy(&reply[1..reply.len() - 1]).into_owned())
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! // LD V0, 5; CALL 0x208; JP 0x204; ADD V1, 1; RET
+//! let rom = [0x60, 0x05, 0x22, 0x08, 0x12, 0x04, 0x00, 0x00, 0x71, 0x01, 0x00, 0xEE];
+//! let mut chip8 = Chip8::new(700);
+//! chip8.store_in_ram(rom)?;
+//! let mut debugger = Debugger::new(chip8);
+//!
+//! let listener = TcpListener::bind("127.0.0.1:0")?;
+//! let port = listener.local_addr()?.port();
+//! let client = thread::spawn(move || -> std::io::Result<Vec<String>> {
+//!     let mut stream = TcpStream::connect(("127.0.0.1", port))?;
+//!     ["Z0,208,2", "c", "p11", "m200,4", "s", "p1", "D"]
+//!         .iter()
+//!         .map(|data| request(&mut stream, data))
+//!         .collect()
+//! });
+//! let (stream, _) = listener.accept()?;
+//! gdb::serve(&mut debugger, stream, &mut Headless, &mut Headless)?;
+//!
+//! let replies = client.join().unwrap()?;
+//! assert_eq!(replies, ["OK", "S05", "0208", "60052208", "S05", "01", "OK"]);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, ErrorKind, Read, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+};
+
+use crate::{
+    debugger::{Access, Breakpoint, Debugger, Location, Stop, Watchpoint},
+    Audio, Chip8Error, FramePacer, Graphics,
+};
+
+/// Byte sent by the client to interrupt a running program.
+const INTERRUPT: u8 = 0x03;
+/// Largest packet accepted from the client.
+const PACKET_SIZE: usize = 0x1000;
+/// Description of the registers.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.rusty-chip8.core">
+    <reg name="v0" bitsize="8" type="uint8"/>
+    <reg name="v1" bitsize="8" type="uint8"/>
+    <reg name="v2" bitsize="8" type="uint8"/>
+    <reg name="v3" bitsize="8" type="uint8"/>
+    <reg name="v4" bitsize="8" type="uint8"/>
+    <reg name="v5" bitsize="8" type="uint8"/>
+    <reg name="v6" bitsize="8" type="uint8"/>
+    <reg name="v7" bitsize="8" type="uint8"/>
+    <reg name="v8" bitsize="8" type="uint8"/>
+    <reg name="v9" bitsize="8" type="uint8"/>
+    <reg name="va" bitsize="8" type="uint8"/>
+    <reg name="vb" bitsize="8" type="uint8"/>
+    <reg name="vc" bitsize="8" type="uint8"/>
+    <reg name="vd" bitsize="8" type="uint8"/>
+    <reg name="ve" bitsize="8" type="uint8"/>
+    <reg name="vf" bitsize="8" type="uint8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="dt" bitsize="8" type="uint8"/>
+    <reg name="st" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+/// Number of the registers.
+const REGISTER_COUNT: usize = 21;
+
+/// Signals reported in the stop replies.
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+/// Waits for a client on `port` of the local host and serves it until it
+/// detaches or kills the program.
+///
+/// # Errors
+///
+/// Returns the errors of the connection.
+pub fn listen(
+    debugger: &mut Debugger,
+    port: u16,
+    graphics: &mut impl Graphics,
+    audio: &mut impl Audio,
+) -> io::Result<()> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    let (stream, _) = listener.accept()?;
+    serve(debugger, stream, graphics, audio)
+}
+
+/// Serves a connected client until it detaches or kills the program.
+///
+/// The breakpoints and watchpoints the client sets are added to the
+/// debugger, and removed when the session ends.
+///
+/// # Errors
+///
+/// Returns the errors of the connection.
+pub fn serve(
+    debugger: &mut Debugger,
+    stream: TcpStream,
+    graphics: &mut impl Graphics,
+    audio: &mut impl Audio,
+) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let mut session = Session {
+        debugger,
+        stream,
+        ack: true,
+        points: BTreeMap::new(),
+    };
+    let result = session.run(graphics, audio);
+    for id in session.points.into_keys() {
+        session.debugger.remove(id);
+    }
+    result
+}
+
+/// Breakpoint or watchpoint set by the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Point {
+    /// The type of the `Z` packet, `0` to `4`.
+    kind: u8,
+    address: usize,
+    len: usize,
+}
+
+#[derive(Debug)]
+struct Session<'a> {
+    debugger: &'a mut Debugger,
+    stream: TcpStream,
+    /// Whether packets are acknowledged, until `QStartNoAckMode`.
+    ack: bool,
+    /// The points set by the client, by identifier in the debugger.
+    points: BTreeMap<usize, Point>,
+}
+
+impl Session<'_> {
+    /// Answers the packets of the client until the session ends.
+    fn run(&mut self, graphics: &mut impl Graphics, audio: &mut impl Audio) -> io::Result<()> {
+        while let Some(packet) = self.receive()? {
+            let reply = match packet.as_bytes().first() {
+                Some(b'?') => stop_reply(SIGTRAP),
+                Some(b'g') => hex(&self.registers()),
+                Some(b'G') => ok(self.write_registers(&packet[1..])),
+                Some(b'p') => self.read_register(&packet[1..]).unwrap_or_else(error),
+                Some(b'P') => ok(self.write_register(&packet[1..])),
+                Some(b'm') => self.read_memory(&packet[1..]).unwrap_or_else(error),
+                Some(b'M') => ok(self.write_memory(&packet[1..])),
+                Some(b'Z') => ok(self.insert_point(&packet[1..])),
+                Some(b'z') => ok(self.remove_point(&packet[1..])),
+                Some(b's') => {
+                    let result = self.debugger.step(graphics, audio);
+                    self.stopped(result)
+                }
+                Some(b'c') => self.resume(graphics, audio)?,
+                Some(b'D') => {
+                    self.send("OK")?;
+                    return Ok(());
+                }
+                Some(b'k') => return Ok(()),
+                Some(b'H') => "OK".to_string(),
+                _ => self.query(&packet),
+            };
+            self.send(&reply)?;
+        }
+        Ok(())
+    }
+
+    /// Answers the general queries, or returns an empty reply for the
+    /// unsupported packets.
+    fn query(&mut self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            format!("PacketSize={PACKET_SIZE:x};QStartNoAckMode+;qXfer:features:read+")
+        } else if packet == "QStartNoAckMode" {
+            self.ack = false;
+            "OK".to_string()
+        } else if let Some(annex) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let (offset, len) = parse_range(annex).unwrap_or_default();
+            let data = TARGET_XML.get(offset..).unwrap_or_default();
+            if data.len() <= len {
+                format!("l{data}")
+            } else {
+                format!("m{}", &data[..len])
+            }
+        } else {
+            match packet {
+                "qAttached" => "1".to_string(),
+                "qC" => "QC1".to_string(),
+                "qfThreadInfo" => "m1".to_string(),
+                "qsThreadInfo" => "l".to_string(),
+                _ => String::new(),
+            }
+        }
+    }
+
+    /// Runs the program until it stops or the client interrupts it, at the
+    /// speed of the machine.
+    fn resume(
+        &mut self,
+        graphics: &mut impl Graphics,
+        audio: &mut impl Audio,
+    ) -> io::Result<String> {
+        self.stream.set_nonblocking(true)?;
+        let mut pacer = FramePacer::new();
+        let mut frame = self.debugger.chip8().frame_count();
+        let mut interrupted = Ok(false);
+        // The client interrupts the run instead.
+        let limit = self.debugger.frame_limit();
+        self.debugger.set_frame_limit(None);
+        let result = self.debugger.run(graphics, audio, |chip8| {
+            if chip8.frame_count() == frame {
+                return None;
+            }
+            frame = chip8.frame_count();
+            interrupted = poll_interrupt(&mut self.stream);
+            if matches!(interrupted, Ok(false)) {
+                pacer.wait();
+                None
+            } else {
+                Some(Stop::Frame)
+            }
+        });
+        self.debugger.set_frame_limit(limit);
+        self.stream.set_nonblocking(false)?;
+        interrupted?;
+        Ok(self.stopped(result))
+    }
+
+    /// Returns the stop reply of a step or a run.
+    fn stopped(&self, result: crate::Result<Stop>) -> String {
+        match result {
+            Ok(Stop::Frame | Stop::Limit) => stop_reply(SIGINT),
+            Ok(Stop::Exit) => "W00".to_string(),
+            Ok(Stop::Watchpoint { id, access }) => self.points.get(&id).map_or_else(
+                || stop_reply(SIGTRAP),
+                |point| {
+                    let reason = match access {
+                        Access::Read => "rwatch",
+                        Access::Write => "watch",
+                        Access::ReadWrite => "awatch",
+                    };
+                    format!("T{SIGTRAP:02x}{reason}:{:x};", point.address)
+                },
+            ),
+            Ok(Stop::Step | Stop::Breakpoint { .. } | Stop::Key) => stop_reply(SIGTRAP),
+            Err(Chip8Error::UnknownOpcode { .. }) => stop_reply(SIGILL),
+            Err(_) => stop_reply(SIGSEGV),
+        }
+    }
+
+    /// Returns the values of all the registers.
+    fn registers(&self) -> Vec<u8> {
+        (0..REGISTER_COUNT)
+            .flat_map(|n| self.register(n).unwrap_or_default())
+            .collect()
+    }
+
+    /// Returns the value of register `n`.
+    fn register(&self, n: usize) -> Option<Vec<u8>> {
+        let chip8 = self.debugger.chip8();
+        let word = |value: usize| {
+            u16::try_from(value & 0xFFFF)
+                .unwrap_or_default()
+                .to_be_bytes()
+                .to_vec()
+        };
+        let byte = |value: usize| vec![u8::try_from(value & 0xFF).unwrap_or_default()];
+        match n {
+            0..16 => Some(vec![chip8.registers()[n]]),
+            16 => Some(word(chip8.index())),
+            17 => Some(word(chip8.pc())),
+            18 => Some(byte(chip8.stack().len())),
+            19 => Some(vec![chip8.delay_timer()]),
+            20 => Some(vec![chip8.sound_timer()]),
+            _ => None,
+        }
+    }
+
+    /// Sets register `n` to `value`, given in the byte order of [`Session::register`].
+    fn set_register(&mut self, n: usize, value: &[u8]) -> Option<()> {
+        let value = value
+            .iter()
+            .fold(0, |value, byte| value << 8 | usize::from(*byte));
+        let byte = u8::try_from(value).ok();
+        let chip8 = self.debugger.chip8_mut();
+        match n {
+            0..16 => chip8.registers_mut()[n] = byte?,
+            16 => chip8.set_index(value).ok()?,
+            17 => chip8.set_pc(value).ok()?,
+            18 => {
+                let stack = chip8.stack().get(..value)?.to_vec();
+                chip8.set_stack(&stack).ok()?;
+            }
+            19 => chip8.set_delay_timer(byte?),
+            20 => chip8.set_sound_timer(byte?),
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn write_registers(&mut self, data: &str) -> Option<()> {
+        let mut data = unhex(data)?;
+        for n in 0..REGISTER_COUNT {
+            let len = self.register(n)?.len();
+            let value: Vec<u8> = data.drain(..len.min(data.len())).collect();
+            if value.len() < len {
+                break;
+            }
+            self.set_register(n, &value)?;
+        }
+        Some(())
+    }
+
+    fn read_register(&self, args: &str) -> Option<String> {
+        let n = usize::from_str_radix(args, 16).ok()?;
+        self.register(n).map(|value| hex(&value))
+    }
+
+    fn write_register(&mut self, args: &str) -> Option<()> {
+        let (n, value) = args.split_once('=')?;
+        let n = usize::from_str_radix(n, 16).ok()?;
+        let value = unhex(value)?;
+        if self.register(n)?.len() != value.len() {
+            return None;
+        }
+        self.set_register(n, &value)
+    }
+
+    fn read_memory(&self, args: &str) -> Option<String> {
+        let (address, len) = parse_range(args)?;
+        let bytes = self.debugger.chip8().read_memory(address, len).ok()?;
+        Some(hex(bytes))
+    }
+
+    fn write_memory(&mut self, args: &str) -> Option<()> {
+        let (range, data) = args.split_once(':')?;
+        let (address, len) = parse_range(range)?;
+        let data = unhex(data)?;
+        if data.len() != len {
+            return None;
+        }
+        self.debugger.chip8_mut().write_memory(address, &data).ok()
+    }
+
+    /// Adds the breakpoint or watchpoint of a `Z` packet.
+    fn insert_point(&mut self, args: &str) -> Option<()> {
+        let point = parse_point(args)?;
+        if self.points.values().any(|existing| *existing == point) {
+            return Some(());
+        }
+        let access = match point.kind {
+            0 | 1 => None,
+            2 => Some(Access::Write),
+            3 => Some(Access::Read),
+            4 => Some(Access::ReadWrite),
+            _ => return None,
+        };
+        let id = match access {
+            None => self
+                .debugger
+                .add_breakpoint(Breakpoint::Address(point.address), None),
+            Some(access) => self.debugger.add_watchpoint(Watchpoint {
+                location: Location::Memory {
+                    address: point.address,
+                    len: point.len,
+                },
+                access,
+            }),
+        };
+        self.points.insert(id, point);
+        Some(())
+    }
+
+    /// Removes the breakpoint or watchpoint of a `z` packet.
+    fn remove_point(&mut self, args: &str) -> Option<()> {
+        let point = parse_point(args)?;
+        let id = self
+            .points
+            .iter()
+            .find_map(|(id, existing)| (*existing == point).then_some(*id));
+        if let Some(id) = id {
+            self.points.remove(&id);
+            self.debugger.remove(id);
+        }
+        Some(())
+    }
+
+    /// Returns the next packet, or `None` when the client disconnects.
+    ///
+    /// The packets longer than [`PACKET_SIZE`] are answered with an error
+    /// and skipped.
+    fn receive(&mut self) -> io::Result<Option<String>> {
+        loop {
+            let mut byte = [0];
+            loop {
+                if self.stream.read(&mut byte)? == 0 {
+                    return Ok(None);
+                }
+                if byte[0] == b'$' {
+                    break;
+                }
+            }
+            let mut data = Vec::new();
+            let mut sum = 0u8;
+            let mut too_long = false;
+            loop {
+                if self.stream.read(&mut byte)? == 0 {
+                    return Ok(None);
+                }
+                if byte[0] == b'#' {
+                    break;
+                }
+                sum = sum.wrapping_add(byte[0]);
+                if data.len() < PACKET_SIZE {
+                    data.push(byte[0]);
+                } else {
+                    too_long = true;
+                }
+            }
+            let mut checksum = [0; 2];
+            self.stream.read_exact(&mut checksum)?;
+            let valid = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|checksum| u8::from_str_radix(checksum, 16).ok())
+                == Some(sum);
+            if self.ack {
+                self.stream.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if valid && too_long {
+                self.send(&error())?;
+            } else if valid {
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+        }
+    }
+
+    /// Sends a packet, again until the client acknowledges it.
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let checksum = data.bytes().fold(0u8, u8::wrapping_add);
+        let packet = format!("${data}#{checksum:02x}");
+        loop {
+            self.stream.write_all(packet.as_bytes())?;
+            if !self.ack {
+                return Ok(());
+            }
+            let mut byte = [0];
+            loop {
+                self.stream.read_exact(&mut byte)?;
+                match byte[0] {
+                    b'+' => return Ok(()),
+                    b'-' => break,
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Returns true if the client sent an interrupt to the non-blocking stream,
+/// or hung up.
+fn poll_interrupt(stream: &mut TcpStream) -> io::Result<bool> {
+    let mut byte = [0];
+    loop {
+        match stream.read(&mut byte) {
+            Ok(0) => return Ok(true),
+            Ok(_) if byte[0] == INTERRUPT => return Ok(true),
+            Ok(_) => {}
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(false),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+fn stop_reply(signal: u8) -> String {
+    format!("S{signal:02x}")
+}
+
+/// Returns `OK`, or an error reply if the packet failed.
+fn ok(result: Option<()>) -> String {
+    result.map_or_else(error, |()| "OK".to_string())
+}
+
+fn error() -> String {
+    "E01".to_string()
+}
+
+/// Parses `ADDR,LEN` in hexadecimal.
+fn parse_range(args: &str) -> Option<(usize, usize)> {
+    let (address, len) = args.split_once(',')?;
+    Some((
+        usize::from_str_radix(address, 16).ok()?,
+        usize::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+/// Parses `TYPE,ADDR,KIND` of the `Z` and `z` packets.
+fn parse_point(args: &str) -> Option<Point> {
+    let (kind, range) = args.split_once(',')?;
+    let (address, len) = parse_range(range.split(';').next()?)?;
+    Some(Point {
+        kind: kind.parse().ok()?,
+        address,
+        len,
+    })
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut text, byte| {
+        let _ = write!(text, "{byte:02x}");
+        text
+    })
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
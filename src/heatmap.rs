@@ -0,0 +1,197 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{self, Write},
+    ops::Range,
+};
+
+/// Number of bytes on a row of the heatmap image.
+const IMAGE_WIDTH: usize = 64;
+
+/// Write of a program to its own code, found by a [`MemoryHeatmap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SelfModification {
+    /// Address of the instruction writing the byte.
+    pub writer: usize,
+    /// Address of the byte, which was executed before the write or after it.
+    pub address: usize,
+    /// Frame of the first such write.
+    pub frame: u64,
+}
+
+/// Reads, writes and executions of every byte of RAM, collected by
+/// [`Chip8::set_memory_heatmap`](crate::Chip8::set_memory_heatmap).
+///
+/// Executions count the instruction fetches, reads count the sprites, the
+/// font lookups of `FX29`/`FX30` and the loads of `FX65`, `5XY3` and `F002`,
+/// and writes count `FX33`, `FX55` and `5XY2`. Writes to bytes that were
+/// executed, or that are executed afterwards, are flagged as
+/// [self-modifications](MemoryHeatmap::self_modifications).
+#[derive(Debug, Clone)]
+pub struct MemoryHeatmap {
+    reads: Vec<u64>,
+    writes: Vec<u64>,
+    executes: Vec<u64>,
+    /// The instruction and the frame of the last write of every byte.
+    last_writes: Vec<Option<(usize, u64)>>,
+    /// Self-modifications by address and writer.
+    self_modifications: BTreeMap<(usize, usize), u64>,
+}
+
+impl MemoryHeatmap {
+    /// Returns an empty heatmap of a RAM of `size` bytes.
+    pub(crate) fn new(size: usize) -> Self {
+        Self {
+            reads: vec![0; size],
+            writes: vec![0; size],
+            executes: vec![0; size],
+            last_writes: vec![None; size],
+            self_modifications: BTreeMap::new(),
+        }
+    }
+
+    /// Accounts for reading `range`.
+    pub(crate) fn record_read(&mut self, range: Range<usize>) {
+        for count in self.reads.get_mut(range).unwrap_or_default() {
+            *count += 1;
+        }
+    }
+
+    /// Accounts for the instruction at `pc` writing `range` in `frame`.
+    pub(crate) fn record_write(&mut self, range: Range<usize>, pc: usize, frame: u64) {
+        if range.end > self.writes.len() {
+            return;
+        }
+        for address in range {
+            self.writes[address] += 1;
+            self.last_writes[address] = Some((pc, frame));
+            if self.executes[address] > 0 {
+                self.self_modifications
+                    .entry((address, pc))
+                    .or_insert(frame);
+            }
+        }
+    }
+
+    /// Accounts for fetching the instruction in `range`.
+    pub(crate) fn record_execute(&mut self, range: Range<usize>) {
+        if range.end > self.executes.len() {
+            return;
+        }
+        for address in range {
+            self.executes[address] += 1;
+            if let Some((writer, frame)) = self.last_writes[address] {
+                self.self_modifications
+                    .entry((address, writer))
+                    .or_insert(frame);
+            }
+        }
+    }
+
+    /// Returns the number of reads of every byte of RAM.
+    #[must_use]
+    pub fn reads(&self) -> &[u64] {
+        &self.reads
+    }
+
+    /// Returns the number of writes of every byte of RAM.
+    #[must_use]
+    pub fn writes(&self) -> &[u64] {
+        &self.writes
+    }
+
+    /// Returns the number of executions of every byte of RAM.
+    #[must_use]
+    pub fn executes(&self) -> &[u64] {
+        &self.executes
+    }
+
+    /// Returns the writes to code, by address and writer.
+    pub fn self_modifications(&self) -> impl Iterator<Item = SelfModification> + '_ {
+        self.self_modifications
+            .iter()
+            .map(|((address, writer), frame)| SelfModification {
+                writer: *writer,
+                address: *address,
+                frame: *frame,
+            })
+    }
+
+    /// Writes the heatmap as a binary PPM image, 64 pixels wide with a pixel
+    /// per byte, i.e. 64x64 for the 4 KiB of CHIP-8.
+    ///
+    /// The red, green and blue channels are the writes, executions and
+    /// reads, on a logarithmic scale up to the largest count of each.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of writing to `w`.
+    pub fn write_ppm(&self, mut w: impl Write) -> io::Result<()> {
+        let height = self.reads.len().div_ceil(IMAGE_WIDTH);
+        writeln!(w, "P6\n{IMAGE_WIDTH} {height}\n255")?;
+        let channels = [&self.writes, &self.executes, &self.reads];
+        let max = channels.map(|counts| counts.iter().copied().max().unwrap_or_default());
+        let mut pixels = Vec::with_capacity(height * IMAGE_WIDTH * 3);
+        for address in 0..height * IMAGE_WIDTH {
+            for (counts, max) in channels.iter().zip(max) {
+                let count = counts.get(address).copied().unwrap_or_default();
+                pixels.push(intensity(count, max));
+            }
+        }
+        w.write_all(&pixels)
+    }
+
+    /// Writes the counts of the bytes accessed at least once as a CSV table,
+    /// with the `address,reads,writes,executes` columns.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of writing to `w`.
+    pub fn write_csv(&self, mut w: impl Write) -> io::Result<()> {
+        writeln!(w, "address,reads,writes,executes")?;
+        for (address, ((reads, writes), executes)) in self
+            .reads
+            .iter()
+            .zip(&self.writes)
+            .zip(&self.executes)
+            .enumerate()
+        {
+            if reads + writes + executes > 0 {
+                writeln!(w, "{address:#05X},{reads},{writes},{executes}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MemoryHeatmap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let touched = |counts: &[u64]| counts.iter().filter(|count| **count > 0).count();
+        writeln!(
+            f,
+            "{} bytes read, {} written, {} executed",
+            touched(&self.reads),
+            touched(&self.writes),
+            touched(&self.executes)
+        )?;
+        for modification in self.self_modifications() {
+            writeln!(
+                f,
+                "code at {:#05X} written by {:#05X} in frame {}",
+                modification.address, modification.writer, modification.frame
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the brightness of `count` on a logarithmic scale up to `max`.
+fn intensity(count: u64, max: u64) -> u8 {
+    if count == 0 {
+        return 0;
+    }
+    // The darkest non-zero counts still stand out from the background.
+    let steps = u64::from(max.ilog2()) + 1;
+    let step = u64::from(count.ilog2()) + 1;
+    u8::try_from(64 + 191 * step / steps).unwrap_or(u8::MAX)
+}
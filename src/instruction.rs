@@ -0,0 +1,397 @@
+use std::fmt;
+
+use crate::Platform;
+
+/// Represents Chip8 instructions.
+///
+/// The variants are named after what they do and their opcode pattern.
+/// Registers are given by their index, `0..16`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    /// `00E0`: clears the display.
+    Cls00E0,
+    /// `ANNN`: sets the index register to NNN.
+    SetIndexRegisterANNN(usize),
+    /// `6XNN`: sets VX to NN.
+    SetVRegister6XNN(usize, u8),
+    /// `DXYN`: draws an 8xN sprite at (VX, VY).
+    Dxyn(usize, usize, usize),
+    /// `7XNN`: adds NN to VX.
+    Add7XNN(usize, u8),
+    /// `1NNN`: jumps to NNN.
+    Jump1NNN(u16),
+    /// `2NNN`: calls the subroutine at NNN.
+    SubroutineCall2NNN(u16),
+    /// `00EE`: returns from a subroutine.
+    SubroutineReturn00EE,
+    /// `3XNN`: skips the next instruction if VX equals NN.
+    SkipEqual3XNN(usize, u8),
+    /// `4XNN`: skips the next instruction if VX does not equal NN.
+    SkipNotEqual4XNN(usize, u8),
+    /// `FX33`: stores the decimal digits of VX at the index register.
+    BinaryCodedDecimalConversionFX33(usize),
+    /// `FX29`: points the index register to the font character of VX.
+    FontCharacterFX29(usize),
+    /// `FX15`: sets the delay timer to VX.
+    SetDelayTimerFX15(usize),
+    /// `FX07`: sets VX to the delay timer.
+    ReadDelayTimerFX07(usize),
+    /// `FX0A`: waits for a key and stores it in VX.
+    GetKeyFX0A(usize),
+    /// `FX18`: sets the sound timer to VX.
+    SetSoundTimerFX18(usize),
+    /// `FX1E`: adds VX to the index register.
+    AddToIndexFX1E(usize),
+    /// `FX55`: stores V0 to VX at the index register.
+    StoreRegistersToMemoryFX55(usize),
+    /// `FX65`: loads V0 to VX from the index register.
+    LoadRegistersFromMemoryFX65(usize),
+    /// `CXNN`: sets VX to a random number masked with NN.
+    RandomCXNN(usize, u8),
+    /// `EX9E`: skips the next instruction if the key in VX is pressed.
+    SkipIfKeyPressedEX9E(usize),
+    /// `EXA1`: skips the next instruction if the key in VX is not pressed.
+    SkipIfKeyNotPressedEXA1(usize),
+    /// `8XY2`: sets VX to VX AND VY.
+    BinaryAnd8XY2(usize, usize),
+    /// `8XY4`: adds VY to VX, setting VF on carry.
+    RegisterAdd8XY4(usize, usize),
+    /// `8XY0`: sets VX to VY.
+    RegisterSet8XY0(usize, usize),
+    /// `8XY5`: subtracts VY from VX, clearing VF on borrow.
+    RegisterSub8XY5(usize, usize),
+    /// `8XY7`: sets VX to VY minus VX, clearing VF on borrow.
+    RegisterSubRev8XY7(usize, usize),
+    /// `8XY6`: shifts right by one bit, setting VF to the bit shifted out.
+    ShiftRight8XY6(usize, usize),
+    /// `8XYE`: shifts left by one bit, setting VF to the bit shifted out.
+    ShiftLeft8XYE(usize, usize),
+    /// `5XY0`: skips the next instruction if VX equals VY.
+    SkipIfEqual5XY0(usize, usize),
+    /// `9XY0`: skips the next instruction if VX does not equal VY.
+    SkipIfNotEqual9XY0(usize, usize),
+    /// `8XY3`: sets VX to VX XOR VY.
+    Xor8XY3(usize, usize),
+    /// `8XY1`: sets VX to VX OR VY.
+    Or8XY1(usize, usize),
+    /// `BNNN`: jumps to NNN plus V0.
+    JumpWithOffsetBNNN(u16),
+    /// `0NNN`: calls the machine code routine at NNN.
+    MachineCodeCall0NNN(u16),
+    /// `00CN`: scrolls the display down by N pixels (SUPER-CHIP).
+    ScrollDown00CN(u8),
+    /// `00FB`: scrolls the display right by 4 pixels (SUPER-CHIP).
+    ScrollRight00FB,
+    /// `00FC`: scrolls the display left by 4 pixels (SUPER-CHIP).
+    ScrollLeft00FC,
+    /// `00FD`: exits the interpreter (SUPER-CHIP).
+    Exit00FD,
+    /// `00FE`: switches to low resolution (SUPER-CHIP).
+    LowResolution00FE,
+    /// `00FF`: switches to high resolution (SUPER-CHIP).
+    HighResolution00FF,
+    /// `DXY0`: draws a 16x16 sprite at (VX, VY) (SUPER-CHIP).
+    BigSpriteDXY0(usize, usize),
+    /// `FX30`: points the index register to the big font character of VX (SUPER-CHIP).
+    BigFontCharacterFX30(usize),
+    /// `FX75`: stores V0 to VX in the RPL user flags (SUPER-CHIP).
+    StoreFlagsFX75(usize),
+    /// `FX85`: loads V0 to VX from the RPL user flags (SUPER-CHIP).
+    LoadFlagsFX85(usize),
+    /// `00DN`: scrolls the display up by N pixels (XO-CHIP).
+    ScrollUp00DN(u8),
+    /// `5XY2`: stores VX to VY at the index register (XO-CHIP).
+    StoreRangeToMemory5XY2(usize, usize),
+    /// `5XY3`: loads VX to VY from the index register (XO-CHIP).
+    LoadRangeFromMemory5XY3(usize, usize),
+    /// `F000 NNNN`: sets the index register to the 16-bit NNNN (XO-CHIP).
+    LongIndexF000NNNN(u16),
+    /// `FN01`: selects the bitplanes N (XO-CHIP).
+    SelectPlanesFN01(u8),
+    /// `F002`: loads the audio pattern from the index register (XO-CHIP).
+    LoadAudioPatternF002,
+    /// `FX3A`: sets the audio pitch to VX (XO-CHIP).
+    SetPitchFX3A(usize),
+}
+
+impl Instruction {
+    /// Decodes the instruction at the start of `bytes`.
+    ///
+    /// Returns `None` if the bytes are not an instruction of the platform, or
+    /// are too short for it. See [`Instruction::size`] for the bytes taken.
+    #[must_use]
+    pub fn decode(bytes: &[u8], platform: Platform) -> Option<Self> {
+        match *bytes {
+            [0xF0, 0x00, b3, b4, ..] if platform >= Platform::XoChip => {
+                Some(Self::LongIndexF000NNNN(u16::from_be_bytes([b3, b4])))
+            }
+            [0xF0, 0x00, ..] if platform >= Platform::XoChip => None,
+            [b1, b2, ..] => Self::new(b1, b2, platform),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of bytes the instruction takes: 4 for `F000 NNNN`, 2 otherwise.
+    #[must_use]
+    pub const fn size(self) -> usize {
+        match self {
+            Self::LongIndexF000NNNN(_) => 4,
+            _ => 2,
+        }
+    }
+
+    /// Returns the address the instruction jumps to, calls or points the
+    /// index register to, if it is fixed.
+    #[must_use]
+    pub fn target(self) -> Option<usize> {
+        match self {
+            Self::Jump1NNN(nnn)
+            | Self::SubroutineCall2NNN(nnn)
+            | Self::JumpWithOffsetBNNN(nnn)
+            | Self::LongIndexF000NNNN(nnn) => Some(nnn.into()),
+            Self::SetIndexRegisterANNN(nnn) => Some(nnn),
+            _ => None,
+        }
+    }
+
+    /// Encodes the instruction, the inverse of [`Instruction::decode`].
+    ///
+    /// Fields too large for their place in the opcode are truncated.
+    #[must_use]
+    pub fn to_bytes(self) -> Vec<u8> {
+        let nibble = |value: usize| u16::try_from(value & 0xF).unwrap_or_default();
+        let address = |nnn: usize| u16::try_from(nnn & 0xFFF).unwrap_or_default();
+        let xy = |i: u16, x: usize, y: usize, n: u16| i << 12 | nibble(x) << 8 | nibble(y) << 4 | n;
+        let xnn = |i: u16, x: usize, nn: u8| i << 12 | nibble(x) << 8 | u16::from(nn);
+        let opcode = match self {
+            Self::LongIndexF000NNNN(nnnn) => {
+                let [high, low] = nnnn.to_be_bytes();
+                return vec![0xF0, 0x00, high, low];
+            }
+            Self::Cls00E0 => 0x00E0,
+            Self::SubroutineReturn00EE => 0x00EE,
+            Self::MachineCodeCall0NNN(nnn) => address(nnn.into()),
+            Self::Jump1NNN(nnn) => 0x1000 | address(nnn.into()),
+            Self::SubroutineCall2NNN(nnn) => 0x2000 | address(nnn.into()),
+            Self::SkipEqual3XNN(x, nn) => xnn(3, x, nn),
+            Self::SkipNotEqual4XNN(x, nn) => xnn(4, x, nn),
+            Self::SkipIfEqual5XY0(x, y) => xy(5, x, y, 0),
+            Self::SetVRegister6XNN(x, nn) => xnn(6, x, nn),
+            Self::Add7XNN(x, nn) => xnn(7, x, nn),
+            Self::RegisterSet8XY0(x, y) => xy(8, x, y, 0),
+            Self::Or8XY1(x, y) => xy(8, x, y, 1),
+            Self::BinaryAnd8XY2(x, y) => xy(8, x, y, 2),
+            Self::Xor8XY3(x, y) => xy(8, x, y, 3),
+            Self::RegisterAdd8XY4(x, y) => xy(8, x, y, 4),
+            Self::RegisterSub8XY5(x, y) => xy(8, x, y, 5),
+            Self::ShiftRight8XY6(x, y) => xy(8, x, y, 6),
+            Self::RegisterSubRev8XY7(x, y) => xy(8, x, y, 7),
+            Self::ShiftLeft8XYE(x, y) => xy(8, x, y, 0xE),
+            Self::SkipIfNotEqual9XY0(x, y) => xy(9, x, y, 0),
+            Self::SetIndexRegisterANNN(nnn) => 0xA000 | address(nnn),
+            Self::JumpWithOffsetBNNN(nnn) => 0xB000 | address(nnn.into()),
+            Self::RandomCXNN(x, nn) => xnn(0xC, x, nn),
+            Self::Dxyn(x, y, n) => xy(0xD, x, y, nibble(n)),
+            Self::SkipIfKeyPressedEX9E(x) => xnn(0xE, x, 0x9E),
+            Self::SkipIfKeyNotPressedEXA1(x) => xnn(0xE, x, 0xA1),
+            Self::ReadDelayTimerFX07(x) => xnn(0xF, x, 0x07),
+            Self::GetKeyFX0A(x) => xnn(0xF, x, 0x0A),
+            Self::SetDelayTimerFX15(x) => xnn(0xF, x, 0x15),
+            Self::SetSoundTimerFX18(x) => xnn(0xF, x, 0x18),
+            Self::AddToIndexFX1E(x) => xnn(0xF, x, 0x1E),
+            Self::FontCharacterFX29(x) => xnn(0xF, x, 0x29),
+            Self::BinaryCodedDecimalConversionFX33(x) => xnn(0xF, x, 0x33),
+            Self::StoreRegistersToMemoryFX55(x) => xnn(0xF, x, 0x55),
+            Self::LoadRegistersFromMemoryFX65(x) => xnn(0xF, x, 0x65),
+            Self::ScrollDown00CN(n) => 0x00C0 | nibble(n.into()),
+            Self::ScrollRight00FB => 0x00FB,
+            Self::ScrollLeft00FC => 0x00FC,
+            Self::Exit00FD => 0x00FD,
+            Self::LowResolution00FE => 0x00FE,
+            Self::HighResolution00FF => 0x00FF,
+            Self::BigSpriteDXY0(x, y) => xy(0xD, x, y, 0),
+            Self::BigFontCharacterFX30(x) => xnn(0xF, x, 0x30),
+            Self::StoreFlagsFX75(x) => xnn(0xF, x, 0x75),
+            Self::LoadFlagsFX85(x) => xnn(0xF, x, 0x85),
+            Self::ScrollUp00DN(n) => 0x00D0 | nibble(n.into()),
+            Self::StoreRangeToMemory5XY2(x, y) => xy(5, x, y, 2),
+            Self::LoadRangeFromMemory5XY3(x, y) => xy(5, x, y, 3),
+            Self::SelectPlanesFN01(n) => xnn(0xF, n.into(), 0x01),
+            Self::LoadAudioPatternF002 => 0xF002,
+            Self::SetPitchFX3A(x) => xnn(0xF, x, 0x3A),
+        };
+        opcode.to_be_bytes().to_vec()
+    }
+
+    fn new(b1: u8, b2: u8, platform: Platform) -> Option<Self> {
+        let i = b1 >> 4;
+        let x = b1 & 0xf;
+        let y = b2 >> 4;
+        let n = usize::from(b2 & 0xf);
+        let nn = b2;
+        let nnn = u16::from(x) << 8 | u16::from(nn);
+        if platform >= Platform::XoChip {
+            if let Some(ins) = Self::new_xo_chip(i, x, y, b2 & 0xf) {
+                return Some(ins);
+            }
+        }
+        let x = usize::from(x);
+        let y = usize::from(y);
+        if platform >= Platform::SuperChip {
+            if let Some(ins) = Self::new_super_chip(i, x, y, b2 & 0xf) {
+                return Some(ins);
+            }
+        }
+        let ins = match (i, x, y, n, nn, nnn) {
+            (0, 0, 0xE, 0, _, _) => Self::Cls00E0,
+            (0xA, _, _, _, _, nnn) => Self::SetIndexRegisterANNN(nnn.into()),
+            (1, _, _, _, _, nnn) => Self::Jump1NNN(nnn),
+            (6, x, _, _, nn, _) => Self::SetVRegister6XNN(x, nn),
+            (0xD, x, y, n, _, _) => Self::Dxyn(x, y, n),
+            (2, _, _, _, _, nnn) => Self::SubroutineCall2NNN(nnn),
+            (0, 0, 0xE, 0xE, _, _) => Self::SubroutineReturn00EE,
+            (3, x, _, _, nn, _) => Self::SkipEqual3XNN(x, nn),
+            (4, x, _, _, nn, _) => Self::SkipNotEqual4XNN(x, nn),
+            (5, x, y, 0, _, _) => Self::SkipIfEqual5XY0(x, y),
+            (9, x, y, 0, _, _) => Self::SkipIfNotEqual9XY0(x, y),
+            (7, x, _, _, nn, _) => Self::Add7XNN(x, nn),
+            (8, x, y, 3, _, _) => Self::Xor8XY3(x, y),
+            (0xF, x, 3, 3, _, _) => Self::BinaryCodedDecimalConversionFX33(x),
+            (0xF, x, 2, 9, _, _) => Self::FontCharacterFX29(x),
+            (0xF, x, 1, 5, _, _) => Self::SetDelayTimerFX15(x),
+            (0xF, x, 0, 7, _, _) => Self::ReadDelayTimerFX07(x),
+            (0xF, x, 0, 0xA, _, _) => Self::GetKeyFX0A(x),
+            (0xF, x, 1, 8, _, _) => Self::SetSoundTimerFX18(x),
+            (0xF, x, 1, 0xE, _, _) => Self::AddToIndexFX1E(x),
+            (0xF, x, 5, 5, _, _) => Self::StoreRegistersToMemoryFX55(x),
+            (0xF, x, 6, 5, _, _) => Self::LoadRegistersFromMemoryFX65(x),
+            (0xC, x, _, _, nn, _) => Self::RandomCXNN(x, nn),
+            (0xE, x, 9, 0xE, _, _) => Self::SkipIfKeyPressedEX9E(x),
+            (0xE, x, 0xA, 1, _, _) => Self::SkipIfKeyNotPressedEXA1(x),
+            (8, x, y, 2, _, _) => Self::BinaryAnd8XY2(x, y),
+            (8, x, y, 4, _, _) => Self::RegisterAdd8XY4(x, y),
+            (8, x, y, 0, _, _) => Self::RegisterSet8XY0(x, y),
+            (8, x, y, 5, _, _) => Self::RegisterSub8XY5(x, y),
+            (8, x, y, 6, _, _) => Self::ShiftRight8XY6(x, y),
+            (8, x, y, 0xE, _, _) => Self::ShiftLeft8XYE(x, y),
+            (8, x, y, 7, _, _) => Self::RegisterSubRev8XY7(x, y),
+            (8, x, y, 1, _, _) => Self::Or8XY1(x, y),
+            (0xB, _, _, _, _, nnn) => Self::JumpWithOffsetBNNN(nnn),
+            (0, _, _, _, _, nnn) => Self::MachineCodeCall0NNN(nnn),
+            _ => return None,
+        };
+        Some(ins)
+    }
+
+    /// Decodes the instructions introduced by SUPER-CHIP 1.1.
+    const fn new_super_chip(i: u8, x: usize, y: usize, n: u8) -> Option<Self> {
+        let ins = match (i, x, y, n) {
+            (0, 0, 0xC, n) => Self::ScrollDown00CN(n),
+            (0, 0, 0xF, 0xB) => Self::ScrollRight00FB,
+            (0, 0, 0xF, 0xC) => Self::ScrollLeft00FC,
+            (0, 0, 0xF, 0xD) => Self::Exit00FD,
+            (0, 0, 0xF, 0xE) => Self::LowResolution00FE,
+            (0, 0, 0xF, 0xF) => Self::HighResolution00FF,
+            (0xD, x, y, 0) => Self::BigSpriteDXY0(x, y),
+            (0xF, x, 3, 0) => Self::BigFontCharacterFX30(x),
+            (0xF, x, 7, 5) => Self::StoreFlagsFX75(x),
+            (0xF, x, 8, 5) => Self::LoadFlagsFX85(x),
+            _ => return None,
+        };
+        Some(ins)
+    }
+
+    /// Decodes the 2 bytes long instructions introduced by XO-CHIP.
+    fn new_xo_chip(i: u8, x: u8, y: u8, n: u8) -> Option<Self> {
+        let ins = match (i, x, y, n) {
+            (0, 0, 0xD, n) => Self::ScrollUp00DN(n),
+            (5, x, y, 2) => Self::StoreRangeToMemory5XY2(x.into(), y.into()),
+            (5, x, y, 3) => Self::LoadRangeFromMemory5XY3(x.into(), y.into()),
+            (0xF, planes, 0, 1) => Self::SelectPlanesFN01(planes),
+            (0xF, 0, 0, 2) => Self::LoadAudioPatternF002,
+            (0xF, x, 3, 0xA) => Self::SetPitchFX3A(x.into()),
+            _ => return None,
+        };
+        Some(ins)
+    }
+
+    pub(crate) const fn requires_pc_inc(self) -> usize {
+        match self {
+            Self::SubroutineCall2NNN(_)
+            | Self::Jump1NNN(_)
+            | Self::JumpWithOffsetBNNN(_)
+            | Self::Exit00FD => 0,
+            _ => self.size(),
+        }
+    }
+
+    /// Writes the mnemonic, formatting the addresses with `address`.
+    pub(crate) fn write_mnemonic(
+        self,
+        f: &mut fmt::Formatter<'_>,
+        address: impl Fn(usize) -> String,
+    ) -> fmt::Result {
+        let reg = |x: usize| format!("V{x:X}");
+        match self {
+            Self::Cls00E0 => write!(f, "CLS"),
+            Self::SubroutineReturn00EE => write!(f, "RET"),
+            Self::MachineCodeCall0NNN(nnn) => write!(f, "SYS {nnn:#05X}"),
+            Self::Jump1NNN(nnn) => write!(f, "JP {}", address(nnn.into())),
+            Self::SubroutineCall2NNN(nnn) => write!(f, "CALL {}", address(nnn.into())),
+            Self::SkipEqual3XNN(x, nn) => write!(f, "SE {}, {nn:#04X}", reg(x)),
+            Self::SkipNotEqual4XNN(x, nn) => write!(f, "SNE {}, {nn:#04X}", reg(x)),
+            Self::SkipIfEqual5XY0(x, y) => write!(f, "SE {}, {}", reg(x), reg(y)),
+            Self::SetVRegister6XNN(x, nn) => write!(f, "LD {}, {nn:#04X}", reg(x)),
+            Self::Add7XNN(x, nn) => write!(f, "ADD {}, {nn:#04X}", reg(x)),
+            Self::RegisterSet8XY0(x, y) => write!(f, "LD {}, {}", reg(x), reg(y)),
+            Self::Or8XY1(x, y) => write!(f, "OR {}, {}", reg(x), reg(y)),
+            Self::BinaryAnd8XY2(x, y) => write!(f, "AND {}, {}", reg(x), reg(y)),
+            Self::Xor8XY3(x, y) => write!(f, "XOR {}, {}", reg(x), reg(y)),
+            Self::RegisterAdd8XY4(x, y) => write!(f, "ADD {}, {}", reg(x), reg(y)),
+            Self::RegisterSub8XY5(x, y) => write!(f, "SUB {}, {}", reg(x), reg(y)),
+            Self::ShiftRight8XY6(x, y) => write!(f, "SHR {}, {}", reg(x), reg(y)),
+            Self::RegisterSubRev8XY7(x, y) => write!(f, "SUBN {}, {}", reg(x), reg(y)),
+            Self::ShiftLeft8XYE(x, y) => write!(f, "SHL {}, {}", reg(x), reg(y)),
+            Self::SkipIfNotEqual9XY0(x, y) => write!(f, "SNE {}, {}", reg(x), reg(y)),
+            Self::SetIndexRegisterANNN(nnn) => write!(f, "LD I, {}", address(nnn)),
+            Self::JumpWithOffsetBNNN(nnn) => write!(f, "JP V0, {}", address(nnn.into())),
+            Self::RandomCXNN(x, nn) => write!(f, "RND {}, {nn:#04X}", reg(x)),
+            Self::Dxyn(x, y, n) => write!(f, "DRW {}, {}, {n}", reg(x), reg(y)),
+            Self::SkipIfKeyPressedEX9E(x) => write!(f, "SKP {}", reg(x)),
+            Self::SkipIfKeyNotPressedEXA1(x) => write!(f, "SKNP {}", reg(x)),
+            Self::ReadDelayTimerFX07(x) => write!(f, "LD {}, DT", reg(x)),
+            Self::GetKeyFX0A(x) => write!(f, "LD {}, K", reg(x)),
+            Self::SetDelayTimerFX15(x) => write!(f, "LD DT, {}", reg(x)),
+            Self::SetSoundTimerFX18(x) => write!(f, "LD ST, {}", reg(x)),
+            Self::AddToIndexFX1E(x) => write!(f, "ADD I, {}", reg(x)),
+            Self::FontCharacterFX29(x) => write!(f, "LD F, {}", reg(x)),
+            Self::BinaryCodedDecimalConversionFX33(x) => write!(f, "LD B, {}", reg(x)),
+            Self::StoreRegistersToMemoryFX55(x) => write!(f, "LD [I], {}", reg(x)),
+            Self::LoadRegistersFromMemoryFX65(x) => write!(f, "LD {}, [I]", reg(x)),
+            Self::ScrollDown00CN(n) => write!(f, "SCD {n}"),
+            Self::ScrollRight00FB => write!(f, "SCR"),
+            Self::ScrollLeft00FC => write!(f, "SCL"),
+            Self::Exit00FD => write!(f, "EXIT"),
+            Self::LowResolution00FE => write!(f, "LOW"),
+            Self::HighResolution00FF => write!(f, "HIGH"),
+            Self::BigSpriteDXY0(x, y) => write!(f, "DRW {}, {}, 0", reg(x), reg(y)),
+            Self::BigFontCharacterFX30(x) => write!(f, "LD HF, {}", reg(x)),
+            Self::StoreFlagsFX75(x) => write!(f, "LD R, {}", reg(x)),
+            Self::LoadFlagsFX85(x) => write!(f, "LD {}, R", reg(x)),
+            Self::ScrollUp00DN(n) => write!(f, "SCU {n}"),
+            Self::StoreRangeToMemory5XY2(x, y) => write!(f, "SAVE {} - {}", reg(x), reg(y)),
+            Self::LoadRangeFromMemory5XY3(x, y) => write!(f, "LOAD {} - {}", reg(x), reg(y)),
+            Self::LongIndexF000NNNN(nnnn) => write!(f, "LD I, LONG {}", address(nnnn.into())),
+            Self::SelectPlanesFN01(n) => write!(f, "PLANE {n}"),
+            Self::LoadAudioPatternF002 => write!(f, "AUDIO"),
+            Self::SetPitchFX3A(x) => write!(f, "PITCH {}", reg(x)),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    /// Writes the conventional mnemonic, e.g. `LD V3, 0x12` or `DRW V0, V1, 5`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_mnemonic(f, |address| format!("{address:#05X}"))
+    }
+}
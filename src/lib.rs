@@ -1,22 +1,67 @@
-#![deny(clippy::all)]
-#![warn(clippy::pedantic)]
-#![deny(clippy::as_conversions)]
-#![warn(clippy::nursery)]
-#![warn(clippy::cargo)]
+use std::{
+    fmt,
+    io::{self, Write},
+    mem,
+    ops::{Range, RangeInclusive},
+    time::Duration,
+    vec,
+};
 
-use std::{thread::sleep, time::Duration, vec};
+use log::trace;
 
-use anyhow::{bail, Context, Ok, Result};
-use log::debug;
+pub mod asm;
+pub mod debugger;
+pub mod disasm;
+mod display;
+mod error;
+mod framebuffer;
+#[cfg(feature = "gdb")]
+pub mod gdb;
+mod heatmap;
+mod instruction;
+mod movie;
+pub mod octo;
+mod pacer;
+mod profile;
+mod quirks;
+mod random;
+mod rewind;
+mod scheduler;
+mod state;
+pub mod trace;
+
+use debugger::Accesses;
+use display::Display;
+pub use error::{Chip8Error, Result};
+pub use framebuffer::{Framebuffer, Rect};
+pub use heatmap::{MemoryHeatmap, SelfModification};
+pub use instruction::Instruction;
+pub use movie::{Movie, MoviePlayer};
+pub use pacer::FramePacer;
+pub use profile::{FramePixels, Profile, SubroutineProfile};
+pub use quirks::{MemoryIncrement, Quirks};
+pub use random::{CosmacVipRandom, Random, SeededRandom};
+pub use rewind::Rewind;
+use scheduler::Scheduler;
+pub use scheduler::Speed;
+use trace::{TraceEntry, Tracer};
 
 /// Number of horizontal sprites.
 pub const TERMINAL_WIDTH: usize = 64;
 /// Number of vertical sprites.
 pub const TERMINAL_HEIGHT: usize = 32;
+/// Number of horizontal pixels in SUPER-CHIP high resolution mode.
+pub const HIRES_WIDTH: usize = 128;
+/// Number of vertical pixels in SUPER-CHIP high resolution mode.
+pub const HIRES_HEIGHT: usize = 64;
 /// Frame rate per second.
 pub const FPS: u64 = 60;
 const RAM_SIZE: usize = 4096;
-const PROGRAM_START: usize = 512;
+const XO_CHIP_RAM_SIZE: usize = 0x10000;
+/// Address where the programs are loaded.
+pub const PROGRAM_START: usize = 512;
+/// Maximum number of return addresses on the call stack.
+pub const STACK_SIZE: usize = 16;
 
 // Font settings
 const FONT_ADDR: usize = 0x50;
@@ -39,186 +84,444 @@ const FONTS: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
+// XO-CHIP audio settings
+const DEFAULT_AUDIO_PATTERN: [u8; 16] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+];
+const DEFAULT_PITCH: u8 = 64;
+
+const BIG_FONT_ADDR: usize = FONT_ADDR + FONTS.len();
+const BIG_FONT_SIZE: usize = 10;
+const BIG_FONTS: [u8; 160] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
 
 /// Chip8 emulator.
 #[derive(Debug, Default)]
 pub struct Chip8 {
-    clock: u64,
-    pixels: Vec<Vec<bool>>,
+    scheduler: Scheduler,
+    frame: u64,
+    display: Display,
     ram: Vec<u8>,
     pc: usize,
+    opcode: u16,
     i: usize,
     stack: Vec<usize>,
     registers: [u8; 16],
     delay_timer: u8,
     sound_timer: u8,
     beeping: bool,
-    key_pressed: Option<u8>,
+    /// Bit N is set while key N is held down.
+    keys: u16,
     waiting_for_input: Option<usize>,
+    /// Key pressed while waiting for input, stored on release.
+    waiting_key: Option<u8>,
+    machine_code_call: MachineCodeCall,
+    quirks: Quirks,
+    platform: Platform,
+    rpl_flags: [u8; 16],
+    exited: bool,
+    audio_pattern: [u8; 16],
+    pitch: u8,
+    audio_changed: bool,
+    random: Box<dyn Random>,
+    profile: Option<Profile>,
+    heatmap: Option<MemoryHeatmap>,
+    tracer: Option<Tracer>,
+    accesses: Option<Accesses>,
 }
 
-/// Represents Chip8 instructions.
-#[derive(Debug, Clone, Copy)]
-enum Instruction {
-    Cls00E0,
-    SetIndexRegisterANNN(usize),
-    SetVRegister6XNN(usize, u8),
-    Dxyn(usize, usize, usize),
-    Add7XNN(usize, u8),
-    Jump1NNN(u16),
-    SubroutineCall2NNN(u16),
-    SubroutineReturn00EE,
-    SkipEqual3XNN(usize, u8),
-    SkipNotEqual4XNN(usize, u8),
-    BinaryCodedDecimalConversionFX33(usize),
-    FontCharacterFX29(usize),
-    SetDelayTimerFX15(usize),
-    ReadDelayTimerFX07(usize),
-    GetKeyFX0A(usize),
-    SetSoundTimerFX18(usize),
-    AddToIndexFX1E(usize),
-    StoreRegistersToMemoryFX55(usize),
-    LoadRegistersFromMemoryFX65(usize),
-    RandomCXNN(usize, u8),
-    SkipIfKeyPressedEX9E(usize),
-    SkipIfKeyNotPressedEXA1(usize),
-    BinaryAnd8XY2(usize, usize),
-    RegisterAdd8XY4(usize, usize),
-    RegisterSet8XY0(usize, usize),
-    RegisterSub8XY5(usize, usize),
-    RegisterSubRev8XY7(usize, usize),
-    ShiftRight8XY6(usize, usize),
-    ShiftLeft8XYE(usize, usize),
-    SkipIfEqual5XY0(usize, usize),
-    SkipIfNotEqual9XY0(usize, usize),
-    Xor8XY3(usize, usize),
+/// Chip8 variant whose instruction set is emulated.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Platform {
+    /// The original instruction set.
+    #[default]
+    Chip8,
+    /// SUPER-CHIP 1.1, adding high resolution mode, scrolling, 16x16 sprites,
+    /// a big hex font and RPL user flags.
+    SuperChip,
+    /// XO-CHIP, adding 64 KiB of RAM, two bitplanes, audio patterns and
+    /// long index loads on top of SUPER-CHIP.
+    XoChip,
 }
 
-impl Instruction {
-    fn new(b1: u8, b2: u8) -> Result<Self> {
-        let i = b1 >> 4;
-        let x = b1 & 0xf;
-        let y = b2 >> 4;
-        let n = usize::from(b2 & 0xf);
-        let nn = b2;
-        let nnn = u16::from_ne_bytes([nn, x]);
-        let x = usize::from(x);
-        let y = usize::from(y);
-        let ins = match (i, x, y, n, nn, nnn) {
-            (0, 0, 0xE, 0, _, _) => Self::Cls00E0,
-            (0xA, _, _, _, _, nnn) => Self::SetIndexRegisterANNN(nnn.into()),
-            (1, _, _, _, _, nnn) => Self::Jump1NNN(nnn),
-            (6, x, _, _, nn, _) => Self::SetVRegister6XNN(x, nn),
-            (0xD, x, y, n, _, _) => Self::Dxyn(x, y, n),
-            (2, _, _, _, _, nnn) => Self::SubroutineCall2NNN(nnn),
-            (0, 0, 0xE, 0xE, _, _) => Self::SubroutineReturn00EE,
-            (3, x, _, _, nn, _) => Self::SkipEqual3XNN(x, nn),
-            (4, x, _, _, nn, _) => Self::SkipNotEqual4XNN(x, nn),
-            (5, x, y, 0, _, _) => Self::SkipIfEqual5XY0(x, y),
-            (9, x, y, 0, _, _) => Self::SkipIfNotEqual9XY0(x, y),
-            (7, x, _, _, nn, _) => Self::Add7XNN(x, nn),
-            (8, x, y, 3, _, _) => Self::Xor8XY3(x, y),
-            (0xF, x, 3, 3, _, _) => Self::BinaryCodedDecimalConversionFX33(x),
-            (0xF, x, 2, 9, _, _) => Self::FontCharacterFX29(x),
-            (0xF, x, 1, 5, _, _) => Self::SetDelayTimerFX15(x),
-            (0xF, x, 0, 7, _, _) => Self::ReadDelayTimerFX07(x),
-            (0xF, x, 0, 0xA, _, _) => Self::GetKeyFX0A(x),
-            (0xF, x, 1, 8, _, _) => Self::SetSoundTimerFX18(x),
-            (0xF, x, 1, 0xE, _, _) => Self::AddToIndexFX1E(x),
-            (0xF, x, 5, 5, _, _) => Self::StoreRegistersToMemoryFX55(x),
-            (0xF, x, 6, 5, _, _) => Self::LoadRegistersFromMemoryFX65(x),
-            (0xC, x, _, _, nn, _) => Self::RandomCXNN(x, nn),
-            (0xE, x, 9, 0xE, _, _) => Self::SkipIfKeyPressedEX9E(x),
-            (0xE, x, 0xA, 1, _, _) => Self::SkipIfKeyNotPressedEXA1(x),
-            (8, x, y, 2, _, _) => Self::BinaryAnd8XY2(x, y),
-            (8, x, y, 4, _, _) => Self::RegisterAdd8XY4(x, y),
-            (8, x, y, 0, _, _) => Self::RegisterSet8XY0(x, y),
-            (8, x, y, 5, _, _) => Self::RegisterSub8XY5(x, y),
-            (8, x, y, 6, _, _) => Self::ShiftRight8XY6(x, y),
-            (8, x, y, 0xE, _, _) => Self::ShiftLeft8XYE(x, y),
-            (8, x, y, 7, _, _) => Self::RegisterSubRev8XY7(x, y),
-            _ => {
-                std::thread::sleep(Duration::from_secs(5));
-                bail!("unimplemented instruction: {} {} {} {}", i, x, y, n)
-            }
-        };
-        Ok(ins)
-    }
-
-    const fn requires_pc_inc(self) -> usize {
+impl Platform {
+    /// Returns the size of the RAM of the platform in bytes.
+    const fn ram_size(self) -> usize {
         match self {
-            Self::SubroutineCall2NNN(_) | Self::Jump1NNN(_) => 0,
-            _ => 2,
+            Self::Chip8 | Self::SuperChip => RAM_SIZE,
+            Self::XoChip => XO_CHIP_RAM_SIZE,
         }
     }
 }
 
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Chip8 => "CHIP-8",
+            Self::SuperChip => "SUPER-CHIP",
+            Self::XoChip => "XO-CHIP",
+        })
+    }
+}
+
+/// Determines how `0NNN` (call machine code routine) instructions are handled.
+///
+/// The original interpreters jumped into native RCA 1802 code, which can not
+/// be emulated, so most ROMs never use it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MachineCodeCall {
+    /// Skips the instruction.
+    #[default]
+    Ignore,
+    /// Fails with an error.
+    Error,
+}
+
 impl Chip8 {
     /// Returns a Chip8 instance.
     ///
     /// # Arguments
     ///
     /// * `clock` - refers to the instructions per second. The common value used is `700`.
+    ///   See [`Chip8::set_speed`] for other ways to set the speed.
     #[must_use]
     pub fn new(clock: u64) -> Self {
-        let mut ram = vec![0; RAM_SIZE];
+        Self::with_quirks(clock, Quirks::default())
+    }
+
+    /// Returns a Chip8 instance emulating the given interpreter quirks.
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - refers to the instructions per second. The common value used is `700`.
+    /// * `quirks` - the interpreter behaviours, see [`Quirks::cosmac_vip`] and the other presets.
+    #[must_use]
+    pub fn with_quirks(clock: u64, quirks: Quirks) -> Self {
+        Self::with_platform(clock, Platform::Chip8, quirks)
+    }
+
+    /// Returns a Chip8 instance emulating the given platform and interpreter quirks.
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - refers to the instructions per second. The common value used is `700`.
+    /// * `platform` - the instruction set to emulate.
+    /// * `quirks` - the interpreter behaviours, see [`Quirks::cosmac_vip`] and the other presets.
+    #[must_use]
+    pub fn with_platform(clock: u64, platform: Platform, quirks: Quirks) -> Self {
+        Self::with_speed(Speed::InstructionsPerSecond(clock), platform, quirks)
+    }
+
+    /// Returns a Chip8 instance running at `speed` from the first frame on,
+    /// unlike [`Chip8::set_speed`] which waits for the next frame.
+    pub(crate) fn with_speed(speed: Speed, platform: Platform, quirks: Quirks) -> Self {
+        let mut ram = vec![0; platform.ram_size()];
         ram[FONT_ADDR..FONT_ADDR + FONTS.len()].copy_from_slice(&FONTS);
+        ram[BIG_FONT_ADDR..BIG_FONT_ADDR + BIG_FONTS.len()].copy_from_slice(&BIG_FONTS);
         Self {
-            clock,
-            pixels: vec![vec![false; TERMINAL_WIDTH]; TERMINAL_HEIGHT],
+            scheduler: Scheduler::new(speed),
+            display: Display::new(),
             ram,
             pc: PROGRAM_START,
+            quirks,
+            platform,
+            audio_pattern: DEFAULT_AUDIO_PATTERN,
+            pitch: DEFAULT_PITCH,
             ..Default::default()
         }
     }
 
-    /// Fetches, decodes and executes Chip8 instructions from RAM.
+    /// Runs the rest of the current frame.
     ///
     /// This function is supposed to be called [FPS] times per second.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Panics when an invalid (or unimplemented) instruction encountered.
-    pub fn tick(&mut self, graphics: &mut impl Graphics, audio: &mut impl Audio) {
-        self.decrease_timers();
-        for _ in 0..self.clock / FPS {
-            sleep(Duration::from_millis(1000 / self.clock));
-            if self.waiting_for_input.is_some() {
-                return;
-            }
-            let inst = self
-                .fetch_and_decode_next_instruction()
-                .expect("instruction failure");
-            self.execute_instruction(inst, graphics)
-                .unwrap_or_else(|_| panic!("failed to execute instruction: {inst:?}"));
-            self.pc += inst.requires_pc_inc();
-            if self.sound_timer > 0 && !self.beeping {
-                audio.start_beep();
-                self.beeping = true;
-            } else if self.sound_timer == 0 && self.beeping {
-                audio.stop_beep();
-                self.beeping = false;
+    /// See [`Chip8::step`].
+    #[deprecated(note = "use `Chip8::run_frame` instead")]
+    pub fn tick(&mut self, graphics: &mut impl Graphics, audio: &mut impl Audio) -> Result<()> {
+        self.run_frame(graphics, audio)
+    }
+
+    /// Runs the rest of the current 60 Hz frame and decreases the timers at its end.
+    ///
+    /// The number of instructions in a frame follows the [`Speed`], so that
+    /// the timers run at exactly [FPS] Hz of emulated time for any speed.
+    ///
+    /// It never blocks, so it is supposed to be called [FPS] times per second
+    /// by the client, e.g. with the help of a [`FramePacer`], to run in real time.
+    ///
+    /// # Errors
+    ///
+    /// See [`Chip8::step`].
+    pub fn run_frame(
+        &mut self,
+        graphics: &mut impl Graphics,
+        audio: &mut impl Audio,
+    ) -> Result<()> {
+        while self.scheduler.budget() > 0 {
+            self.execute_next_instruction(graphics, audio)?;
+        }
+        self.end_frame(graphics, audio);
+        Ok(())
+    }
+
+    /// Runs as many whole frames as fit into `duration` of emulated time.
+    ///
+    /// # Errors
+    ///
+    /// See [`Chip8::step`].
+    pub fn run_for(
+        &mut self,
+        duration: Duration,
+        graphics: &mut impl Graphics,
+        audio: &mut impl Audio,
+    ) -> Result<()> {
+        let frames = duration.as_nanos() * u128::from(FPS) / Duration::from_secs(1).as_nanos();
+        for _ in 0..frames {
+            self.run_frame(graphics, audio)?;
+        }
+        Ok(())
+    }
+
+    /// Executes `cycles` instructions, crossing frame boundaries as needed.
+    ///
+    /// # Errors
+    ///
+    /// See [`Chip8::step`].
+    pub fn run_cycles(
+        &mut self,
+        cycles: u64,
+        graphics: &mut impl Graphics,
+        audio: &mut impl Audio,
+    ) -> Result<()> {
+        for _ in 0..cycles {
+            self.step(graphics, audio)?;
+        }
+        Ok(())
+    }
+
+    /// Fetches, decodes and executes exactly one instruction from RAM.
+    ///
+    /// If the current frame has no instructions left, the frame ends first and
+    /// the timers are decreased. Nothing is executed while waiting for a key
+    /// press (`FX0A`) or after the program exited, but the emulated time
+    /// still passes. At a speed of zero no instruction ever runs, so each
+    /// step ends a frame instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when an invalid instruction is encountered or an
+    /// instruction fails, e.g. by accessing memory outside of the RAM. The
+    /// program counter is left pointing to the failed instruction.
+    pub fn step(&mut self, graphics: &mut impl Graphics, audio: &mut impl Audio) -> Result<()> {
+        if !self.scheduler.is_running() {
+            self.end_frame(graphics, audio);
+            return Ok(());
+        }
+        while self.scheduler.budget() == 0 {
+            self.end_frame(graphics, audio);
+        }
+        self.execute_next_instruction(graphics, audio)
+    }
+
+    /// Returns the emulation speed.
+    #[must_use]
+    pub const fn speed(&self) -> Speed {
+        self.scheduler.speed()
+    }
+
+    /// Changes the emulation speed, starting from the next frame.
+    pub const fn set_speed(&mut self, speed: Speed) {
+        self.scheduler.set_speed(speed);
+    }
+
+    /// Starts collecting a new [`Profile`] of the execution, or stops it.
+    ///
+    /// While profiling, every instruction is counted by address and
+    /// variant, the cycles are attributed to the subroutines being called,
+    /// and the pixels drawn and erased by the sprites of each frame are
+    /// counted.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = enabled.then(|| Profile::new(self.frame, self.stack.len()));
+    }
+
+    /// Returns the profile collected since profiling was enabled.
+    #[must_use]
+    pub const fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Starts recording a new [`MemoryHeatmap`] of the accesses to RAM, or
+    /// stops it.
+    ///
+    /// While recording, every byte fetched as code, read or written by an
+    /// instruction is counted, and the writes to code are flagged.
+    pub fn set_memory_heatmap(&mut self, enabled: bool) {
+        self.heatmap = enabled.then(|| MemoryHeatmap::new(self.ram.len()));
+    }
+
+    /// Returns the heatmap recorded since it was enabled.
+    #[must_use]
+    pub const fn memory_heatmap(&self) -> Option<&MemoryHeatmap> {
+        self.heatmap.as_ref()
+    }
+
+    /// Starts recording the registers and memory accessed by the
+    /// instructions for the watchpoints of the
+    /// [`Debugger`](debugger::Debugger), or stops it.
+    pub(crate) fn record_accesses(&mut self, enabled: bool) {
+        if !enabled {
+            self.accesses = None;
+        } else if self.accesses.is_none() {
+            self.accesses = Some(Accesses::default());
+        }
+    }
+
+    /// Returns the accesses recorded since the previous call.
+    pub(crate) fn take_accesses(&mut self) -> Accesses {
+        self.accesses.as_mut().map(mem::take).unwrap_or_default()
+    }
+
+    /// Starts writing a [trace](mod@crate::trace) line to `writer` before every
+    /// executed instruction, replacing the current trace.
+    ///
+    /// The lines are small and many, so files are better wrapped in an
+    /// [`io::BufWriter`].
+    ///
+    /// # Errors
+    ///
+    /// See [`Chip8::stop_trace`], for the replaced trace.
+    pub fn start_trace(&mut self, writer: impl Write + Send + Sync + 'static) -> io::Result<()> {
+        let result = self.stop_trace();
+        self.tracer = Some(Tracer::new(writer));
+        result
+    }
+
+    /// Stops the trace and flushes its writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of writing the trace, after which no more
+    /// lines were written.
+    pub fn stop_trace(&mut self) -> io::Result<()> {
+        self.tracer.take().map_or(Ok(()), Tracer::finish)
+    }
+
+    /// Returns the number of frames run since the start.
+    #[must_use]
+    pub const fn frame_count(&self) -> u64 {
+        self.frame
+    }
+
+    /// Executes the instruction at the program counter within the current frame.
+    fn execute_next_instruction(
+        &mut self,
+        graphics: &mut impl Graphics,
+        audio: &mut impl Audio,
+    ) -> Result<()> {
+        if self.waiting_for_input.is_some() || self.exited {
+            self.scheduler.idle();
+            return Ok(());
+        }
+        let pc = self.pc;
+        let cycles = self.scheduler.cycles();
+        let inst = self.fetch_and_decode_next_instruction()?;
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.record_execute(pc..pc + inst.size());
+        }
+        if self.tracer.is_some() {
+            let entry = self.trace_entry(inst);
+            if let Some(tracer) = &mut self.tracer {
+                tracer.write(&entry);
             }
         }
+        self.execute_instruction(inst, graphics)?;
+        self.pc += inst.requires_pc_inc();
+        self.update_audio(audio);
+        self.scheduler.consume(inst);
+        if let Some(profile) = &mut self.profile {
+            let cycles = self.scheduler.cycles() - cycles;
+            profile.record(pc, inst, cycles, self.stack.len());
+        }
+        Ok(())
+    }
+
+    /// Ends the current frame: presents the display, decreases the timers and
+    /// starts the next frame.
+    fn end_frame(&mut self, graphics: &mut impl Graphics, audio: &mut impl Audio) {
+        self.display.present(graphics);
+        self.decrease_timers();
+        self.update_audio(audio);
+        self.frame += 1;
+        self.random.next_frame();
+        self.scheduler.next_frame();
+        if let Some(profile) = &mut self.profile {
+            profile.next_frame();
+        }
+    }
+
+    /// Notifies the audio about pattern changes and starts or stops the beep
+    /// according to the sound timer.
+    fn update_audio(&mut self, audio: &mut impl Audio) {
+        if self.audio_changed {
+            audio.set_pattern(self.audio_pattern, self.pitch);
+            self.audio_changed = false;
+        }
+        if self.sound_timer > 0 && !self.beeping {
+            audio.start_beep();
+            self.beeping = true;
+        } else if self.sound_timer == 0 && self.beeping {
+            audio.stop_beep();
+            self.beeping = false;
+        }
     }
 
     /// Decreases sound and delay timers.
-    fn decrease_timers(&mut self) {
+    const fn decrease_timers(&mut self) {
         self.delay_timer = self.delay_timer.saturating_sub(1);
         self.sound_timer = self.sound_timer.saturating_sub(1);
     }
 
     fn fetch_and_decode_next_instruction(&mut self) -> Result<Instruction> {
-        let b1 = *self
-            .ram
-            .get(self.pc)
-            .unwrap_or_else(|| panic!("invalid memory address: {}", self.pc));
-        let b2 = *self
+        let pc = self.pc;
+        let out_of_range = |address| Chip8Error::AddressOutOfRange {
+            pc,
+            opcode: 0,
+            address,
+        };
+        let [b1, b2] = *self
             .ram
-            .get(self.pc + 1)
-            .unwrap_or_else(|| panic!("invalid memory address: {}", self.pc));
-        let inst = Instruction::new(b1, b2).context("failed to decode instruction")?;
-        Ok(inst)
+            .get(pc..pc + 2)
+            .and_then(|bytes| <&[u8; 2]>::try_from(bytes).ok())
+            .ok_or_else(|| out_of_range(pc.max(self.ram.len())))?;
+        self.opcode = u16::from_be_bytes([b1, b2]);
+        let bytes = &self.ram[pc..self.ram.len().min(pc + 4)];
+        if self.platform >= Platform::XoChip && self.opcode == 0xF000 && bytes.len() < 4 {
+            return Err(out_of_range(self.ram.len()));
+        }
+        Instruction::decode(bytes, self.platform).ok_or(Chip8Error::UnknownOpcode {
+            pc,
+            opcode: self.opcode,
+        })
     }
 
     /// Executes the Chip8 instruction.
@@ -228,65 +531,21 @@ impl Chip8 {
         inst: Instruction,
         graphics: &mut impl Graphics,
     ) -> Result<()> {
-        debug!(
-            "pc = {}, index = {}, registers = {:?}\n",
-            self.pc, self.i, self.registers
-        );
-        debug!("{:?}", inst);
+        trace!("{}", self.trace_entry(inst));
         match inst {
-            Instruction::Cls00E0 => {
-                for (y, row) in self.pixels.iter().enumerate() {
-                    for (x, pixel) in row.iter().enumerate() {
-                        if *pixel {
-                            graphics.clear_pixel(x, y);
-                        }
-                    }
-                }
-                self.pixels = vec![vec![false; TERMINAL_WIDTH]; TERMINAL_HEIGHT];
-            }
+            Instruction::Cls00E0 => self.display.clear(graphics),
             Instruction::SetIndexRegisterANNN(nnn) => self.i = nnn,
-            Instruction::SetVRegister6XNN(x, nn) => self.registers[x] = nn,
-            Instruction::Dxyn(x, y, n) => {
-                let x_org = usize::from(self.registers[x]) % TERMINAL_WIDTH;
-                let mut y = usize::from(self.registers[y]) % TERMINAL_HEIGHT;
-                self.registers[15] = 0;
-                let mut collision = false;
-                let sprites = &self.ram[self.i..self.i + n];
-                for row in sprites {
-                    let mut x = x_org;
-                    for i in (0..8).rev() {
-                        let pixel = (row >> i) & 1;
-                        if pixel == 1 {
-                            let is_pixel_on =
-                                self.is_pixel_on(x, y).context("failed to check pixel")?;
-                            self.pixels[y][x] = !is_pixel_on;
-                            if is_pixel_on {
-                                graphics.clear_pixel(x, y);
-                                collision = true;
-                            } else {
-                                graphics.draw_pixel(x, y);
-                            }
-                        }
-                        x += 1;
-                        if x == TERMINAL_WIDTH {
-                            break;
-                        }
-                    }
-                    y += 1;
-                    if y == TERMINAL_HEIGHT {
-                        break;
-                    }
-                }
-                if collision {
-                    self.registers[15] = 1;
-                }
-            }
+            Instruction::SetVRegister6XNN(x, nn) => self.set_register(x, nn),
+            Instruction::Dxyn(x, y, n) => self.draw_sprite(x, y, 8, n, graphics)?,
             Instruction::Add7XNN(x, nn) => {
-                let (res, _) = self.registers[x].overflowing_add(nn);
-                self.registers[x] = res;
+                let (res, _) = self.register(x).overflowing_add(nn);
+                self.set_register(x, res);
             }
             Instruction::Jump1NNN(nnn) => self.pc = nnn.into(),
             Instruction::SubroutineCall2NNN(nnn) => {
+                if self.stack.len() == STACK_SIZE {
+                    return Err(Chip8Error::StackOverflow { pc: self.pc });
+                }
                 self.stack.push(self.pc);
                 self.pc = usize::from(nnn);
             }
@@ -294,174 +553,715 @@ impl Chip8 {
                 self.pc = self
                     .stack
                     .pop()
-                    .context("failed to return from subroutine: stack underflow")?;
+                    .ok_or(Chip8Error::StackUnderflow { pc: self.pc })?;
             }
             Instruction::SkipEqual3XNN(x, nn) => {
-                if self.registers[x] == nn {
-                    self.pc += 2;
+                if self.register(x) == nn {
+                    self.skip_next_instruction();
                 }
             }
             Instruction::SkipNotEqual4XNN(x, nn) => {
-                if self.registers[x] != nn {
-                    self.pc += 2;
+                if self.register(x) != nn {
+                    self.skip_next_instruction();
                 }
             }
             Instruction::BinaryCodedDecimalConversionFX33(x) => {
-                let val = self.registers[x];
-                self.ram[self.i] = val / 100;
-                self.ram[self.i + 1] = (val % 100) / 10;
-                self.ram[self.i + 2] = val % 10;
+                let val = self.register(x);
+                self.memory_at_index_mut(3)?.copy_from_slice(&[
+                    val / 100,
+                    (val % 100) / 10,
+                    val % 10,
+                ]);
             }
             Instruction::FontCharacterFX29(x) => {
-                self.i = FONT_ADDR + (usize::from(self.registers[x]) * FONT_SIZE);
+                self.i = FONT_ADDR + (usize::from(self.register(x)) * FONT_SIZE);
+                self.record_read(self.i..self.i + FONT_SIZE);
             }
             Instruction::SetDelayTimerFX15(x) => {
-                self.delay_timer = self.registers[x];
+                self.delay_timer = self.register(x);
             }
-            Instruction::ReadDelayTimerFX07(x) => self.registers[x] = self.delay_timer,
-            Instruction::SetSoundTimerFX18(x) => self.sound_timer = self.registers[x],
+            Instruction::ReadDelayTimerFX07(x) => self.set_register(x, self.delay_timer),
+            Instruction::SetSoundTimerFX18(x) => self.sound_timer = self.register(x),
             Instruction::AddToIndexFX1E(x) => {
-                let (res, overflow) = self.i.overflowing_add(self.registers[x].into());
+                let (res, overflow) = self.i.overflowing_add(self.register(x).into());
                 self.i = res;
                 if overflow {
-                    self.registers[15] = 1;
+                    self.set_register(15, 1);
                 }
             }
             Instruction::StoreRegistersToMemoryFX55(x) => {
-                self.ram[self.i..=self.i + x].copy_from_slice(&self.registers[0..=x]);
+                self.record_register_reads(0..=x);
+                let registers = self.registers;
+                self.memory_at_index_mut(x + 1)?
+                    .copy_from_slice(&registers[0..=x]);
+                self.increment_index_after_memory_access(x);
             }
             Instruction::LoadRegistersFromMemoryFX65(x) => {
-                let data = &self.ram[self.i..=self.i + x];
-                self.registers[0..=x].copy_from_slice(data);
+                let data = self.memory_at_index(x + 1)?.to_vec();
+                self.record_register_writes(0..=x);
+                self.registers[0..=x].copy_from_slice(&data);
+                self.increment_index_after_memory_access(x);
             }
             Instruction::RandomCXNN(x, nn) => {
-                let r: u8 = rand::random();
-                self.registers[x] = r & nn;
+                let value = self.random.next_byte() & nn;
+                self.set_register(x, value);
             }
             Instruction::SkipIfKeyPressedEX9E(x) => {
-                if self.key_pressed == Some(self.registers[x]) {
-                    self.pc += 2;
+                let key = self.register(x);
+                if self.is_key_pressed(key) {
+                    self.skip_next_instruction();
                 }
             }
             Instruction::SkipIfKeyNotPressedEXA1(x) => {
-                if self.key_pressed != Some(self.registers[x]) {
-                    self.pc += 2;
+                let key = self.register(x);
+                if !self.is_key_pressed(key) {
+                    self.skip_next_instruction();
                 }
             }
             Instruction::BinaryAnd8XY2(x, y) => {
-                self.registers[x] &= self.registers[y];
+                let value = self.register(x) & self.register(y);
+                self.set_register(x, value);
+                self.reset_flag_after_logic_operation();
             }
             Instruction::RegisterAdd8XY4(x, y) => {
-                let (res, carry) = self.registers[x].overflowing_add(self.registers[y]);
-                self.registers[x] = res;
-                self.registers[15] = u8::from(carry);
+                let (res, carry) = self.register(x).overflowing_add(self.register(y));
+                self.set_register(x, res);
+                self.set_register(15, u8::from(carry));
             }
             Instruction::RegisterSet8XY0(x, y) => {
-                self.registers[x] = self.registers[y];
+                let value = self.register(y);
+                self.set_register(x, value);
             }
             Instruction::RegisterSub8XY5(x, y) => {
-                let (res, carry) = self.registers[x].overflowing_sub(self.registers[y]);
-                self.registers[x] = res;
-                self.registers[15] = u8::from(!carry);
+                let (res, carry) = self.register(x).overflowing_sub(self.register(y));
+                self.set_register(x, res);
+                self.set_register(15, u8::from(!carry));
             }
             Instruction::RegisterSubRev8XY7(x, y) => {
-                let (res, carry) = self.registers[y].overflowing_sub(self.registers[x]);
-                self.registers[x] = res;
-                self.registers[15] = u8::from(!carry);
+                let (res, carry) = self.register(y).overflowing_sub(self.register(x));
+                self.set_register(x, res);
+                self.set_register(15, u8::from(!carry));
             }
-            Instruction::GetKeyFX0A(x) => self.waiting_for_input = Some(x),
-            Instruction::ShiftRight8XY6(x, _) => {
-                self.registers[15] = self.registers[x] & 1u8;
-                self.registers[x] >>= 1;
+            Instruction::GetKeyFX0A(x) => {
+                self.waiting_for_input = Some(x);
+                self.waiting_key = None;
             }
-            Instruction::ShiftLeft8XYE(x, _) => {
-                self.registers[15] = self.registers[x] & (1u8 << 7);
-                self.registers[x] <<= 1;
+            Instruction::ShiftRight8XY6(x, y) => {
+                let val = if self.quirks.shift_uses_vy {
+                    self.register(y)
+                } else {
+                    self.register(x)
+                };
+                self.set_register(x, val >> 1);
+                self.set_register(15, val & 1u8);
+            }
+            Instruction::ShiftLeft8XYE(x, y) => {
+                let val = if self.quirks.shift_uses_vy {
+                    self.register(y)
+                } else {
+                    self.register(x)
+                };
+                self.set_register(x, val << 1);
+                self.set_register(15, val >> 7);
             }
             Instruction::SkipIfEqual5XY0(x, y) => {
-                if self.registers[x] == self.registers[y] {
-                    self.pc += 2;
+                if self.register(x) == self.register(y) {
+                    self.skip_next_instruction();
                 }
             }
             Instruction::SkipIfNotEqual9XY0(x, y) => {
-                if self.registers[x] != self.registers[y] {
-                    self.pc += 2;
+                if self.register(x) != self.register(y) {
+                    self.skip_next_instruction();
                 }
             }
             Instruction::Xor8XY3(x, y) => {
-                self.registers[x] ^= self.registers[y];
+                let value = self.register(x) ^ self.register(y);
+                self.set_register(x, value);
+                self.reset_flag_after_logic_operation();
+            }
+            Instruction::Or8XY1(x, y) => {
+                let value = self.register(x) | self.register(y);
+                self.set_register(x, value);
+                self.reset_flag_after_logic_operation();
+            }
+            Instruction::JumpWithOffsetBNNN(nnn) => {
+                let offset = if self.quirks.jump_uses_vx {
+                    self.register(usize::from(nnn >> 8))
+                } else {
+                    self.register(0)
+                };
+                self.pc = usize::from(nnn) + usize::from(offset);
+            }
+            Instruction::MachineCodeCall0NNN(nnn) => {
+                if self.machine_code_call == MachineCodeCall::Error {
+                    return Err(Chip8Error::MachineCodeCall {
+                        pc: self.pc,
+                        opcode: nnn,
+                    });
+                }
+            }
+            Instruction::ScrollDown00CN(n) => self.display.scroll(0, n.into(), graphics),
+            Instruction::ScrollRight00FB => self.display.scroll(4, 0, graphics),
+            Instruction::ScrollLeft00FC => self.display.scroll(-4, 0, graphics),
+            Instruction::Exit00FD => self.exited = true,
+            Instruction::LowResolution00FE => {
+                let clear = self.platform >= Platform::XoChip;
+                self.display.set_hires(false, clear, graphics);
+            }
+            Instruction::HighResolution00FF => {
+                let clear = self.platform >= Platform::XoChip;
+                self.display.set_hires(true, clear, graphics);
+            }
+            Instruction::BigSpriteDXY0(x, y) => self.draw_sprite(x, y, 16, 16, graphics)?,
+            Instruction::BigFontCharacterFX30(x) => {
+                self.i = BIG_FONT_ADDR + (usize::from(self.register(x) & 0xF) * BIG_FONT_SIZE);
+                self.record_read(self.i..self.i + BIG_FONT_SIZE);
+            }
+            Instruction::StoreFlagsFX75(x) => {
+                self.record_register_reads(0..=x);
+                self.rpl_flags[0..=x].copy_from_slice(&self.registers[0..=x]);
+            }
+            Instruction::LoadFlagsFX85(x) => {
+                self.record_register_writes(0..=x);
+                self.registers[0..=x].copy_from_slice(&self.rpl_flags[0..=x]);
+            }
+            Instruction::ScrollUp00DN(n) => self.display.scroll(0, -isize::from(n), graphics),
+            Instruction::StoreRangeToMemory5XY2(x, y) => {
+                self.record_register_reads(x.min(y)..=x.max(y));
+                let registers = self.registers;
+                let memory = self.memory_at_index_mut(x.abs_diff(y) + 1)?;
+                for (byte, reg) in memory.iter_mut().zip(register_range(x, y)) {
+                    *byte = registers[reg];
+                }
+            }
+            Instruction::LoadRangeFromMemory5XY3(x, y) => {
+                let memory = self.memory_at_index(x.abs_diff(y) + 1)?.to_vec();
+                self.record_register_writes(x.min(y)..=x.max(y));
+                for (byte, reg) in memory.into_iter().zip(register_range(x, y)) {
+                    self.registers[reg] = byte;
+                }
+            }
+            Instruction::LongIndexF000NNNN(nnnn) => self.i = nnnn.into(),
+            Instruction::SelectPlanesFN01(n) => self.display.select_planes(n),
+            Instruction::LoadAudioPatternF002 => {
+                let pattern = self.memory_at_index(16)?.to_vec();
+                self.audio_pattern.copy_from_slice(&pattern);
+                self.audio_changed = true;
+            }
+            Instruction::SetPitchFX3A(x) => {
+                self.pitch = self.register(x);
+                self.audio_changed = true;
             }
         }
         Ok(())
     }
 
-    /// Returns true if the pixel at the coordinates is on, otherwise false.
+    /// Returns the trace entry of `inst` about to be executed.
+    const fn trace_entry(&self, inst: Instruction) -> TraceEntry {
+        TraceEntry {
+            cycles: self.scheduler.cycles(),
+            pc: self.pc,
+            instruction: inst,
+            registers: self.registers,
+            i: self.i,
+            stack_depth: self.stack.len(),
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        }
+    }
+
+    /// Returns `len` bytes of RAM starting at the index register.
+    fn memory_at_index(&mut self, len: usize) -> Result<&[u8]> {
+        let out_of_range = self.index_out_of_range();
+        self.record_read(self.i..self.i + len);
+        self.ram.get(self.i..self.i + len).ok_or(out_of_range)
+    }
+
+    /// Returns `len` bytes of mutable RAM starting at the index register.
+    fn memory_at_index_mut(&mut self, len: usize) -> Result<&mut [u8]> {
+        let out_of_range = self.index_out_of_range();
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.record_write(self.i..self.i + len, self.pc, self.frame);
+        }
+        if let Some(accesses) = &mut self.accesses {
+            accesses.write_memory(self.i..self.i + len);
+        }
+        self.ram.get_mut(self.i..self.i + len).ok_or(out_of_range)
+    }
+
+    /// Accounts for an instruction reading `range` in the memory heatmap
+    /// and the recorded accesses.
+    fn record_read(&mut self, range: Range<usize>) {
+        if let Some(accesses) = &mut self.accesses {
+            accesses.read_memory(range.clone());
+        }
+        if let Some(heatmap) = &mut self.heatmap {
+            heatmap.record_read(range);
+        }
+    }
+
+    /// Returns `VX`, recording the read in the accesses.
+    fn register(&mut self, x: usize) -> u8 {
+        self.record_register_reads(x..=x);
+        self.registers[x]
+    }
+
+    /// Sets `VX`, recording the write in the accesses.
+    fn set_register(&mut self, x: usize, value: u8) {
+        self.record_register_writes(x..=x);
+        self.registers[x] = value;
+    }
+
+    /// Records a read of the registers in `registers` in the accesses.
+    fn record_register_reads(&mut self, registers: RangeInclusive<usize>) {
+        if let Some(accesses) = &mut self.accesses {
+            accesses.read_registers(registers);
+        }
+    }
+
+    /// Records a write to the registers in `registers` in the accesses.
+    fn record_register_writes(&mut self, registers: RangeInclusive<usize>) {
+        if let Some(accesses) = &mut self.accesses {
+            accesses.write_registers(registers);
+        }
+    }
+
+    /// Returns the error raised when the index register points outside of the RAM.
+    fn index_out_of_range(&self) -> Chip8Error {
+        Chip8Error::AddressOutOfRange {
+            pc: self.pc,
+            opcode: self.opcode,
+            address: self.i.max(self.ram.len()),
+        }
+    }
+
+    /// Skips the next instruction, which is 4 bytes long if it is the XO-CHIP `F000 NNNN`.
+    fn skip_next_instruction(&mut self) {
+        let next = self.ram.get(self.pc + 2..self.pc + 4);
+        if self.platform >= Platform::XoChip && next == Some(&[0xF0, 0x00]) {
+            self.pc += 4;
+        } else {
+            self.pc += 2;
+        }
+    }
+
+    /// Resets VF after `8XY1`/`8XY2`/`8XY3` if the quirk is enabled.
+    fn reset_flag_after_logic_operation(&mut self) {
+        if self.quirks.logic_resets_vf {
+            self.set_register(15, 0);
+        }
+    }
+
+    /// Moves the index register after `FX55`/`FX65` according to the quirks.
+    const fn increment_index_after_memory_access(&mut self, x: usize) {
+        match self.quirks.memory_increment {
+            MemoryIncrement::None => {}
+            MemoryIncrement::X => self.i += x,
+            MemoryIncrement::XPlusOne => self.i += x + 1,
+        }
+    }
+
+    /// Draws the sprite pointed by the index register at (VX, VY) and sets VF on collision.
     ///
-    /// If the coordinates is out of the screen area it returns an Error.
-    fn is_pixel_on(&self, x: usize, y: usize) -> Result<bool> {
-        check_coordinates(x, y)?;
-        Ok(self.pixels[y][x])
+    /// # Arguments
+    ///
+    /// * `width` - the sprite width in pixels, either `8` or `16`.
+    /// * `height` - the number of sprite rows.
+    fn draw_sprite(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        graphics: &mut impl Graphics,
+    ) -> Result<()> {
+        let pos = (usize::from(self.register(x)), usize::from(self.register(y)));
+        let size = height * width / 8 * self.display.selected_plane_count();
+        let sprite = self.memory_at_index(size)?.to_vec();
+        if self.quirks.display_wait {
+            self.scheduler.wait_for_next_frame();
+        }
+        let (drawn, erased) =
+            self.display
+                .draw_sprite(pos, &sprite, width, self.quirks.clip_sprites, graphics);
+        if let Some(profile) = &mut self.profile {
+            profile.record_sprite(drawn, erased);
+        }
+        let collision = erased > 0;
+        self.set_register(15, u8::from(collision));
+        let rows = u64::try_from(height).unwrap_or(u64::MAX);
+        let aligned = pos.0 % 8 == 0;
+        self.scheduler.consume_sprite(rows, aligned, collision);
+        Ok(())
     }
 
     /// Stores data in RAM.
     ///
     /// # Errors
     ///
-    /// If the data is bigger than the available space it returns Error.
+    /// If the data is bigger than the available space it returns
+    /// [`Chip8Error::RomTooLarge`].
     pub fn store_in_ram(&mut self, rom: impl AsRef<[u8]>) -> Result<()> {
         let rom = &rom.as_ref();
-        if rom.len() + PROGRAM_START > RAM_SIZE {
-            bail!("data is too big to fit into the ram");
+        if rom.len() + PROGRAM_START > self.ram.len() {
+            return Err(Chip8Error::RomTooLarge {
+                size: rom.len(),
+                capacity: self.ram.len() - PROGRAM_START,
+            });
         }
         self.ram[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(rom);
         Ok(())
     }
 
+    /// Sets the source of the random numbers used by `CXNN`.
+    ///
+    /// Use a [`SeededRandom`] with a different seed, or
+    /// [`SeededRandom::from_entropy`] for a different sequence on every run.
+    pub fn set_random(&mut self, random: impl Random + 'static) {
+        self.random = Box::new(random);
+    }
+
+    /// Sets how `0NNN` (call machine code routine) instructions are handled.
+    pub const fn set_machine_code_call(&mut self, machine_code_call: MachineCodeCall) {
+        self.machine_code_call = machine_code_call;
+    }
+
+    /// Returns the displayed pixels.
+    #[must_use]
+    pub const fn framebuffer(&self) -> &Framebuffer {
+        self.display.framebuffer()
+    }
+
+    /// Returns the current display resolution as `(width, height)`.
+    #[must_use]
+    pub const fn resolution(&self) -> (usize, usize) {
+        (self.display.width(), self.display.height())
+    }
+
+    /// Returns true if the program exited with the SUPER-CHIP `00FD` instruction.
+    #[must_use]
+    pub const fn has_exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Returns the SUPER-CHIP RPL user flags.
+    ///
+    /// Games use them to keep e.g. high scores, so clients may persist them
+    /// between runs and restore them with [`Chip8::set_rpl_flags`].
+    #[must_use]
+    pub const fn rpl_flags(&self) -> [u8; 16] {
+        self.rpl_flags
+    }
+
+    /// Restores the SUPER-CHIP RPL user flags.
+    pub const fn set_rpl_flags(&mut self, flags: [u8; 16]) {
+        self.rpl_flags = flags;
+    }
+
+    /// Returns the emulated platform.
+    #[must_use]
+    pub const fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    /// Returns the emulated interpreter quirks.
+    #[must_use]
+    pub const fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Returns the program counter.
+    #[must_use]
+    pub const fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Sets the program counter.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::InvalidAddress`] if the address is outside of the RAM.
+    pub fn set_pc(&mut self, pc: usize) -> Result<()> {
+        self.pc = self.check_address(pc)?;
+        Ok(())
+    }
+
+    /// Returns the index register.
+    #[must_use]
+    pub const fn index(&self) -> usize {
+        self.i
+    }
+
+    /// Sets the index register.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::InvalidAddress`] if the address is outside of the RAM.
+    pub fn set_index(&mut self, i: usize) -> Result<()> {
+        self.i = self.check_address(i)?;
+        Ok(())
+    }
+
+    /// Returns the V0 to VF registers.
+    #[must_use]
+    pub const fn registers(&self) -> &[u8; 16] {
+        &self.registers
+    }
+
+    /// Returns the V0 to VF registers for modification.
+    pub const fn registers_mut(&mut self) -> &mut [u8; 16] {
+        &mut self.registers
+    }
+
+    /// Returns the addresses of the pending subroutine calls, the innermost
+    /// last. `00EE` returns to the instruction after the call.
+    #[must_use]
+    pub fn stack(&self) -> &[usize] {
+        &self.stack
+    }
+
+    /// Replaces the addresses of the pending subroutine calls.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::StackTooDeep`] if there are more than [`STACK_SIZE`]
+    /// addresses, or [`Chip8Error::InvalidAddress`] if any of them is outside
+    /// of the RAM.
+    pub fn set_stack(&mut self, stack: &[usize]) -> Result<()> {
+        if stack.len() > STACK_SIZE {
+            return Err(Chip8Error::StackTooDeep { depth: stack.len() });
+        }
+        for address in stack {
+            self.check_address(*address)?;
+        }
+        self.stack = stack.to_vec();
+        Ok(())
+    }
+
+    /// Returns the whole RAM.
+    #[must_use]
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    /// Returns the whole RAM for modification.
+    pub fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    /// Returns `len` bytes of RAM starting at `address`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::InvalidAddress`] if the range is not entirely in the RAM.
+    pub fn read_memory(&self, address: usize, len: usize) -> Result<&[u8]> {
+        let end = self.check_range(address, len)?;
+        Ok(&self.ram[address..end])
+    }
+
+    /// Copies `data` into the RAM starting at `address`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::InvalidAddress`] if the range is not entirely in the RAM.
+    pub fn write_memory(&mut self, address: usize, data: &[u8]) -> Result<()> {
+        let end = self.check_range(address, data.len())?;
+        self.ram[address..end].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Returns the delay timer.
+    #[must_use]
+    pub const fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    /// Sets the delay timer.
+    pub const fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    /// Returns the sound timer.
+    #[must_use]
+    pub const fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// Sets the sound timer. The beep starts or stops with the next instruction.
+    pub const fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    /// Sets the colour of a pixel of the display, see [`Framebuffer`].
+    ///
+    /// The change is only reported through [`Graphics::present`], not the
+    /// per-pixel callbacks.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::InvalidPixel`] if the coordinates are out of the
+    /// display or the colour is not in the range `0..4`.
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: u8) -> Result<()> {
+        let (width, height) = self.resolution();
+        if x >= width || y >= height || color >= 4 {
+            return Err(Chip8Error::InvalidPixel { x, y, color });
+        }
+        self.display.set_pixel_silently(x, y, color);
+        Ok(())
+    }
+
+    /// Returns the address if it is inside of the RAM.
+    const fn check_address(&self, address: usize) -> Result<usize> {
+        if address < self.ram.len() {
+            Ok(address)
+        } else {
+            Err(Chip8Error::InvalidAddress { address })
+        }
+    }
+
+    /// Returns the end of the `len` bytes starting at `address`, if they are inside of the RAM.
+    const fn check_range(&self, address: usize, len: usize) -> Result<usize> {
+        match address.checked_add(len) {
+            Some(end) if end <= self.ram.len() => Ok(end),
+            _ => Err(Chip8Error::InvalidAddress {
+                address: address.saturating_add(len),
+            }),
+        }
+    }
+
     /// Handles released key.
     ///
     /// The real key press/release logic is supposed to be handled by the client.
-    pub fn handle_key_released(&mut self) {
-        self.key_pressed = None;
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key is supposed to be a value in the range `0..16`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::InvalidKey`] if the key is out of range.
+    pub fn handle_key_released(&mut self, key: u8) -> Result<()> {
+        let mask = key_mask(key)?;
+        self.keys &= !mask;
+        if self.waiting_key == Some(key) {
+            self.complete_key_wait(key);
+        }
+        Ok(())
     }
 
     /// Handles pressed key.
     ///
     /// The real key press/release logic is supposed to be handled by the client.
+    /// Several keys can be held down at the same time. While `FX0A` waits for
+    /// input, the first key pressed is the one stored.
     ///
     /// # Arguments
     ///
     /// * `key` - The key is supposed to be a value in the range `0..16`.
-    ///     Chip8's original keypad has 16 buttons.
-    pub fn handle_key_pressed(&mut self, key: u8) {
-        self.key_pressed = Some(key);
+    ///   Chip8's original keypad has 16 buttons.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::InvalidKey`] if the key is out of range.
+    pub fn handle_key_pressed(&mut self, key: u8) -> Result<()> {
+        let mask = key_mask(key)?;
+        self.keys |= mask;
+        if self.waiting_for_input.is_some() {
+            if !self.quirks.key_wait_release {
+                self.complete_key_wait(key);
+            } else if self.waiting_key.is_none() {
+                self.waiting_key = Some(key);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true while `FX0A` is waiting for a key.
+    ///
+    /// No instruction runs until a key is pressed, or pressed and released
+    /// with [`Quirks::key_wait_release`], but the timers keep running.
+    #[must_use]
+    pub const fn is_waiting_for_key(&self) -> bool {
+        self.waiting_for_input.is_some()
+    }
+
+    /// Stores the key awaited by `FX0A` and resumes the execution.
+    fn complete_key_wait(&mut self, key: u8) {
         if let Some(x) = self.waiting_for_input {
-            self.registers[x] = key;
+            self.set_register(x, key);
             self.waiting_for_input = None;
+            self.waiting_key = None;
         }
     }
-}
 
-/// Checks if the coordinates are valid.
-fn check_coordinates(x: usize, y: usize) -> Result<()> {
-    if x >= TERMINAL_WIDTH {
-        bail!("invalid X coordinate to draw: {}", x);
+    /// Returns the keys held down, with bit N set while key N is.
+    #[must_use]
+    pub const fn keypad(&self) -> u16 {
+        self.keys
+    }
+
+    /// Returns true if the key is held down. Keys out of range are never pressed.
+    #[must_use]
+    pub const fn is_key_pressed(&self, key: u8) -> bool {
+        match key_mask(key) {
+            Ok(mask) => self.keys & mask != 0,
+            Err(_) => false,
+        }
     }
-    if y >= TERMINAL_HEIGHT {
-        bail!("invalid Y coordinate to draw: {}", y);
+}
+
+/// Returns the bit of `key` in the keypad state.
+const fn key_mask(key: u8) -> Result<u16> {
+    if key < 16 {
+        Ok(1 << key)
+    } else {
+        Err(Chip8Error::InvalidKey { key })
     }
-    Ok(())
+}
+
+/// Returns the registers `X..=Y`, in descending order if `X > Y`.
+fn register_range(x: usize, y: usize) -> impl Iterator<Item = usize> {
+    (0..=x.abs_diff(y)).map(move |i| if x <= y { x + i } else { x - i })
 }
 
 /// Graphics abstraction for Chip8.
 ///
 /// Clients are supposed to implement this trait in accordance with
-/// the graphics library used.
+/// the graphics library used: either the per-pixel callbacks, invoked as
+/// the pixels change, or [`Graphics::present`], invoked once per frame.
 pub trait Graphics {
     /// Clears/turns off a pixel on a specific coordinate.
-    fn clear_pixel(&mut self, x: usize, y: usize);
+    fn clear_pixel(&mut self, _x: usize, _y: usize) {}
 
     /// Draws/turns on a pixel on a specific coordinate.
-    fn draw_pixel(&mut self, x: usize, y: usize);
+    fn draw_pixel(&mut self, _x: usize, _y: usize) {}
+
+    /// Draws a pixel with a colour on a specific coordinate.
+    ///
+    /// The colour is in the range `0..4`, combining the two XO-CHIP bitplanes:
+    /// bit 0 is set if the pixel is on in the first plane and bit 1 if it is
+    /// on in the second one. `0` is the background colour. The default
+    /// implementation draws any colour other than the background as a pixel
+    /// turned on.
+    fn draw_color(&mut self, x: usize, y: usize, color: u8) {
+        if color == 0 {
+            self.clear_pixel(x, y);
+        } else {
+            self.draw_pixel(x, y);
+        }
+    }
+
+    /// Changes the display resolution.
+    ///
+    /// It is called after all the pixels of the previous resolution are cleared.
+    fn resize(&mut self, _width: usize, _height: usize) {}
+
+    /// Presents the whole display, once at the end of every frame.
+    ///
+    /// Frontends drawing a frame at a time can implement only this method
+    /// and upload the [`Framebuffer::dirty_rect`] region, instead of
+    /// following the per-pixel callbacks.
+    fn present(&mut self, _framebuffer: &Framebuffer) {}
 }
 
 /// Audio abstraction for Chip8.
@@ -474,4 +1274,11 @@ pub trait Audio {
 
     /// Stops the beep sound.
     fn stop_beep(&mut self);
+
+    /// Sets the XO-CHIP audio pattern played while beeping.
+    ///
+    /// The 128 bits of the pattern are played in a loop, most significant bit
+    /// of the first byte first, at `4000 * 2 ^ ((pitch - 64) / 48)` bits per
+    /// second. The default implementation ignores the pattern.
+    fn set_pattern(&mut self, _pattern: [u8; 16], _pitch: u8) {}
 }
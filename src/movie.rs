@@ -0,0 +1,379 @@
+use std::any::Any;
+
+use crate::{
+    state::{fnv1a, Reader, Writer},
+    Chip8, Chip8Error, Platform, Quirks, Result, SeededRandom, Speed,
+};
+
+/// Identifies the movie file format.
+const MAGIC: &[u8; 4] = b"CH8M";
+/// Version of the movie file format written by this crate.
+const VERSION: u8 = 1;
+/// Number of frames between two state hashes.
+const CHECKPOINT_INTERVAL: usize = 60;
+
+/// Recording of the keypad input of a program run, frame by frame.
+///
+/// It also holds everything else the run depends on: a hash of the ROM, the
+/// platform, the quirks, the speed and the random seed, so that playing it
+/// back reproduces the run exactly. The state of the machine is hashed
+/// every second, to detect where a playback diverges from the recording.
+///
+/// The key events are recorded in order with the frame they happen before,
+/// so that a key pressed and released between two frames still completes
+/// an `FX0A` waiting for it. They must go through
+/// [`Movie::handle_key_pressed`] and [`Movie::handle_key_released`] for
+/// that: [`Movie::record`] only sees the keys held down at the end of the
+/// frame.
+///
+/// The random numbers are replayed by seeding a [`SeededRandom`], so the
+/// generator of the machine must not be replaced, e.g. by a
+/// [`CosmacVipRandom`](crate::CosmacVipRandom):
+/// [`Movie::record`] and [`MoviePlayer::play`] fail if it is.
+///
+/// ```no_run
+/// # use rusty_chip8::{Graphics, Audio, Movie, Platform, Quirks, Speed};
+/// # fn run(rom: &[u8], graphics: &mut impl Graphics, audio: &mut impl Audio) -> rusty_chip8::Result<()> {
+/// let mut movie = Movie::new(rom, Platform::Chip8, Quirks::default(), Speed::default(), 42);
+/// let mut chip8 = movie.start(rom)?;
+/// for _ in 0..600 {
+///     // For each key event of the frame:
+///     movie.handle_key_pressed(&mut chip8, 5)?;
+///     movie.handle_key_released(&mut chip8, 5)?;
+///     // Then:
+///     movie.record(&chip8)?;
+///     chip8.run_frame(graphics, audio)?;
+/// }
+///
+/// let mut chip8 = movie.start(rom)?;
+/// let mut player = movie.player();
+/// while player.play(&mut chip8)? {
+///     chip8.run_frame(graphics, audio)?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    rom_hash: u64,
+    platform: Platform,
+    quirks: Quirks,
+    speed: Speed,
+    random_seed: u64,
+    /// Number of recorded frames.
+    frames: usize,
+    /// Key events, with the frame they happen before.
+    events: Vec<(usize, KeyEvent)>,
+    /// Keys held down after the recorded events.
+    keypad: u16,
+    /// State hashes taken before every [`CHECKPOINT_INTERVAL`]th frame.
+    checkpoints: Vec<u64>,
+}
+
+/// Press or release of a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct KeyEvent {
+    key: u8,
+    pressed: bool,
+}
+
+impl KeyEvent {
+    /// Presses or releases the key of `chip8`.
+    fn apply(self, chip8: &mut Chip8) -> Result<()> {
+        if self.pressed {
+            chip8.handle_key_pressed(self.key)
+        } else {
+            chip8.handle_key_released(self.key)
+        }
+    }
+
+    /// Returns the keypad state after the event.
+    const fn keypad(self, keypad: u16) -> u16 {
+        let mask = 1 << self.key;
+        if self.pressed {
+            keypad | mask
+        } else {
+            keypad & !mask
+        }
+    }
+
+    /// Returns the events turning the keypad state `from` into `to`.
+    fn between(from: u16, to: u16) -> impl Iterator<Item = Self> {
+        let changed = from ^ to;
+        (0..16)
+            .filter(move |key| changed & (1 << key) != 0)
+            .map(move |key| Self {
+                key,
+                pressed: to & (1 << key) != 0,
+            })
+    }
+}
+
+impl Movie {
+    /// Returns an empty movie for running `rom` with the given settings.
+    #[must_use]
+    pub fn new(
+        rom: &[u8],
+        platform: Platform,
+        quirks: Quirks,
+        speed: Speed,
+        random_seed: u64,
+    ) -> Self {
+        Self {
+            rom_hash: fnv1a(rom),
+            platform,
+            quirks,
+            speed,
+            random_seed,
+            frames: 0,
+            events: Vec::new(),
+            keypad: 0,
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Returns a machine set up as the movie was recorded, with `rom` loaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::MovieRomMismatch`] if the ROM is not the one of the
+    /// movie, or an error of [`Chip8::store_in_ram`].
+    pub fn start(&self, rom: &[u8]) -> Result<Chip8> {
+        if fnv1a(rom) != self.rom_hash {
+            return Err(Chip8Error::MovieRomMismatch);
+        }
+        let mut chip8 = Chip8::with_speed(self.speed, self.platform, self.quirks);
+        chip8.set_random(SeededRandom::new(self.random_seed));
+        chip8.store_in_ram(rom)?;
+        Ok(chip8)
+    }
+
+    /// Presses `key` on `chip8` and records it before the next frame.
+    ///
+    /// # Errors
+    ///
+    /// See [`Chip8::handle_key_pressed`].
+    pub fn handle_key_pressed(&mut self, chip8: &mut Chip8, key: u8) -> Result<()> {
+        self.handle_key(chip8, KeyEvent { key, pressed: true })
+    }
+
+    /// Releases `key` on `chip8` and records it before the next frame.
+    ///
+    /// # Errors
+    ///
+    /// See [`Chip8::handle_key_released`].
+    pub fn handle_key_released(&mut self, chip8: &mut Chip8, key: u8) -> Result<()> {
+        self.handle_key(
+            chip8,
+            KeyEvent {
+                key,
+                pressed: false,
+            },
+        )
+    }
+
+    fn handle_key(&mut self, chip8: &mut Chip8, event: KeyEvent) -> Result<()> {
+        event.apply(chip8)?;
+        self.events.push((self.frames, event));
+        self.keypad = event.keypad(self.keypad);
+        Ok(())
+    }
+
+    /// Ends the recording of the next frame.
+    ///
+    /// It is supposed to be called right before every
+    /// [`Chip8::run_frame`] of a machine returned by [`Movie::start`], after
+    /// the key events of the frame were handled. Keys pressed or released
+    /// directly on `chip8` are recorded as well, but only by their state at
+    /// this point: a press and release in between is lost.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::MovieRandomMismatch`] if the random number
+    /// generator of `chip8` is not a [`SeededRandom`].
+    pub fn record(&mut self, chip8: &Chip8) -> Result<()> {
+        check_random(chip8)?;
+        let keypad = chip8.keypad();
+        let frame = self.frames;
+        self.events
+            .extend(KeyEvent::between(self.keypad, keypad).map(|event| (frame, event)));
+        self.keypad = keypad;
+        if frame.is_multiple_of(CHECKPOINT_INTERVAL) {
+            self.checkpoints.push(chip8.state_hash());
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Returns the number of recorded frames.
+    #[must_use]
+    pub const fn frame_count(&self) -> usize {
+        self.frames
+    }
+
+    /// Returns a player feeding the recorded input back.
+    #[must_use]
+    pub const fn player(&self) -> MoviePlayer<'_> {
+        MoviePlayer {
+            movie: self,
+            frame: 0,
+            event: 0,
+        }
+    }
+
+    /// Returns the movie in a compact versioned binary format.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.bytes(MAGIC);
+        w.u8(VERSION);
+        w.u64(self.rom_hash);
+        w.platform(self.platform);
+        w.quirks(self.quirks);
+        w.speed(self.speed);
+        w.u64(self.random_seed);
+        w.len(self.frames);
+        w.len(self.events.len());
+        for (frame, event) in &self.events {
+            w.len(*frame);
+            w.u8(event.key | if event.pressed { 0x10 } else { 0 });
+        }
+        w.len(self.checkpoints.len());
+        for hash in &self.checkpoints {
+            w.u64(*hash);
+        }
+        w.into_bytes()
+    }
+
+    /// Reads a movie returned by [`Movie::to_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::InvalidMovie`] if the data is not a valid movie,
+    /// or was written by a newer version of the crate.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut r = Reader::new(data);
+        Self::read(&mut r)
+            .ok()
+            .filter(|_| r.is_empty())
+            .ok_or(Chip8Error::InvalidMovie)
+    }
+
+    fn read(r: &mut Reader) -> Result<Self> {
+        if r.bytes(MAGIC.len())? != MAGIC {
+            return Err(Chip8Error::InvalidMovie);
+        }
+        let version = r.u8()?;
+        if version != VERSION {
+            return Err(Chip8Error::InvalidMovie);
+        }
+        let rom_hash = r.u64()?;
+        let platform = r.platform()?;
+        let quirks = r.quirks()?;
+        let speed = r.speed()?;
+        let random_seed = r.u64()?;
+        let frames = r.len()?;
+        let events = Self::read_events(r, frames)?;
+        let keypad = events
+            .iter()
+            .fold(0, |keypad, (_, event)| event.keypad(keypad));
+        let checkpoints = r.len()?;
+        let checkpoints = (0..checkpoints).map(|_| r.u64()).collect::<Result<_>>()?;
+        Ok(Self {
+            rom_hash,
+            platform,
+            quirks,
+            speed,
+            random_seed,
+            frames,
+            events,
+            keypad,
+            checkpoints,
+        })
+    }
+
+    /// Reads the key events, in frame order.
+    fn read_events(r: &mut Reader, frames: usize) -> Result<Vec<(usize, KeyEvent)>> {
+        let count = r.len()?;
+        let mut events = Vec::new();
+        for _ in 0..count {
+            let frame = r.len()?;
+            let event = r.u8()?;
+            let previous = events.last().map_or(0, |(frame, _)| *frame);
+            if frame < previous || frame >= frames || event > 0x1F {
+                return Err(Chip8Error::InvalidMovie);
+            }
+            let event = KeyEvent {
+                key: event & 0xF,
+                pressed: event & 0x10 != 0,
+            };
+            events.push((frame, event));
+        }
+        Ok(events)
+    }
+}
+
+/// Plays the input of a [`Movie`] back, checking that the run is the same.
+#[derive(Debug)]
+pub struct MoviePlayer<'a> {
+    movie: &'a Movie,
+    frame: usize,
+    /// Index of the next key event to play.
+    event: usize,
+}
+
+impl MoviePlayer<'_> {
+    /// Presses and releases the keys of the next frame.
+    ///
+    /// It is supposed to be called right before every
+    /// [`Chip8::run_frame`] of a machine returned by [`Movie::start`]. Returns
+    /// false once all the frames were played.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::MovieDesync`] with the first frame whose state
+    /// differs from the recording. States are compared every second, so the
+    /// actual divergence happened at most a second earlier. Returns
+    /// [`Chip8Error::MovieRandomMismatch`] if the random number generator of
+    /// `chip8` is not a [`SeededRandom`].
+    pub fn play(&mut self, chip8: &mut Chip8) -> Result<bool> {
+        if self.frame >= self.movie.frames {
+            return Ok(false);
+        }
+        check_random(chip8)?;
+        while let Some((_, event)) = self
+            .movie
+            .events
+            .get(self.event)
+            .filter(|(frame, _)| *frame == self.frame)
+        {
+            event.apply(chip8)?;
+            self.event += 1;
+        }
+        if self.frame.is_multiple_of(CHECKPOINT_INTERVAL) {
+            let checkpoint = self.frame / CHECKPOINT_INTERVAL;
+            if self.movie.checkpoints.get(checkpoint) != Some(&chip8.state_hash()) {
+                return Err(Chip8Error::MovieDesync { frame: self.frame });
+            }
+        }
+        self.frame += 1;
+        Ok(true)
+    }
+
+    /// Returns the number of frames played.
+    #[must_use]
+    pub const fn frame(&self) -> usize {
+        self.frame
+    }
+}
+
+/// Returns an error unless `chip8` draws its random numbers from a
+/// [`SeededRandom`], the only generator a movie can replay.
+fn check_random(chip8: &Chip8) -> Result<()> {
+    let random: &dyn Any = &*chip8.random;
+    if random.is::<SeededRandom>() {
+        Ok(())
+    } else {
+        Err(Chip8Error::MovieRandomMismatch)
+    }
+}
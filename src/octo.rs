@@ -0,0 +1,1171 @@
+//! Compiler of [Octo](https://github.com/JohnEarnest/Octo) programs.
+//!
+//! Octo is the structured assembly language most of the modern Chip8
+//! programs are written in. [`compile`] turns a `.8o` source into a ROM to be
+//! loaded with [`Chip8::store_in_ram`](crate::Chip8::store_in_ram), and a
+//! [`SourceMap`] relating the addresses of the ROM to the source lines:
+//!
+//! ```
+//! use rusty_chip8::{octo, Platform};
+//!
+//! let source = "
+//! : main
+//!     v0 := 0
+//!     loop
+//!         v0 += 1
+//!         if v0 == 10 then jump done
+//!     again
+//! : done
+//!     jump done
+//! ";
+//! let program = octo::compile(source, Platform::Chip8)?;
+//! assert_eq!(program.rom()[..2], [0x60, 0x00]);
+//! assert_eq!(program.source_map().line(0x202), Some(5));
+//! assert_eq!(program.source_map().label("done"), Some(0x20A));
+//! # Ok::<(), rusty_chip8::asm::AsmError>(())
+//! ```
+//!
+//! The language is supported but for the `:assert` and `:stringmode`
+//! directives. `:calc` expressions are evaluated with integers, right to
+//! left and without precedence like in Octo. The comparisons `<`, `>`, `<=`
+//! and `>=` of `if` and `while` overwrite `vf`.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::{
+    asm::{register, AsmError, AsmErrorKind},
+    Instruction, Platform, PROGRAM_START,
+};
+
+/// Words with a meaning of their own, which can not be used as names.
+const KEYWORDS: [&str; 60] = [
+    ":=",
+    "|=",
+    "&=",
+    "^=",
+    "+=",
+    "-=",
+    "=-",
+    ">>=",
+    "<<=",
+    "==",
+    "!=",
+    "<",
+    ">",
+    "<=",
+    ">=",
+    "key",
+    "-key",
+    "hex",
+    "bighex",
+    "long",
+    "random",
+    "delay",
+    "buzzer",
+    "pitch",
+    "return",
+    "clear",
+    "bcd",
+    "save",
+    "load",
+    "saveflags",
+    "loadflags",
+    "sprite",
+    "jump",
+    "jump0",
+    "native",
+    "i",
+    "if",
+    "then",
+    "begin",
+    "else",
+    "end",
+    "loop",
+    "again",
+    "while",
+    "hires",
+    "lores",
+    "exit",
+    "scroll-down",
+    "scroll-up",
+    "scroll-left",
+    "scroll-right",
+    "plane",
+    "audio",
+    ";",
+    "-",
+    "{",
+    "}",
+    "(",
+    ")",
+    "HERE",
+];
+/// Maximum number of macro expansions, to stop recursive macros.
+const MAX_EXPANSIONS: usize = 0x10000;
+
+/// Compiles the Octo `source` for the platform.
+///
+/// The program starts at the `main` label: a jump to it is inserted at the
+/// start of the ROM, unless `main` is the first thing of the source.
+///
+/// # Errors
+///
+/// Returns an [`AsmError`] locating the first error found in the source.
+pub fn compile(source: &str, platform: Platform) -> Result<Program, AsmError> {
+    let mut compiler = Compiler::new(platform, tokenize(source));
+    while let Some(token) = compiler.tokens.pop_front() {
+        compiler.last = token.clone();
+        compiler.statement(token)?;
+    }
+    compiler.finish()
+}
+
+/// Compiled Octo program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    rom: Vec<u8>,
+    source_map: SourceMap,
+}
+
+impl Program {
+    /// Returns the ROM, loaded at address `0x200`.
+    #[must_use]
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    /// Returns the ROM, dropping the source map.
+    #[must_use]
+    pub fn into_rom(self) -> Vec<u8> {
+        self.rom
+    }
+
+    /// Returns where the code and data of the ROM come from.
+    #[must_use]
+    pub const fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+}
+
+/// Relation between the addresses of a compiled program and its source lines.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    /// Source line and size of the bytes emitted at each address.
+    lines: BTreeMap<usize, (usize, usize)>,
+    labels: BTreeMap<String, usize>,
+    breakpoints: BTreeMap<usize, String>,
+}
+
+impl SourceMap {
+    /// Returns the source line, starting at 1, of the instruction or data at `address`.
+    #[must_use]
+    pub fn line(&self, address: usize) -> Option<usize> {
+        let (start, (line, size)) = self.lines.range(..=address).next_back()?;
+        (address < start + size).then_some(*line)
+    }
+
+    /// Returns the first address of the code or data of the source `line`.
+    #[must_use]
+    pub fn address(&self, line: usize) -> Option<usize> {
+        self.lines
+            .iter()
+            .find(|(_, (source_line, _))| *source_line == line)
+            .map(|(address, _)| *address)
+    }
+
+    /// Returns the address of the label.
+    #[must_use]
+    pub fn label(&self, name: &str) -> Option<usize> {
+        self.labels.get(name).copied()
+    }
+
+    /// Returns the labels and their addresses, sorted by name.
+    pub fn labels(&self) -> impl Iterator<Item = (&str, usize)> + '_ {
+        self.labels
+            .iter()
+            .map(|(name, address)| (name.as_str(), *address))
+    }
+
+    /// Returns the addresses and names of the `:breakpoint` directives.
+    pub fn breakpoints(&self) -> impl Iterator<Item = (usize, &str)> + '_ {
+        self.breakpoints
+            .iter()
+            .map(|(address, name)| (*address, name.as_str()))
+    }
+}
+
+/// Word of the source, with its position.
+#[derive(Debug, Clone, Default)]
+struct Token {
+    text: String,
+    line: usize,
+    column: usize,
+}
+
+impl Token {
+    const fn error(&self, kind: AsmErrorKind) -> AsmError {
+        AsmError::new(self.line, self.column, kind)
+    }
+
+    fn unbalanced(&self) -> AsmError {
+        self.error(AsmErrorKind::UnbalancedBlock {
+            keyword: self.text.clone(),
+        })
+    }
+
+    fn invalid_operands(&self) -> AsmError {
+        self.error(AsmErrorKind::InvalidOperands {
+            mnemonic: self.text.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Token>,
+}
+
+/// Reference to a label defined further down, patched at the end.
+#[derive(Debug)]
+struct Fixup {
+    /// Offset of the patched bytes in the ROM.
+    offset: usize,
+    kind: FixupKind,
+    name: Token,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum FixupKind {
+    /// The 12 low bits of an opcode.
+    Address,
+    /// A 16-bit big-endian word.
+    Long,
+    /// The 4 low bits of a byte, set to the high bits of a 12-bit address.
+    HighNibble,
+    /// A byte, set to the high byte of a 16-bit address.
+    HighByte,
+    /// A byte, set to the low byte of an address.
+    LowByte,
+}
+
+/// Open `if ... begin`, `else` or `loop` block.
+#[derive(Debug)]
+enum Block {
+    /// Holds the offset of the jump over the block.
+    If { jump: usize, token: Token },
+    /// Holds the offset of the jump over the block.
+    Else { jump: usize, token: Token },
+    /// Holds the start address, and the offsets of the jumps out of the loop.
+    Loop {
+        start: usize,
+        whiles: Vec<usize>,
+        token: Token,
+    },
+}
+
+/// Condition of `if` and `while`.
+#[derive(Debug, Clone, Copy)]
+enum Condition {
+    Equal(usize, Rhs),
+    NotEqual(usize, Rhs),
+    Key(usize),
+    NotKey(usize),
+}
+
+impl Condition {
+    const fn not(self) -> Self {
+        match self {
+            Self::Equal(x, rhs) => Self::NotEqual(x, rhs),
+            Self::NotEqual(x, rhs) => Self::Equal(x, rhs),
+            Self::Key(x) => Self::NotKey(x),
+            Self::NotKey(x) => Self::Key(x),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rhs {
+    Register(usize),
+    Byte(u8),
+}
+
+#[derive(Debug)]
+struct Compiler {
+    platform: Platform,
+    tokens: VecDeque<Token>,
+    /// The last token taken, to locate an unexpected end of the source.
+    last: Token,
+    rom: Vec<u8>,
+    /// Address of the next emitted byte.
+    here: usize,
+    /// True once the jump to `main` is emitted, or known to be unneeded.
+    started: bool,
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, i64>,
+    aliases: HashMap<String, usize>,
+    macros: HashMap<String, Macro>,
+    expansions: usize,
+    fixups: Vec<Fixup>,
+    blocks: Vec<Block>,
+    source_map: SourceMap,
+}
+
+impl Compiler {
+    fn new(platform: Platform, tokens: VecDeque<Token>) -> Self {
+        Self {
+            platform,
+            tokens,
+            last: Token::default(),
+            rom: Vec::new(),
+            here: PROGRAM_START,
+            started: false,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            expansions: 0,
+            fixups: Vec::new(),
+            blocks: Vec::new(),
+            source_map: SourceMap::default(),
+        }
+    }
+
+    fn next(&mut self) -> Result<Token, AsmError> {
+        let token = self
+            .tokens
+            .pop_front()
+            .ok_or_else(|| self.last.error(AsmErrorKind::UnexpectedEnd))?;
+        self.last = token.clone();
+        Ok(token)
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.front().map(|token| token.text.as_str())
+    }
+
+    /// Takes the next token, which must be `text`.
+    fn expect(&mut self, text: &str, statement: &Token) -> Result<(), AsmError> {
+        let token = self.next()?;
+        if token.text == text {
+            Ok(())
+        } else {
+            Err(token.error(AsmErrorKind::InvalidOperands {
+                mnemonic: statement.text.clone(),
+            }))
+        }
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn statement(&mut self, token: Token) -> Result<(), AsmError> {
+        if let Some(value) = number(&token.text) {
+            return self.byte(&token, value);
+        }
+        match token.text.as_str() {
+            ":" => {
+                let name = self.next()?;
+                if name.text == "main" && !self.started {
+                    self.started = true;
+                } else {
+                    self.start(&name)?;
+                }
+                self.define_label(&name, self.here)
+            }
+            ":next" => {
+                let name = self.next()?;
+                self.start(&name)?;
+                self.define_label(&name, self.here + 1)
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.next()?;
+                let value = self.value(&value)?;
+                self.check_name(&name)?;
+                self.constants.insert(name.text, value);
+                Ok(())
+            }
+            ":calc" => {
+                let name = self.next()?;
+                let value = self.calc(&token)?;
+                self.check_name(&name)?;
+                self.constants.insert(name.text, value);
+                Ok(())
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let register = self.next()?;
+                let register = self.register(&register)?;
+                if !is_name(&name.text) {
+                    return Err(name.error(AsmErrorKind::InvalidSymbol {
+                        name: name.text.clone(),
+                    }));
+                }
+                if !self.aliases.contains_key(&name.text) {
+                    self.check_name(&name)?;
+                }
+                self.aliases.insert(name.text, register);
+                Ok(())
+            }
+            ":byte" => {
+                let value = if self.peek() == Some("{") {
+                    self.calc(&token)?
+                } else {
+                    let value = self.next()?;
+                    self.value(&value)?
+                };
+                self.byte(&token, value)
+            }
+            ":macro" => self.macro_definition(),
+            ":unpack" => {
+                let prefix = self.next()?;
+                let target = self.next()?;
+                let (high, kind) = if prefix.text == "long" {
+                    (0, FixupKind::HighByte)
+                } else {
+                    (self.nibble(&prefix)? << 4, FixupKind::HighNibble)
+                };
+                let offset = self.instruction(&token, Instruction::SetVRegister6XNN(0, high))?;
+                self.reference(offset + 1, kind, &target)?;
+                let offset = self.instruction(&token, Instruction::SetVRegister6XNN(1, 0))?;
+                self.reference(offset + 1, FixupKind::LowByte, &target)
+            }
+            ":org" => {
+                let address = self.next()?;
+                let value = self.value(&address)?;
+                let max = i64::try_from(self.platform.ram_size()).unwrap_or(i64::MAX);
+                let min = i64::try_from(PROGRAM_START).unwrap_or_default();
+                self.here = usize::try_from(value)
+                    .ok()
+                    .filter(|_| (min..=max).contains(&value))
+                    .ok_or_else(|| address.error(AsmErrorKind::OutOfRange { value, min, max }))?;
+                self.started = true;
+                Ok(())
+            }
+            ":pointer" => {
+                let target = self.next()?;
+                let offset = self.emit(&token, &[0, 0])?;
+                self.reference(offset, FixupKind::Long, &target)
+            }
+            ":call" => {
+                let target = self.next()?;
+                self.call(&token, &target)
+            }
+            ":breakpoint" => {
+                let name = self.next()?;
+                self.source_map.breakpoints.insert(self.here, name.text);
+                Ok(())
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+                Ok(())
+            }
+            ":proto" => {
+                self.next()?;
+                Ok(())
+            }
+            ";" | "return" => self.emit_instruction(&token, Instruction::SubroutineReturn00EE),
+            "clear" => self.emit_instruction(&token, Instruction::Cls00E0),
+            "hires" => self.emit_instruction(&token, Instruction::HighResolution00FF),
+            "lores" => self.emit_instruction(&token, Instruction::LowResolution00FE),
+            "exit" => self.emit_instruction(&token, Instruction::Exit00FD),
+            "scroll-left" => self.emit_instruction(&token, Instruction::ScrollLeft00FC),
+            "scroll-right" => self.emit_instruction(&token, Instruction::ScrollRight00FB),
+            "audio" => self.emit_instruction(&token, Instruction::LoadAudioPatternF002),
+            "scroll-down" | "scroll-up" | "plane" => {
+                let n = self.next()?;
+                let n = self.nibble(&n)?;
+                let inst = match token.text.as_str() {
+                    "scroll-down" => Instruction::ScrollDown00CN(n),
+                    "scroll-up" => Instruction::ScrollUp00DN(n),
+                    _ => Instruction::SelectPlanesFN01(n),
+                };
+                self.emit_instruction(&token, inst)
+            }
+            "bcd" | "saveflags" | "loadflags" => {
+                let x = self.next()?;
+                let x = self.register(&x)?;
+                let inst = match token.text.as_str() {
+                    "bcd" => Instruction::BinaryCodedDecimalConversionFX33(x),
+                    "saveflags" => Instruction::StoreFlagsFX75(x),
+                    _ => Instruction::LoadFlagsFX85(x),
+                };
+                self.emit_instruction(&token, inst)
+            }
+            "save" | "load" => {
+                let x = self.next()?;
+                let x = self.register(&x)?;
+                let save = token.text == "save";
+                let inst = if self.peek() == Some("-") {
+                    self.next()?;
+                    let y = self.next()?;
+                    let y = self.register(&y)?;
+                    if save {
+                        Instruction::StoreRangeToMemory5XY2(x, y)
+                    } else {
+                        Instruction::LoadRangeFromMemory5XY3(x, y)
+                    }
+                } else if save {
+                    Instruction::StoreRegistersToMemoryFX55(x)
+                } else {
+                    Instruction::LoadRegistersFromMemoryFX65(x)
+                };
+                self.emit_instruction(&token, inst)
+            }
+            "sprite" => {
+                let x = self.next()?;
+                let x = self.register(&x)?;
+                let y = self.next()?;
+                let y = self.register(&y)?;
+                let n = self.next()?;
+                let inst = match self.nibble(&n)? {
+                    0 if self.platform >= Platform::SuperChip => Instruction::BigSpriteDXY0(x, y),
+                    n => Instruction::Dxyn(x, y, n.into()),
+                };
+                self.emit_instruction(&token, inst)
+            }
+            "jump" | "jump0" | "native" => {
+                let target = self.next()?;
+                let inst = match token.text.as_str() {
+                    "jump" => Instruction::Jump1NNN(0),
+                    "jump0" => Instruction::JumpWithOffsetBNNN(0),
+                    _ => Instruction::MachineCodeCall0NNN(0),
+                };
+                let offset = self.instruction(&token, inst)?;
+                self.reference(offset, FixupKind::Address, &target)
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=", &token)?;
+                let x = self.next()?;
+                let x = self.register(&x)?;
+                let inst = match token.text.as_str() {
+                    "delay" => Instruction::SetDelayTimerFX15(x),
+                    "buzzer" => Instruction::SetSoundTimerFX18(x),
+                    _ => Instruction::SetPitchFX3A(x),
+                };
+                self.emit_instruction(&token, inst)
+            }
+            "i" => self.index_statement(&token),
+            "if" => self.if_statement(&token),
+            "else" => {
+                let Some(Block::If { jump, .. }) = self.blocks.pop() else {
+                    return Err(token.unbalanced());
+                };
+                let end = self.jump_placeholder(&token)?;
+                self.resolve_jump(jump, &token)?;
+                self.blocks.push(Block::Else { jump: end, token });
+                Ok(())
+            }
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump, .. } | Block::Else { jump, .. }) => {
+                    self.resolve_jump(jump, &token)
+                }
+                _ => Err(token.unbalanced()),
+            },
+            "loop" => {
+                self.blocks.push(Block::Loop {
+                    start: self.here,
+                    whiles: Vec::new(),
+                    token,
+                });
+                Ok(())
+            }
+            "while" => {
+                if !self
+                    .blocks
+                    .iter()
+                    .any(|block| matches!(block, Block::Loop { .. }))
+                {
+                    return Err(token.unbalanced());
+                }
+                let condition = self.condition(&token)?;
+                self.skip_if(&token, condition)?;
+                let jump = self.jump_placeholder(&token)?;
+                if let Some(Block::Loop { whiles, .. }) = self
+                    .blocks
+                    .iter_mut()
+                    .rev()
+                    .find(|block| matches!(block, Block::Loop { .. }))
+                {
+                    whiles.push(jump);
+                }
+                Ok(())
+            }
+            "again" => {
+                let Some(Block::Loop { start, whiles, .. }) = self.blocks.pop() else {
+                    return Err(token.unbalanced());
+                };
+                let jump = self.jump_placeholder(&token)?;
+                self.patch(jump, FixupKind::Address, start, &token)?;
+                for jump in whiles {
+                    self.resolve_jump(jump, &token)?;
+                }
+                Ok(())
+            }
+            name if self.macros.contains_key(name) => self.expand(&token),
+            name if self.aliases.contains_key(name) || register(name).is_some() => {
+                self.register_statement(&token)
+            }
+            name if is_name(name) => self.call(&token, &token),
+            _ => Err(token.error(AsmErrorKind::UnknownMnemonic {
+                mnemonic: token.text.clone(),
+            })),
+        }
+    }
+
+    /// Compiles the statements starting with a register, `vx := ...` and the like.
+    fn register_statement(&mut self, token: &Token) -> Result<(), AsmError> {
+        let x = self.register(token)?;
+        let op = self.next()?;
+        let rhs = self.next()?;
+        let y = self.register(&rhs).ok();
+        let inst = match (op.text.as_str(), y) {
+            (":=", Some(y)) => Instruction::RegisterSet8XY0(x, y),
+            (":=", None) => match rhs.text.as_str() {
+                "random" => {
+                    let mask = self.next()?;
+                    Instruction::RandomCXNN(x, self.byte_value(&mask)?)
+                }
+                "key" => Instruction::GetKeyFX0A(x),
+                "delay" => Instruction::ReadDelayTimerFX07(x),
+                _ => Instruction::SetVRegister6XNN(x, self.byte_value(&rhs)?),
+            },
+            ("+=", Some(y)) => Instruction::RegisterAdd8XY4(x, y),
+            ("+=", None) => Instruction::Add7XNN(x, self.byte_value(&rhs)?),
+            ("-=", Some(y)) => Instruction::RegisterSub8XY5(x, y),
+            ("-=", None) => Instruction::Add7XNN(x, self.byte_value(&rhs)?.wrapping_neg()),
+            ("=-", Some(y)) => Instruction::RegisterSubRev8XY7(x, y),
+            ("|=", Some(y)) => Instruction::Or8XY1(x, y),
+            ("&=", Some(y)) => Instruction::BinaryAnd8XY2(x, y),
+            ("^=", Some(y)) => Instruction::Xor8XY3(x, y),
+            (">>=", Some(y)) => Instruction::ShiftRight8XY6(x, y),
+            ("<<=", Some(y)) => Instruction::ShiftLeft8XYE(x, y),
+            _ => return Err(op.invalid_operands()),
+        };
+        self.emit_instruction(&op, inst)
+    }
+
+    /// Compiles `i := ...` and `i += vx`.
+    fn index_statement(&mut self, token: &Token) -> Result<(), AsmError> {
+        let op = self.next()?;
+        let rhs = self.next()?;
+        match (op.text.as_str(), rhs.text.as_str()) {
+            (":=", "hex" | "bighex") => {
+                let x = self.next()?;
+                let x = self.register(&x)?;
+                let inst = if rhs.text == "hex" {
+                    Instruction::FontCharacterFX29(x)
+                } else {
+                    Instruction::BigFontCharacterFX30(x)
+                };
+                self.emit_instruction(token, inst)
+            }
+            (":=", "long") => {
+                let target = self.next()?;
+                let offset = self.instruction(token, Instruction::LongIndexF000NNNN(0))?;
+                self.reference(offset + 2, FixupKind::Long, &target)
+            }
+            (":=", _) => {
+                let offset = self.instruction(token, Instruction::SetIndexRegisterANNN(0))?;
+                self.reference(offset, FixupKind::Address, &rhs)
+            }
+            ("+=", _) => {
+                let x = self.register(&rhs)?;
+                self.emit_instruction(token, Instruction::AddToIndexFX1E(x))
+            }
+            _ => Err(op.invalid_operands()),
+        }
+    }
+
+    /// Compiles `if ... then` and `if ... begin`.
+    fn if_statement(&mut self, token: &Token) -> Result<(), AsmError> {
+        let condition = self.condition(token)?;
+        let keyword = self.next()?;
+        match keyword.text.as_str() {
+            "then" => self.skip_if(token, condition.not()),
+            "begin" => {
+                self.skip_if(token, condition)?;
+                let jump = self.jump_placeholder(token)?;
+                self.blocks.push(Block::If {
+                    jump,
+                    token: token.clone(),
+                });
+                Ok(())
+            }
+            _ => Err(keyword.error(AsmErrorKind::InvalidOperands {
+                mnemonic: token.text.clone(),
+            })),
+        }
+    }
+
+    /// Parses the condition of `if` or `while`.
+    ///
+    /// The comparisons are turned into a test of `vf`, computed right away.
+    fn condition(&mut self, keyword: &Token) -> Result<Condition, AsmError> {
+        let lhs = self.next()?;
+        let x = self.register(&lhs)?;
+        let op = self.next()?;
+        match op.text.as_str() {
+            "key" => return Ok(Condition::Key(x)),
+            "-key" => return Ok(Condition::NotKey(x)),
+            _ => {}
+        }
+        let rhs = self.next()?;
+        let rhs = match self.register(&rhs) {
+            Ok(y) => Rhs::Register(y),
+            Err(_) => Rhs::Byte(self.byte_value(&rhs)?),
+        };
+        match op.text.as_str() {
+            "==" => Ok(Condition::Equal(x, rhs)),
+            "!=" => Ok(Condition::NotEqual(x, rhs)),
+            "<" | ">" | "<=" | ">=" => {
+                if x == 0xF || rhs == Rhs::Register(0xF) {
+                    return Err(lhs.error(AsmErrorKind::InvalidOperands {
+                        mnemonic: op.text.clone(),
+                    }));
+                }
+                let load = match rhs {
+                    Rhs::Register(y) => Instruction::RegisterSet8XY0(0xF, y),
+                    Rhs::Byte(n) => Instruction::SetVRegister6XNN(0xF, n),
+                };
+                self.emit_instruction(keyword, load)?;
+                // `vf -= vx` leaves vf = 1 if vx <= rhs, `vf =- vx` if vx >= rhs.
+                let (compare, holds) = match op.text.as_str() {
+                    "<=" => (Instruction::RegisterSub8XY5(0xF, x), 1),
+                    ">" => (Instruction::RegisterSub8XY5(0xF, x), 0),
+                    ">=" => (Instruction::RegisterSubRev8XY7(0xF, x), 1),
+                    _ => (Instruction::RegisterSubRev8XY7(0xF, x), 0),
+                };
+                self.emit_instruction(keyword, compare)?;
+                Ok(Condition::Equal(0xF, Rhs::Byte(holds)))
+            }
+            _ => Err(op.error(AsmErrorKind::InvalidOperands {
+                mnemonic: keyword.text.clone(),
+            })),
+        }
+    }
+
+    /// Emits the instruction skipping the next one if `condition` holds.
+    fn skip_if(&mut self, token: &Token, condition: Condition) -> Result<(), AsmError> {
+        let inst = match condition {
+            Condition::Equal(x, Rhs::Register(y)) => Instruction::SkipIfEqual5XY0(x, y),
+            Condition::Equal(x, Rhs::Byte(nn)) => Instruction::SkipEqual3XNN(x, nn),
+            Condition::NotEqual(x, Rhs::Register(y)) => Instruction::SkipIfNotEqual9XY0(x, y),
+            Condition::NotEqual(x, Rhs::Byte(nn)) => Instruction::SkipNotEqual4XNN(x, nn),
+            Condition::Key(x) => Instruction::SkipIfKeyPressedEX9E(x),
+            Condition::NotKey(x) => Instruction::SkipIfKeyNotPressedEXA1(x),
+        };
+        self.emit_instruction(token, inst)
+    }
+
+    /// Emits a jump whose target is patched later, and returns its offset.
+    fn jump_placeholder(&mut self, token: &Token) -> Result<usize, AsmError> {
+        self.instruction(token, Instruction::Jump1NNN(0))
+    }
+
+    /// Points the jump at `offset` to the current address.
+    fn resolve_jump(&mut self, offset: usize, token: &Token) -> Result<(), AsmError> {
+        self.patch(offset, FixupKind::Address, self.here, token)
+    }
+
+    fn call(&mut self, token: &Token, target: &Token) -> Result<(), AsmError> {
+        let offset = self.instruction(token, Instruction::SubroutineCall2NNN(0))?;
+        self.reference(offset, FixupKind::Address, target)
+    }
+
+    fn macro_definition(&mut self) -> Result<(), AsmError> {
+        let name = self.next()?;
+        let mut params = Vec::new();
+        loop {
+            let param = self.next()?;
+            if param.text == "{" {
+                break;
+            }
+            params.push(param.text);
+        }
+        let body = self.braced()?;
+        self.check_name(&name)?;
+        self.macros.insert(name.text, Macro { params, body });
+        Ok(())
+    }
+
+    /// Replaces the macro call `token` and its arguments by the macro body.
+    fn expand(&mut self, token: &Token) -> Result<(), AsmError> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(token.error(AsmErrorKind::RecursiveMacro {
+                name: token.text.clone(),
+            }));
+        }
+        let Macro { params, body } = self.macros[&token.text].clone();
+        let args = params
+            .iter()
+            .map(|_| self.next())
+            .collect::<Result<Vec<_>, _>>()?;
+        for body_token in body.into_iter().rev() {
+            let text = params
+                .iter()
+                .position(|param| *param == body_token.text)
+                .map_or(body_token.text, |index| args[index].text.clone());
+            self.tokens.push_front(Token { text, ..body_token });
+        }
+        Ok(())
+    }
+
+    /// Takes the tokens up to the `}` matching an already taken `{`.
+    fn braced(&mut self) -> Result<Vec<Token>, AsmError> {
+        let mut tokens = Vec::new();
+        let mut depth = 0;
+        loop {
+            let token = self.next()?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" if depth == 0 => return Ok(tokens),
+                "}" => depth -= 1,
+                _ => {}
+            }
+            tokens.push(token);
+        }
+    }
+
+    /// Evaluates the `{ ... }` expression following `token`.
+    fn calc(&mut self, token: &Token) -> Result<i64, AsmError> {
+        self.expect("{", token)?;
+        let open = self.last.clone();
+        let tokens = self.braced()?;
+        let mut pos = 0;
+        let value = self.expression(&tokens, &mut pos, &open)?;
+        if let Some(token) = tokens.get(pos) {
+            return Err(token.error(AsmErrorKind::InvalidExpression));
+        }
+        Ok(value)
+    }
+
+    /// Evaluates a `:calc` expression from right to left.
+    fn expression(&self, tokens: &[Token], pos: &mut usize, open: &Token) -> Result<i64, AsmError> {
+        let lhs = self.term(tokens, pos, open)?;
+        let Some(op) = tokens.get(*pos).filter(|op| op.text != ")") else {
+            return Ok(lhs);
+        };
+        *pos += 1;
+        let rhs = self.expression(tokens, pos, open)?;
+        let shift = || u32::try_from(rhs).ok();
+        let value = match op.text.as_str() {
+            "+" => Some(lhs.wrapping_add(rhs)),
+            "-" => Some(lhs.wrapping_sub(rhs)),
+            "*" => Some(lhs.wrapping_mul(rhs)),
+            "/" => lhs.checked_div(rhs),
+            "%" => lhs.checked_rem(rhs),
+            "&" => Some(lhs & rhs),
+            "|" => Some(lhs | rhs),
+            "^" => Some(lhs ^ rhs),
+            "<<" => shift().and_then(|shift| lhs.checked_shl(shift)),
+            ">>" => shift().and_then(|shift| lhs.checked_shr(shift)),
+            "min" => Some(lhs.min(rhs)),
+            "max" => Some(lhs.max(rhs)),
+            _ => None,
+        };
+        value.ok_or_else(|| op.error(AsmErrorKind::InvalidExpression))
+    }
+
+    fn term(&self, tokens: &[Token], pos: &mut usize, open: &Token) -> Result<i64, AsmError> {
+        let token = tokens
+            .get(*pos)
+            .ok_or_else(|| open.error(AsmErrorKind::UnexpectedEnd))?;
+        *pos += 1;
+        match token.text.as_str() {
+            "(" => {
+                let value = self.expression(tokens, pos, open)?;
+                if tokens.get(*pos).map(|token| token.text.as_str()) != Some(")") {
+                    return Err(token.error(AsmErrorKind::InvalidExpression));
+                }
+                *pos += 1;
+                Ok(value)
+            }
+            "-" => Ok(self.term(tokens, pos, open)?.wrapping_neg()),
+            "~" => Ok(!self.term(tokens, pos, open)?),
+            "!" => Ok(i64::from(self.term(tokens, pos, open)? == 0)),
+            "HERE" => Ok(i64::try_from(self.here).unwrap_or(i64::MAX)),
+            _ => self.value(token),
+        }
+    }
+
+    /// Returns the value of a number, a constant or a label defined above.
+    fn value(&self, token: &Token) -> Result<i64, AsmError> {
+        if let Some(value) = number(&token.text) {
+            return Ok(value);
+        }
+        if let Some(value) = self.constants.get(&token.text) {
+            return Ok(*value);
+        }
+        if let Some(address) = self.labels.get(&token.text) {
+            return Ok(i64::try_from(*address).unwrap_or(i64::MAX));
+        }
+        let kind = if is_name(&token.text) {
+            AsmErrorKind::UndefinedSymbol {
+                name: token.text.clone(),
+            }
+        } else {
+            AsmErrorKind::InvalidExpression
+        };
+        Err(token.error(kind))
+    }
+
+    fn value_in(&self, token: &Token, min: i64, max: i64) -> Result<i64, AsmError> {
+        let value = self.value(token)?;
+        if (min..=max).contains(&value) {
+            Ok(value)
+        } else {
+            Err(token.error(AsmErrorKind::OutOfRange { value, min, max }))
+        }
+    }
+
+    fn byte_value(&self, token: &Token) -> Result<u8, AsmError> {
+        let value = self.value_in(token, -0x80, 0xFF)?;
+        Ok(u8::try_from(value & 0xFF).unwrap_or_default())
+    }
+
+    fn nibble(&self, token: &Token) -> Result<u8, AsmError> {
+        let value = self.value_in(token, 0, 0xF)?;
+        Ok(u8::try_from(value).unwrap_or_default())
+    }
+
+    fn register(&self, token: &Token) -> Result<usize, AsmError> {
+        register(&token.text)
+            .or_else(|| self.aliases.get(&token.text).copied())
+            .ok_or_else(|| token.error(AsmErrorKind::InvalidExpression))
+    }
+
+    /// Checks that `name` is valid and not taken.
+    fn check_name(&self, name: &Token) -> Result<(), AsmError> {
+        let kind = if !is_name(&name.text) {
+            AsmErrorKind::InvalidSymbol {
+                name: name.text.clone(),
+            }
+        } else if self.labels.contains_key(&name.text)
+            || self.constants.contains_key(&name.text)
+            || self.aliases.contains_key(&name.text)
+            || self.macros.contains_key(&name.text)
+        {
+            AsmErrorKind::DuplicateSymbol {
+                name: name.text.clone(),
+            }
+        } else {
+            return Ok(());
+        };
+        Err(name.error(kind))
+    }
+
+    fn define_label(&mut self, name: &Token, address: usize) -> Result<(), AsmError> {
+        self.check_name(name)?;
+        self.labels.insert(name.text.clone(), address);
+        Ok(())
+    }
+
+    /// Emits the jump to `main`, unless the program already started.
+    fn start(&mut self, token: &Token) -> Result<(), AsmError> {
+        if self.started {
+            return Ok(());
+        }
+        self.started = true;
+        let offset = self.instruction(token, Instruction::Jump1NNN(0))?;
+        let main = Token {
+            text: "main".to_string(),
+            ..token.clone()
+        };
+        self.reference(offset, FixupKind::Address, &main)
+    }
+
+    fn byte(&mut self, token: &Token, value: i64) -> Result<(), AsmError> {
+        if !(-0x80..=0xFF).contains(&value) {
+            return Err(token.error(AsmErrorKind::OutOfRange {
+                value,
+                min: -0x80,
+                max: 0xFF,
+            }));
+        }
+        self.emit(token, &[u8::try_from(value & 0xFF).unwrap_or_default()])?;
+        Ok(())
+    }
+
+    fn emit_instruction(&mut self, token: &Token, inst: Instruction) -> Result<(), AsmError> {
+        self.instruction(token, inst)?;
+        Ok(())
+    }
+
+    /// Emits the instruction if the platform supports it, and returns its offset.
+    fn instruction(&mut self, token: &Token, inst: Instruction) -> Result<usize, AsmError> {
+        let bytes = inst.to_bytes();
+        if Instruction::decode(&bytes, self.platform) != Some(inst) {
+            return Err(token.error(AsmErrorKind::UnsupportedInstruction {
+                mnemonic: token.text.clone(),
+                platform: self.platform,
+            }));
+        }
+        self.emit(token, &bytes)
+    }
+
+    /// Writes the bytes at the current address, and returns their offset in the ROM.
+    fn emit(&mut self, token: &Token, bytes: &[u8]) -> Result<usize, AsmError> {
+        self.start(token)?;
+        let end = self.here + bytes.len();
+        if end > self.platform.ram_size() {
+            return Err(token.error(AsmErrorKind::RomTooLarge {
+                capacity: self.platform.ram_size() - PROGRAM_START,
+            }));
+        }
+        let offset = self.here - PROGRAM_START;
+        if self.rom.len() < offset + bytes.len() {
+            self.rom.resize(offset + bytes.len(), 0);
+        }
+        self.rom[offset..offset + bytes.len()].copy_from_slice(bytes);
+        self.source_map
+            .lines
+            .insert(self.here, (token.line, bytes.len()));
+        self.here = end;
+        Ok(offset)
+    }
+
+    /// Sets the bytes at `offset` to the address `target`, now if it is
+    /// known or once the label is defined.
+    fn reference(
+        &mut self,
+        offset: usize,
+        kind: FixupKind,
+        target: &Token,
+    ) -> Result<(), AsmError> {
+        if number(&target.text).is_some() || self.constants.contains_key(&target.text) {
+            let value = self.value(target)?;
+            let address = usize::try_from(value).map_err(|_| {
+                target.error(AsmErrorKind::OutOfRange {
+                    value,
+                    min: 0,
+                    max: kind.max(),
+                })
+            })?;
+            return self.patch(offset, kind, address, target);
+        }
+        self.fixups.push(Fixup {
+            offset,
+            kind,
+            name: target.clone(),
+        });
+        Ok(())
+    }
+
+    fn patch(
+        &mut self,
+        offset: usize,
+        kind: FixupKind,
+        address: usize,
+        token: &Token,
+    ) -> Result<(), AsmError> {
+        let value = u16::try_from(address)
+            .ok()
+            .filter(|value| i64::from(*value) <= kind.max())
+            .ok_or_else(|| {
+                token.error(AsmErrorKind::OutOfRange {
+                    value: i64::try_from(address).unwrap_or(i64::MAX),
+                    min: 0,
+                    max: kind.max(),
+                })
+            })?;
+        let [high, low] = value.to_be_bytes();
+        match kind {
+            FixupKind::Address => {
+                self.rom[offset] = self.rom[offset] & 0xF0 | high;
+                self.rom[offset + 1] = low;
+            }
+            FixupKind::Long => {
+                self.rom[offset] = high;
+                self.rom[offset + 1] = low;
+            }
+            FixupKind::HighNibble => self.rom[offset] = self.rom[offset] & 0xF0 | high,
+            FixupKind::HighByte => self.rom[offset] = high,
+            FixupKind::LowByte => self.rom[offset] = low,
+        }
+        Ok(())
+    }
+
+    /// Resolves the references to the labels and returns the program.
+    fn finish(mut self) -> Result<Program, AsmError> {
+        if let Some(block) = self.blocks.first() {
+            let (Block::If { token, .. } | Block::Else { token, .. } | Block::Loop { token, .. }) =
+                block;
+            return Err(token.unbalanced());
+        }
+        for fixup in std::mem::take(&mut self.fixups) {
+            let address = self.labels.get(&fixup.name.text).copied().ok_or_else(|| {
+                fixup.name.error(AsmErrorKind::UndefinedSymbol {
+                    name: fixup.name.text.clone(),
+                })
+            })?;
+            self.patch(fixup.offset, fixup.kind, address, &fixup.name)?;
+        }
+        self.source_map.labels = self.labels.into_iter().collect();
+        Ok(Program {
+            rom: self.rom,
+            source_map: self.source_map,
+        })
+    }
+}
+
+impl FixupKind {
+    /// Returns the largest address the patched bytes can hold.
+    const fn max(self) -> i64 {
+        match self {
+            Self::Address | Self::HighNibble => 0xFFF,
+            Self::Long | Self::HighByte | Self::LowByte => 0xFFFF,
+        }
+    }
+}
+
+/// Splits the source into words, leaving out the `#` comments.
+fn tokenize(source: &str) -> VecDeque<Token> {
+    let mut tokens = VecDeque::new();
+    for (index, line) in source.lines().enumerate() {
+        let code = line.find('#').map_or(line, |end| &line[..end]);
+        let mut start = None;
+        for (offset, c) in code.char_indices().chain([(code.len(), ' ')]) {
+            match (c.is_whitespace(), start) {
+                (true, Some(word_start)) => {
+                    tokens.push_back(Token {
+                        text: code[word_start..offset].to_string(),
+                        line: index + 1,
+                        column: code[..word_start].chars().count() + 1,
+                    });
+                    start = None;
+                }
+                (false, None) => start = Some(offset),
+                _ => {}
+            }
+        }
+    }
+    tokens
+}
+
+/// Parses a decimal, `0x` hexadecimal or `0b` binary number, maybe negative.
+pub(crate) fn number(text: &str) -> Option<i64> {
+    let (negative, digits) = text
+        .strip_prefix('-')
+        .map_or((false, text), |digits| (true, digits));
+    let (digits, radix) = match digits.get(..2) {
+        Some("0x" | "0X") => (&digits[2..], 16),
+        Some("0b" | "0B") => (&digits[2..], 2),
+        _ => (digits, 10),
+    };
+    if !digits.starts_with(|c: char| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let value = i64::from_str_radix(digits, radix).ok()?;
+    Some(if negative { -value } else { value })
+}
+
+/// Returns true if `text` can name a label, a constant, an alias or a macro.
+fn is_name(text: &str) -> bool {
+    !text.is_empty()
+        && !text.starts_with(':')
+        && number(text).is_none()
+        && register(text).is_none()
+        && !KEYWORDS.contains(&text)
+}
@@ -0,0 +1,74 @@
+use std::{
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use crate::FPS;
+
+/// Keeps a frame loop in step with the wall clock.
+///
+/// The emulator itself never sleeps; clients that do not have a frame timer of
+/// their own (e.g. a game engine schedule) can use this helper to call
+/// [`Chip8::run_frame`](crate::Chip8::run_frame) [FPS] times per second.
+#[derive(Debug)]
+pub struct FramePacer {
+    frame: Duration,
+    next_frame: Instant,
+    max_catch_up: u32,
+}
+
+impl FramePacer {
+    /// Returns a pacer for [FPS] frames per second, starting now.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_frame_rate(FPS)
+    }
+
+    /// Returns a pacer for `fps` frames per second, starting now.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fps` is zero.
+    #[must_use]
+    pub fn with_frame_rate(fps: u64) -> Self {
+        assert!(fps > 0, "frame rate must be positive");
+        Self {
+            frame: Duration::from_nanos(1_000_000_000 / fps),
+            next_frame: Instant::now(),
+            max_catch_up: 5,
+        }
+    }
+
+    /// Returns the number of frames due since the last call, without blocking.
+    ///
+    /// When the client falls behind by more than a few frames, the missed
+    /// frames are dropped instead of being run in a burst.
+    pub fn frames_due(&mut self) -> u32 {
+        let now = Instant::now();
+        let mut frames = 0;
+        while self.next_frame <= now {
+            self.next_frame += self.frame;
+            frames += 1;
+            if frames == self.max_catch_up {
+                self.next_frame = now + self.frame;
+                break;
+            }
+        }
+        frames
+    }
+
+    /// Blocks until the next frame is due.
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        if self.next_frame > now {
+            sleep(self.next_frame - now);
+        }
+        self.next_frame = self.next_frame.max(now) + self.frame;
+    }
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
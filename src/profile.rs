@@ -0,0 +1,284 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    io::{self, Write},
+    mem::{discriminant, Discriminant},
+};
+
+use crate::Instruction;
+
+/// Number of entries in each table of the report.
+const REPORT_ENTRIES: usize = 20;
+
+/// Pixels the sprites of a frame turned on and off.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FramePixels {
+    /// Pixels turned on.
+    pub drawn: u64,
+    /// Pixels turned off, i.e. collisions.
+    pub erased: u64,
+}
+
+/// Calls of a subroutine and the cycles spent inside of it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SubroutineProfile {
+    /// Number of `2NNN` calls.
+    pub calls: u64,
+    /// Cycles spent in the subroutine and the subroutines it called.
+    pub cycles: u64,
+}
+
+/// Execution statistics of a program, collected by [`Chip8::set_profiling`](crate::Chip8::set_profiling).
+///
+/// The cycles are instructions, or machine cycles with
+/// [`Speed::CosmacVip`](crate::Speed::CosmacVip), so they show where the
+/// time of a frame goes. Its [`Display`](fmt::Display) is a report of the
+/// hottest addresses, instructions and subroutines, and
+/// [`Profile::write_folded`] writes the call stacks for flame graphs.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    instructions: u64,
+    cycles: u64,
+    /// Executions by address, with the instruction last executed there.
+    addresses: BTreeMap<usize, (Instruction, u64)>,
+    /// Executions by instruction variant, with an instruction of the variant.
+    variants: HashMap<Discriminant<Instruction>, (Instruction, u64)>,
+    subroutines: BTreeMap<usize, SubroutineProfile>,
+    /// Subroutines called and not returned yet, the innermost last.
+    stack: Vec<usize>,
+    /// Depth of the call stack of the machine below [`Profile::stack`],
+    /// i.e. in subroutines called before the profiling started.
+    base_depth: usize,
+    /// Cycles by call stack, without the cycles of the callees.
+    stacks: HashMap<Vec<usize>, u64>,
+    /// Frame in which the profiling started.
+    first_frame: u64,
+    /// Sprite pixels of each frame, the current one last.
+    frames: Vec<FramePixels>,
+}
+
+impl Profile {
+    /// Returns an empty profile starting in frame `first_frame`, with
+    /// `depth` subroutines called and not returned yet.
+    pub(crate) fn new(first_frame: u64, depth: usize) -> Self {
+        Self {
+            instructions: 0,
+            cycles: 0,
+            addresses: BTreeMap::new(),
+            variants: HashMap::new(),
+            subroutines: BTreeMap::new(),
+            stack: Vec::new(),
+            base_depth: depth,
+            stacks: HashMap::new(),
+            first_frame,
+            frames: vec![FramePixels::default()],
+        }
+    }
+
+    /// Accounts for an instruction executed at `pc`.
+    ///
+    /// # Arguments
+    ///
+    /// * `cycles` - the cycles the instruction took.
+    /// * `depth` - the depth of the call stack after the instruction.
+    pub(crate) fn record(&mut self, pc: usize, inst: Instruction, cycles: u64, depth: usize) {
+        self.instructions += 1;
+        self.cycles += cycles;
+        let address = self.addresses.entry(pc).or_insert((inst, 0));
+        *address = (inst, address.1 + 1);
+        self.variants
+            .entry(discriminant(&inst))
+            .or_insert((inst, 0))
+            .1 += 1;
+        if let Some(stack_cycles) = self.stacks.get_mut(&self.stack) {
+            *stack_cycles += cycles;
+        } else {
+            self.stacks.insert(self.stack.clone(), cycles);
+        }
+        for (i, target) in self.stack.iter().enumerate() {
+            // Recursive calls are only counted once.
+            if !self.stack[..i].contains(target) {
+                self.subroutines.entry(*target).or_default().cycles += cycles;
+            }
+        }
+        // Returning from the subroutines called before the profiling
+        // started leaves the stack empty.
+        self.base_depth = self.base_depth.min(depth);
+        self.stack.truncate(depth - self.base_depth);
+        if let Instruction::SubroutineCall2NNN(target) = inst {
+            let target = usize::from(target);
+            self.stack.push(target);
+            self.subroutines.entry(target).or_default().calls += 1;
+        }
+    }
+
+    /// Accounts for the pixels a sprite turned on and off.
+    pub(crate) fn record_sprite(&mut self, drawn: usize, erased: usize) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.drawn += u64::try_from(drawn).unwrap_or(u64::MAX);
+            frame.erased += u64::try_from(erased).unwrap_or(u64::MAX);
+        }
+    }
+
+    /// Starts the next frame.
+    pub(crate) fn next_frame(&mut self) {
+        self.frames.push(FramePixels::default());
+    }
+
+    /// Returns the number of instructions executed.
+    #[must_use]
+    pub const fn instruction_count(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Returns the number of cycles the instructions took.
+    #[must_use]
+    pub const fn cycle_count(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Returns the number of executions of the instructions, by address.
+    pub fn address_counts(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        self.addresses
+            .iter()
+            .map(|(address, (_, count))| (*address, *count))
+    }
+
+    /// Returns the number of executions of each instruction variant, such
+    /// as `Dxyn`, the most executed first.
+    #[must_use]
+    pub fn variant_counts(&self) -> Vec<(String, u64)> {
+        let mut counts: Vec<(String, u64)> = self
+            .variants
+            .values()
+            .map(|(inst, count)| (variant_name(*inst), *count))
+            .collect();
+        counts.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then_with(|| a_name.cmp(b_name)));
+        counts
+    }
+
+    /// Returns the calls and cycles of the subroutines, by address.
+    pub fn subroutines(&self) -> impl Iterator<Item = (usize, SubroutineProfile)> + '_ {
+        self.subroutines
+            .iter()
+            .map(|(address, subroutine)| (*address, *subroutine))
+    }
+
+    /// Returns the frame in which the profiling started, see
+    /// [`Chip8::frame_count`](crate::Chip8::frame_count).
+    #[must_use]
+    pub const fn first_frame(&self) -> u64 {
+        self.first_frame
+    }
+
+    /// Returns the sprite pixels of each frame since [`Profile::first_frame`],
+    /// the current one last.
+    #[must_use]
+    pub fn frame_pixels(&self) -> &[FramePixels] {
+        &self.frames
+    }
+
+    /// Writes the cycles by call stack in the folded format of flame graph
+    /// tools, one `main;L2A0;L2F0 cycles` line per stack.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of writing to `w`.
+    pub fn write_folded(&self, mut w: impl Write) -> io::Result<()> {
+        let mut stacks: Vec<(String, u64)> = self
+            .stacks
+            .iter()
+            .map(|(stack, cycles)| {
+                let frames: Vec<String> = std::iter::once("main".to_string())
+                    .chain(stack.iter().map(|address| format!("L{address:03X}")))
+                    .collect();
+                (frames.join(";"), *cycles)
+            })
+            .collect();
+        stacks.sort();
+        for (stack, cycles) in stacks {
+            writeln!(w, "{stack} {cycles}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let share = |count: u64, total: u64| fixed_point(u128::from(count) * 100, total, 2);
+        writeln!(
+            f,
+            "{} instructions, {} cycles, {} frames",
+            self.instructions,
+            self.cycles,
+            self.frames.len()
+        )?;
+
+        writeln!(f, "\nHottest addresses:")?;
+        let mut addresses: Vec<_> = self.addresses.iter().collect();
+        addresses.sort_by(|(a_address, (_, a)), (b_address, (_, b))| {
+            b.cmp(a).then_with(|| a_address.cmp(b_address))
+        });
+        for (address, (inst, count)) in addresses.into_iter().take(REPORT_ENTRIES) {
+            let share = share(*count, self.instructions);
+            let inst = inst.to_string();
+            writeln!(f, "  {address:#05X}  {inst:<24} {count:>12} {share:>6}%")?;
+        }
+
+        writeln!(f, "\nInstructions:")?;
+        for (name, count) in self.variant_counts().into_iter().take(REPORT_ENTRIES) {
+            let share = share(count, self.instructions);
+            writeln!(f, "  {name:<31} {count:>12} {share:>6}%")?;
+        }
+
+        writeln!(f, "\nSubroutines by cycles, including their callees:")?;
+        let mut subroutines: Vec<_> = self.subroutines().collect();
+        subroutines.sort_by(|(a_address, a), (b_address, b)| {
+            b.cycles
+                .cmp(&a.cycles)
+                .then_with(|| a_address.cmp(b_address))
+        });
+        for (address, subroutine) in subroutines.into_iter().take(REPORT_ENTRIES) {
+            let share = share(subroutine.cycles, self.cycles);
+            writeln!(
+                f,
+                "  L{address:03X}  {:>10} calls {:>12} cycles {share:>6}%",
+                subroutine.calls, subroutine.cycles
+            )?;
+        }
+
+        writeln!(f, "\nSprite pixels per frame:")?;
+        let frames = u64::try_from(self.frames.len()).unwrap_or(u64::MAX);
+        let drawn: Vec<u64> = self.frames.iter().map(|pixels| pixels.drawn).collect();
+        let erased: Vec<u64> = self.frames.iter().map(|pixels| pixels.erased).collect();
+        for (name, counts) in [("drawn", drawn), ("erased", erased)] {
+            let total: u64 = counts.iter().sum();
+            let (frame, most) = counts
+                .into_iter()
+                .enumerate()
+                .max_by_key(|(frame, count)| (*count, std::cmp::Reverse(*frame)))
+                .unwrap_or_default();
+            let average = fixed_point(total.into(), frames, 1);
+            let frame = self.first_frame + u64::try_from(frame).unwrap_or_default();
+            writeln!(
+                f,
+                "  {name:<7} {average:>10} on average, {most} at most in frame {frame}"
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats `numerator / denominator` with `decimals` digits after the point.
+fn fixed_point(numerator: u128, denominator: u64, decimals: u32) -> String {
+    let scale = 10u128.pow(decimals);
+    let value = numerator * scale / u128::from(denominator.max(1));
+    let width = usize::try_from(decimals).unwrap_or_default();
+    format!("{}.{:0width$}", value / scale, value % scale)
+}
+
+/// Returns the name of the variant of the instruction, e.g. `Dxyn`.
+fn variant_name(inst: Instruction) -> String {
+    let name = format!("{inst:?}");
+    name.split('(').next().unwrap_or_default().to_string()
+}
@@ -0,0 +1,105 @@
+/// Behaviours that differ between Chip8 interpreters.
+///
+/// The original COSMAC VIP interpreter and its successors disagree on how a
+/// handful of instructions work, and ROMs are written against one of them.
+/// Use one of the presets matching the platform a ROM was written for, or
+/// tweak the individual fields.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quirks {
+    /// `8XY6`/`8XYE` shift VY and store the result in VX, instead of shifting
+    /// VX in place.
+    pub shift_uses_vy: bool,
+    /// How `FX55`/`FX65` change the index register.
+    pub memory_increment: MemoryIncrement,
+    /// `8XY1`/`8XY2`/`8XY3` reset VF to zero.
+    pub logic_resets_vf: bool,
+    /// `DXYN` clips sprites at the screen edges instead of wrapping them around.
+    pub clip_sprites: bool,
+    /// `BNNN` jumps to `XNN + VX` instead of `NNN + V0`.
+    pub jump_uses_vx: bool,
+    /// `DXYN` waits for the vertical blank interrupt, so at most one sprite
+    /// is drawn per frame.
+    pub display_wait: bool,
+    /// `FX0A` completes when the pressed key is released, instead of as soon
+    /// as it is pressed.
+    pub key_wait_release: bool,
+}
+
+/// Index register behaviour of `FX55`/`FX65`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MemoryIncrement {
+    /// The index register is left unchanged.
+    None,
+    /// The index register is incremented by X.
+    X,
+    /// The index register is incremented by X + 1.
+    XPlusOne,
+}
+
+impl Quirks {
+    /// Behaviour of the original COSMAC VIP interpreter.
+    #[must_use]
+    pub const fn cosmac_vip() -> Self {
+        Self {
+            shift_uses_vy: true,
+            memory_increment: MemoryIncrement::XPlusOne,
+            logic_resets_vf: true,
+            clip_sprites: true,
+            jump_uses_vx: false,
+            display_wait: true,
+            key_wait_release: true,
+        }
+    }
+
+    /// Behaviour of the CHIP-48 interpreter for the HP-48 calculators.
+    #[must_use]
+    pub const fn chip48() -> Self {
+        Self {
+            shift_uses_vy: false,
+            memory_increment: MemoryIncrement::X,
+            logic_resets_vf: false,
+            clip_sprites: true,
+            jump_uses_vx: true,
+            display_wait: false,
+            key_wait_release: false,
+        }
+    }
+
+    /// Behaviour of the SUPER-CHIP 1.1 interpreter.
+    #[must_use]
+    pub const fn super_chip() -> Self {
+        Self {
+            shift_uses_vy: false,
+            memory_increment: MemoryIncrement::None,
+            logic_resets_vf: false,
+            clip_sprites: true,
+            jump_uses_vx: true,
+            display_wait: false,
+            key_wait_release: false,
+        }
+    }
+
+    /// Behaviour of modern interpreters such as Octo.
+    #[must_use]
+    pub const fn octo() -> Self {
+        Self {
+            shift_uses_vy: true,
+            memory_increment: MemoryIncrement::XPlusOne,
+            logic_resets_vf: false,
+            clip_sprites: false,
+            jump_uses_vx: false,
+            display_wait: false,
+            key_wait_release: true,
+        }
+    }
+}
+
+impl Default for Quirks {
+    /// Returns the SUPER-CHIP 1.1 behaviour.
+    fn default() -> Self {
+        Self::super_chip()
+    }
+}
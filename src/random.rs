@@ -0,0 +1,147 @@
+use std::{any::Any, fmt};
+
+/// Source of the random numbers used by `CXNN`.
+///
+/// Set it with [`Chip8::set_random`](crate::Chip8::set_random). The emulator
+/// uses a [`SeededRandom`] with a fixed seed by default, so that every run of
+/// a ROM is reproducible.
+pub trait Random: Any + fmt::Debug + Send + Sync {
+    /// Returns the next random byte.
+    fn next_byte(&mut self) -> u8;
+
+    /// Called at the end of every frame.
+    ///
+    /// Generators whose sequence depends on the timing of the program can
+    /// advance their state here.
+    fn next_frame(&mut self) {}
+
+    /// Returns the internal state, to be restored with [`Random::restore`].
+    ///
+    /// It is stored in the save states of [`Chip8::save_state`](crate::Chip8::save_state).
+    fn state(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restores a state returned by [`Random::state`] and returns true on success.
+    fn restore(&mut self, state: &[u8]) -> bool {
+        state.is_empty()
+    }
+}
+
+impl Default for Box<dyn Random> {
+    fn default() -> Self {
+        Box::new(SeededRandom::default())
+    }
+}
+
+/// Deterministic pseudo-random generator (xorshift64*) starting from a seed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeededRandom {
+    state: u64,
+}
+
+impl SeededRandom {
+    /// Seed used by [`SeededRandom::default`].
+    pub const DEFAULT_SEED: u64 = 0x0C8_0C8;
+
+    /// Returns a generator producing the same sequence for the same `seed`.
+    #[must_use]
+    pub const fn new(seed: u64) -> Self {
+        // xorshift gets stuck on a zero state.
+        let state = if seed == 0 { Self::DEFAULT_SEED } else { seed };
+        Self { state }
+    }
+
+    /// Returns a generator seeded from the operating system's entropy source.
+    #[must_use]
+    pub fn from_entropy() -> Self {
+        Self::new(rand::random())
+    }
+}
+
+impl Default for SeededRandom {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_SEED)
+    }
+}
+
+impl Random for SeededRandom {
+    fn next_byte(&mut self) -> u8 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let [byte, ..] = self.state.wrapping_mul(0x2545_F491_4F6C_DD1D).to_be_bytes();
+        byte
+    }
+
+    fn state(&self) -> Vec<u8> {
+        self.state.to_le_bytes().to_vec()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> bool {
+        match <[u8; 8]>::try_from(state) {
+            Ok(bytes) if bytes != [0; 8] => {
+                self.state = u64::from_le_bytes(bytes);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Emulation of the random number routine of the COSMAC VIP interpreter.
+///
+/// The interpreter kept a 16-bit counter in register R9 that its interrupt
+/// routine incremented every frame. `CXNN` incremented it once more, used its
+/// high byte to index a table in the interpreter page, added the byte found
+/// there to the low byte and stored the sum back as the new high byte.
+/// The numbers therefore depend on when the program asks for them, as on
+/// the real machine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CosmacVipRandom {
+    counter: u16,
+    table: [u8; 256],
+}
+
+impl CosmacVipRandom {
+    /// Returns a generator with the counter at `seed`, its value after power-on.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - the 256 bytes of memory the routine reads from; on the VIP
+    ///   this is the interpreter code at `0x0100..0x0200`, which is not
+    ///   bundled with the crate and must come from a dump of the VIP ROM.
+    #[must_use]
+    pub const fn new(seed: u16, table: [u8; 256]) -> Self {
+        Self {
+            counter: seed,
+            table,
+        }
+    }
+}
+
+impl Random for CosmacVipRandom {
+    fn next_byte(&mut self) -> u8 {
+        self.counter = self.counter.wrapping_add(1);
+        let [high, low] = self.counter.to_be_bytes();
+        let value = self.table[usize::from(high)].wrapping_add(low);
+        self.counter = u16::from_be_bytes([value, low]);
+        value
+    }
+
+    fn next_frame(&mut self) {
+        self.counter = self.counter.wrapping_add(1);
+    }
+
+    fn state(&self) -> Vec<u8> {
+        self.counter.to_le_bytes().to_vec()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> bool {
+        let Ok(bytes) = <[u8; 2]>::try_from(state) else {
+            return false;
+        };
+        self.counter = u16::from_le_bytes(bytes);
+        true
+    }
+}
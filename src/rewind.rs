@@ -0,0 +1,247 @@
+use std::collections::VecDeque;
+
+use crate::{Chip8, Result};
+
+/// Ring buffer of save states for rewinding the emulation.
+///
+/// Call [`Rewind::record`] after every frame and [`Rewind::rewind`] to go
+/// back in time. Only the newest state is kept whole; the older ones are
+/// stored as the differences to their successor, which are small since few
+/// bytes of the machine change between frames. The oldest states are
+/// dropped to stay within the memory budget.
+#[derive(Debug)]
+pub struct Rewind {
+    interval: u64,
+    budget: usize,
+    latest: Option<Snapshot>,
+    /// Older snapshots, the oldest first, each holding the difference to the next one.
+    deltas: VecDeque<Snapshot>,
+    delta_bytes: usize,
+}
+
+#[derive(Debug)]
+struct Snapshot {
+    frame: u64,
+    data: Vec<u8>,
+}
+
+impl Rewind {
+    /// Returns an empty rewind buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - records a state every `interval` frames; `1` allows
+    ///   rewinding to any frame.
+    /// * `budget` - the maximum memory used by the states, in bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    #[must_use]
+    pub fn new(interval: u64, budget: usize) -> Self {
+        assert!(interval > 0, "rewind interval must be positive");
+        Self {
+            interval,
+            budget,
+            latest: None,
+            deltas: VecDeque::new(),
+            delta_bytes: 0,
+        }
+    }
+
+    /// Records the state of the machine if `interval` frames passed since the last one.
+    pub fn record(&mut self, chip8: &Chip8) {
+        let frame = chip8.frame_count();
+        if let Some(latest) = &self.latest {
+            if frame >= latest.frame && frame - latest.frame < self.interval {
+                return;
+            }
+        }
+        let data = chip8.save_state();
+        if let Some(latest) = self.latest.take() {
+            let delta = Snapshot {
+                frame: latest.frame,
+                data: diff(&data, &latest.data),
+            };
+            self.delta_bytes += delta.data.len();
+            self.deltas.push_back(delta);
+        }
+        self.latest = Some(Snapshot { frame, data });
+        self.evict();
+    }
+
+    /// Goes back `frames` frames, to the newest recorded state at or before that frame.
+    ///
+    /// The states newer than the restored one are dropped. If not enough
+    /// history is recorded, the oldest state is restored. Returns the frame
+    /// count of the restored state, or the current one if nothing is recorded.
+    ///
+    /// # Errors
+    ///
+    /// See [`Chip8::load_state`].
+    pub fn rewind(&mut self, chip8: &mut Chip8, frames: u64) -> Result<u64> {
+        let target = chip8.frame_count().saturating_sub(frames);
+        let Some(mut current) = self.latest.take() else {
+            return Ok(chip8.frame_count());
+        };
+        while current.frame > target {
+            let Some(delta) = self.deltas.pop_back() else {
+                break;
+            };
+            self.delta_bytes -= delta.data.len();
+            current = Snapshot {
+                frame: delta.frame,
+                data: patch(&current.data, &delta.data),
+            };
+        }
+        let result = chip8.load_state(&current.data);
+        let frame = current.frame;
+        self.latest = Some(current);
+        result.map(|()| frame)
+    }
+
+    /// Returns the number of recorded states.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.deltas.len() + usize::from(self.latest.is_some())
+    }
+
+    /// Returns true if no state is recorded.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    /// Returns the memory used by the states, in bytes.
+    #[must_use]
+    pub fn memory_usage(&self) -> usize {
+        self.delta_bytes + self.latest.as_ref().map_or(0, |latest| latest.data.len())
+    }
+
+    /// Drops all the recorded states.
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.delta_bytes = 0;
+    }
+
+    /// Drops the oldest states until the memory budget is met.
+    fn evict(&mut self) {
+        while self.memory_usage() > self.budget {
+            let Some(oldest) = self.deltas.pop_front() else {
+                break;
+            };
+            self.delta_bytes -= oldest.data.len();
+        }
+    }
+}
+
+/// Returns the difference turning `from` into `to`.
+///
+/// It is the length of `to`, followed by runs of unchanged bytes to skip and
+/// of bytes to XOR, each preceded by its length.
+fn diff(from: &[u8], to: &[u8]) -> Vec<u8> {
+    let xor: Vec<u8> = (0..to.len())
+        .map(|i| to[i] ^ from.get(i).copied().unwrap_or(0))
+        .collect();
+    let mut delta = Vec::new();
+    write_varint(&mut delta, to.len());
+    let mut pos = 0;
+    while pos < xor.len() {
+        let skip = xor[pos..].iter().take_while(|b| **b == 0).count();
+        let changed = xor[pos + skip..].iter().take_while(|b| **b != 0).count();
+        write_varint(&mut delta, skip);
+        write_varint(&mut delta, changed);
+        delta.extend_from_slice(&xor[pos + skip..pos + skip + changed]);
+        pos += skip + changed;
+    }
+    delta
+}
+
+/// Applies a difference returned by [`diff`] to `from`.
+fn patch(from: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut delta = delta.iter().copied();
+    let len = read_varint(&mut delta);
+    let mut to: Vec<u8> = (0..len)
+        .map(|i| from.get(i).copied().unwrap_or(0))
+        .collect();
+    let mut pos = 0;
+    while pos < len {
+        pos += read_varint(&mut delta);
+        let changed = read_varint(&mut delta);
+        for (byte, xor) in to[pos..pos + changed].iter_mut().zip(&mut delta) {
+            *byte ^= xor;
+        }
+        pos += changed;
+    }
+    to
+}
+
+/// Writes an unsigned LEB128 number.
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = u8::try_from(value & 0x7F).expect("7 bits fit in u8");
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Reads an unsigned LEB128 number.
+fn read_varint(input: &mut impl Iterator<Item = u8>) -> usize {
+    let mut value = 0;
+    for (shift, byte) in input.enumerate() {
+        value |= usize::from(byte & 0x7F) << (7 * shift);
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn varint_round_trip() {
+        for (value, len) in [(0, 1), (127, 1), (128, 2), (usize::MAX, 10)] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, value);
+            assert_eq!(bytes.len(), len, "{value}");
+            assert_eq!(read_varint(&mut bytes.into_iter()), value);
+        }
+    }
+
+    #[test]
+    fn patch_reverts_diff() {
+        let long_run = [vec![1; 3], vec![0; 1000], vec![2; 3]].concat();
+        let cases: [(&[u8], &[u8]); 6] = [
+            (&[], &[]),
+            (&[1, 2, 3], &[1, 2, 3]),
+            (&[1, 2, 3, 4], &[1, 5, 3, 6]),
+            (&[1, 2, 3], &[1, 2, 3, 4, 0, 5]),
+            (&[1, 2, 3, 4, 5], &[1, 9]),
+            (&[0; 1006], &long_run),
+        ];
+        for (from, to) in cases {
+            assert_eq!(patch(from, &diff(from, to)), to, "{from:?} -> {to:?}");
+        }
+    }
+
+    #[test]
+    fn unchanged_runs_take_a_few_bytes() {
+        let from = vec![7; 1000];
+        let mut to = from.clone();
+        to[500] = 8;
+        // The length, then runs of 500 unchanged and 1 changed byte, and of
+        // 499 unchanged bytes.
+        assert_eq!(
+            diff(&from, &to),
+            [0xE8, 0x07, 0xF4, 0x03, 0x01, 0x0F, 0xF3, 0x03, 0x00]
+        );
+    }
+}
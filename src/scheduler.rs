@@ -0,0 +1,221 @@
+use crate::state::{Reader, Writer};
+use crate::{Instruction, FPS};
+
+/// Machine cycles of the COSMAC VIP available to the interpreter in a frame.
+///
+/// The RCA 1802 runs 3668 machine cycles per 60 Hz frame, of which the
+/// display DMA steals 1024 and the interrupt routine about 50.
+const COSMAC_VIP_CYCLES_PER_FRAME: u64 = 3668 - 1024 - 50;
+
+/// Emulation speed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Speed {
+    /// Instructions per second. The common value used is `700`.
+    ///
+    /// When it is not a multiple of [FPS], frames run a varying number of
+    /// instructions, so that exactly this many run per emulated second.
+    InstructionsPerSecond(u64),
+    /// Instructions per frame, as Octo's "cycles per frame" setting.
+    CyclesPerFrame(u64),
+    /// Instructions take as long as on the original COSMAC VIP interpreter.
+    ///
+    /// Each instruction costs the machine cycles the interpreter needed for
+    /// it, and sprite drawing costs depend on the sprite size, position and
+    /// collisions. Combine it with [`Quirks::cosmac_vip`](crate::Quirks::cosmac_vip),
+    /// whose display wait makes `DXYN` wait for the vertical blank interrupt.
+    CosmacVip,
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Self::InstructionsPerSecond(700)
+    }
+}
+
+/// Splits the emulated time into 60 Hz frames and the instructions within them.
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    speed: Speed,
+    /// Fractional instructions carried over to the next frame, in `1 / FPS` units.
+    remainder: u64,
+    /// Cycles left to run in the current frame.
+    budget: u64,
+    /// Cycles overrunning the current frame, taken from the next one.
+    debt: u64,
+    /// Cycles charged since the start, for profiling. Not part of the save states.
+    cycles: u64,
+}
+
+impl Scheduler {
+    /// Returns a scheduler at the start of the first frame.
+    pub fn new(speed: Speed) -> Self {
+        let mut scheduler = Self {
+            speed,
+            ..Self::default()
+        };
+        scheduler.next_frame();
+        scheduler
+    }
+
+    /// Returns the speed.
+    pub const fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// Changes the speed, starting from the next frame.
+    pub const fn set_speed(&mut self, speed: Speed) {
+        self.speed = speed;
+        self.remainder = 0;
+        self.debt = 0;
+    }
+
+    /// Returns the number of cycles left in the current frame.
+    pub const fn budget(&self) -> u64 {
+        self.budget
+    }
+
+    /// Returns the number of cycles charged since the start.
+    pub const fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Returns false if no instruction would ever run, i.e. the speed is zero.
+    pub const fn is_running(&self) -> bool {
+        !matches!(
+            self.speed,
+            Speed::InstructionsPerSecond(0) | Speed::CyclesPerFrame(0)
+        )
+    }
+
+    /// Accounts for an instruction run in the current frame.
+    pub fn consume(&mut self, inst: Instruction) {
+        let cycles = match self.speed {
+            Speed::CosmacVip => cosmac_vip_cycles(inst),
+            _ => 1,
+        };
+        self.charge(cycles);
+    }
+
+    /// Accounts for a slot in which no instruction ran, e.g. while waiting for a key.
+    ///
+    /// The COSMAC VIP timing has no instruction slots, so the rest of the frame
+    /// is skipped instead.
+    pub const fn idle(&mut self) {
+        match self.speed {
+            Speed::CosmacVip => self.wait_for_next_frame(),
+            _ => self.charge(1),
+        }
+    }
+
+    /// Accounts for drawing a sprite, on top of the cost of the instruction.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - the number of sprite rows.
+    /// * `aligned` - true if the sprite starts at a multiple of 8 horizontally,
+    ///   so each row is drawn into a single byte of the display memory.
+    /// * `collision` - true if any pixel was turned off.
+    pub const fn consume_sprite(&mut self, rows: u64, aligned: bool, collision: bool) {
+        if matches!(self.speed, Speed::CosmacVip) {
+            let row_cycles = if aligned { 17 } else { 25 };
+            let collision_cycles = if collision { 4 } else { 0 };
+            self.charge(rows * row_cycles + collision_cycles);
+        }
+    }
+
+    /// Skips the rest of the current frame, waiting for the vertical blank interrupt.
+    pub const fn wait_for_next_frame(&mut self) {
+        self.budget = 0;
+    }
+
+    /// Ends the current frame and computes the budget of the next one.
+    pub const fn next_frame(&mut self) {
+        let budget = match self.speed {
+            Speed::CyclesPerFrame(cycles) => cycles,
+            Speed::InstructionsPerSecond(ips) => {
+                self.remainder += ips;
+                let budget = self.remainder / FPS;
+                self.remainder %= FPS;
+                budget
+            }
+            Speed::CosmacVip => COSMAC_VIP_CYCLES_PER_FRAME,
+        };
+        self.budget = budget.saturating_sub(self.debt);
+        self.debt = self.debt.saturating_sub(budget);
+    }
+
+    /// Writes the scheduler into a save state.
+    pub fn save(&self, w: &mut Writer) {
+        w.speed(self.speed);
+        w.u64(self.remainder);
+        w.u64(self.budget);
+        w.u64(self.debt);
+    }
+
+    /// Reads a scheduler written by [`Scheduler::save`].
+    pub fn load(r: &mut Reader) -> crate::Result<Self> {
+        Ok(Self {
+            speed: r.speed()?,
+            remainder: r.u64()?,
+            budget: r.u64()?,
+            debt: r.u64()?,
+            cycles: 0,
+        })
+    }
+
+    /// Takes cycles from the current frame, and from the next ones if it runs out.
+    const fn charge(&mut self, cycles: u64) {
+        self.cycles += cycles;
+        if cycles <= self.budget {
+            self.budget -= cycles;
+        } else {
+            self.debt += cycles - self.budget;
+            self.budget = 0;
+        }
+    }
+}
+
+/// Returns the approximate machine cycles the COSMAC VIP interpreter needed
+/// for an instruction, including fetching and decoding it.
+///
+/// Sprite drawing costs on top of this are accounted by
+/// [`Scheduler::consume_sprite`].
+fn cosmac_vip_cycles(inst: Instruction) -> u64 {
+    match inst {
+        Instruction::SetVRegister6XNN(..) => 6,
+        Instruction::SkipEqual3XNN(..)
+        | Instruction::SkipNotEqual4XNN(..)
+        | Instruction::SetIndexRegisterANNN(_) => 12,
+        Instruction::SkipIfEqual5XY0(..)
+        | Instruction::SkipIfNotEqual9XY0(..)
+        | Instruction::SkipIfKeyPressedEX9E(_)
+        | Instruction::SkipIfKeyNotPressedEXA1(_)
+        | Instruction::GetKeyFX0A(_) => 16,
+        Instruction::AddToIndexFX1E(_) => 19,
+        Instruction::FontCharacterFX29(_) => 20,
+        Instruction::SubroutineReturn00EE
+        | Instruction::Jump1NNN(_)
+        | Instruction::SubroutineCall2NNN(_)
+        | Instruction::JumpWithOffsetBNNN(_) => 23,
+        Instruction::Cls00E0 => 24,
+        Instruction::Dxyn(..) => 26,
+        Instruction::RandomCXNN(..) => 36,
+        Instruction::RegisterSet8XY0(..)
+        | Instruction::Or8XY1(..)
+        | Instruction::BinaryAnd8XY2(..)
+        | Instruction::Xor8XY3(..)
+        | Instruction::RegisterAdd8XY4(..)
+        | Instruction::RegisterSub8XY5(..)
+        | Instruction::ShiftRight8XY6(..)
+        | Instruction::RegisterSubRev8XY7(..)
+        | Instruction::ShiftLeft8XYE(..) => 44,
+        Instruction::StoreRegistersToMemoryFX55(x)
+        | Instruction::LoadRegistersFromMemoryFX65(x) => {
+            20 + 14 * u64::try_from(x + 1).expect("register count fits in u64")
+        }
+        Instruction::BinaryCodedDecimalConversionFX33(_) => 204,
+        // 7XNN, FX07, FX15, FX18 and the instructions the VIP did not have.
+        _ => 10,
+    }
+}
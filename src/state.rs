@@ -0,0 +1,343 @@
+use crate::{
+    display::Display, scheduler::Scheduler, Chip8, Chip8Error, MachineCodeCall, MemoryIncrement,
+    Platform, Quirks, Result, Speed, STACK_SIZE,
+};
+
+/// Identifies the save state format.
+const MAGIC: &[u8; 4] = b"CH8S";
+/// Version of the save state format written by this crate.
+///
+/// Increment it when the layout changes, and keep reading the older versions.
+const VERSION: u8 = 1;
+/// Marks an absent optional byte.
+const NONE: u8 = 0xFF;
+
+impl Chip8 {
+    /// Returns a snapshot of the complete machine, to be restored with [`Chip8::load_state`].
+    ///
+    /// The snapshot is a compact versioned binary format, holding the RAM,
+    /// the registers, the call stack, the timers, the display, the keypad,
+    /// the random number generator state and the settings. Later versions of
+    /// the crate can read it.
+    #[must_use]
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        w.bytes(MAGIC);
+        w.u8(VERSION);
+        w.platform(self.platform);
+        w.quirks(self.quirks);
+        w.bool(self.machine_code_call == MachineCodeCall::Error);
+        self.scheduler.save(&mut w);
+        w.u64(self.frame);
+        w.len(self.ram.len());
+        w.bytes(&self.ram);
+        w.len(self.pc);
+        w.len(self.i);
+        w.u16(self.opcode);
+        w.bytes(&self.registers);
+        w.len(self.stack.len());
+        for address in &self.stack {
+            w.len(*address);
+        }
+        w.u8(self.delay_timer);
+        w.u8(self.sound_timer);
+        w.u16(self.keys);
+        let waiting_for_input = self.waiting_for_input.and_then(|x| u8::try_from(x).ok());
+        w.u8(waiting_for_input.unwrap_or(NONE));
+        w.u8(self.waiting_key.unwrap_or(NONE));
+        w.bytes(&self.rpl_flags);
+        w.bool(self.exited);
+        w.bytes(&self.audio_pattern);
+        w.u8(self.pitch);
+        self.display.save(&mut w);
+        let random = self.random.state();
+        w.len(random.len());
+        w.bytes(&random);
+        w.into_bytes()
+    }
+
+    /// Returns a hash of the complete machine state, as saved by [`Chip8::save_state`].
+    ///
+    /// It is stable across platforms and crate versions writing the same
+    /// save state format, so it can be stored to check determinism later.
+    #[must_use]
+    pub fn state_hash(&self) -> u64 {
+        fnv1a(&self.save_state())
+    }
+
+    /// Restores a snapshot returned by [`Chip8::save_state`].
+    ///
+    /// The random number generator must be of the same type as when the
+    /// snapshot was taken, see [`Chip8::set_random`]. The restored display is
+    /// reported through [`Graphics::present`](crate::Graphics::present) and
+    /// the audio is brought up to date, at the end of the next frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Chip8Error::UnsupportedSaveState`] if the snapshot was written
+    /// by a newer version of the crate, or [`Chip8Error::CorruptSaveState`] if
+    /// it is invalid. The machine is left unchanged on error.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<()> {
+        let mut r = Reader::new(state);
+        if r.bytes(MAGIC.len())? != MAGIC {
+            return Err(Chip8Error::CorruptSaveState);
+        }
+        let version = r.u8()?;
+        if version > VERSION {
+            return Err(Chip8Error::UnsupportedSaveState { version });
+        }
+        let platform = r.platform()?;
+        let quirks = r.quirks()?;
+        let machine_code_call = if r.bool()? {
+            MachineCodeCall::Error
+        } else {
+            MachineCodeCall::Ignore
+        };
+        let scheduler = Scheduler::load(&mut r)?;
+        let frame = r.u64()?;
+        let ram_size = r.len()?;
+        if ram_size != platform.ram_size() {
+            return Err(Chip8Error::CorruptSaveState);
+        }
+        let ram = r.bytes(ram_size)?.to_vec();
+        let address = |address: usize| {
+            if address < ram.len() {
+                Ok(address)
+            } else {
+                Err(Chip8Error::CorruptSaveState)
+            }
+        };
+        let pc = address(r.len()?)?;
+        // Instructions can leave the index register right after the RAM.
+        let i = r.len()?;
+        if i > ram.len() {
+            return Err(Chip8Error::CorruptSaveState);
+        }
+        let opcode = r.u16()?;
+        let registers = r.array()?;
+        let depth = r.len()?;
+        if depth > STACK_SIZE {
+            return Err(Chip8Error::CorruptSaveState);
+        }
+        let stack = (0..depth)
+            .map(|_| address(r.len()?))
+            .collect::<Result<Vec<_>>>()?;
+        let delay_timer = r.u8()?;
+        let sound_timer = r.u8()?;
+        let keys = r.u16()?;
+        let waiting_for_input = r.option()?.map(usize::from);
+        let waiting_key = r.option()?;
+        if waiting_for_input.is_some_and(|x| x >= 16) || waiting_key.is_some_and(|k| k >= 16) {
+            return Err(Chip8Error::CorruptSaveState);
+        }
+        let rpl_flags = r.array()?;
+        let exited = r.bool()?;
+        let audio_pattern = r.array()?;
+        let pitch = r.u8()?;
+        let display = Display::load(&mut r)?;
+        let random_size = r.len()?;
+        let random = r.bytes(random_size)?;
+        if !r.is_empty() || !self.random.restore(random) {
+            return Err(Chip8Error::CorruptSaveState);
+        }
+
+        self.platform = platform;
+        self.quirks = quirks;
+        self.machine_code_call = machine_code_call;
+        self.scheduler = scheduler;
+        self.frame = frame;
+        self.ram = ram;
+        self.pc = pc;
+        self.i = i;
+        self.opcode = opcode;
+        self.registers = registers;
+        self.stack = stack;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.keys = keys;
+        self.waiting_for_input = waiting_for_input;
+        self.waiting_key = waiting_key;
+        self.rpl_flags = rpl_flags;
+        self.exited = exited;
+        self.audio_pattern = audio_pattern;
+        self.pitch = pitch;
+        self.audio_changed = true;
+        self.display = display;
+        Ok(())
+    }
+}
+
+/// Returns the 64-bit FNV-1a hash of the data.
+pub fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01B3)
+    })
+}
+
+/// Little-endian encoder of the save states.
+#[derive(Debug, Default)]
+pub struct Writer {
+    data: Vec<u8>,
+}
+
+impl Writer {
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(u8::from(value));
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    /// Writes a size or an address.
+    pub fn len(&mut self, value: usize) {
+        self.u64(u64::try_from(value).expect("usize fits in u64"));
+    }
+
+    pub fn platform(&mut self, platform: Platform) {
+        self.u8(match platform {
+            Platform::Chip8 => 0,
+            Platform::SuperChip => 1,
+            Platform::XoChip => 2,
+        });
+    }
+
+    pub fn quirks(&mut self, quirks: Quirks) {
+        self.bool(quirks.shift_uses_vy);
+        self.u8(match quirks.memory_increment {
+            MemoryIncrement::None => 0,
+            MemoryIncrement::X => 1,
+            MemoryIncrement::XPlusOne => 2,
+        });
+        self.bool(quirks.logic_resets_vf);
+        self.bool(quirks.clip_sprites);
+        self.bool(quirks.jump_uses_vx);
+        self.bool(quirks.display_wait);
+        self.bool(quirks.key_wait_release);
+    }
+
+    pub fn speed(&mut self, speed: Speed) {
+        let (tag, value) = match speed {
+            Speed::InstructionsPerSecond(ips) => (0, ips),
+            Speed::CyclesPerFrame(cycles) => (1, cycles),
+            Speed::CosmacVip => (2, 0),
+        };
+        self.u8(tag);
+        self.u64(value);
+    }
+}
+
+/// Decoder of the save states, failing with [`Chip8Error::CorruptSaveState`]
+/// when the data ends early or holds invalid values.
+#[derive(Debug)]
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Returns true if all the data was read.
+    pub const fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub const fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.data.len() {
+            return Err(Chip8Error::CorruptSaveState);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let bytes = self.bytes(N)?;
+        Ok(bytes.try_into().expect("slice has the array length"))
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        let [value] = self.array()?;
+        Ok(value)
+    }
+
+    pub fn bool(&mut self) -> Result<bool> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Chip8Error::CorruptSaveState),
+        }
+    }
+
+    /// Reads a byte written as [`NONE`] when absent.
+    pub fn option(&mut self) -> Result<Option<u8>> {
+        let value = self.u8()?;
+        Ok((value != NONE).then_some(value))
+    }
+
+    pub fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    /// Reads a size or an address.
+    pub fn len(&mut self) -> Result<usize> {
+        usize::try_from(self.u64()?).map_err(|_| Chip8Error::CorruptSaveState)
+    }
+
+    pub fn platform(&mut self) -> Result<Platform> {
+        match self.u8()? {
+            0 => Ok(Platform::Chip8),
+            1 => Ok(Platform::SuperChip),
+            2 => Ok(Platform::XoChip),
+            _ => Err(Chip8Error::CorruptSaveState),
+        }
+    }
+
+    pub fn quirks(&mut self) -> Result<Quirks> {
+        Ok(Quirks {
+            shift_uses_vy: self.bool()?,
+            memory_increment: match self.u8()? {
+                0 => MemoryIncrement::None,
+                1 => MemoryIncrement::X,
+                2 => MemoryIncrement::XPlusOne,
+                _ => return Err(Chip8Error::CorruptSaveState),
+            },
+            logic_resets_vf: self.bool()?,
+            clip_sprites: self.bool()?,
+            jump_uses_vx: self.bool()?,
+            display_wait: self.bool()?,
+            key_wait_release: self.bool()?,
+        })
+    }
+
+    pub fn speed(&mut self) -> Result<Speed> {
+        match (self.u8()?, self.u64()?) {
+            (0, ips) => Ok(Speed::InstructionsPerSecond(ips)),
+            (1, cycles) => Ok(Speed::CyclesPerFrame(cycles)),
+            (2, _) => Ok(Speed::CosmacVip),
+            _ => Err(Chip8Error::CorruptSaveState),
+        }
+    }
+}
@@ -0,0 +1,218 @@
+//! Instruction traces in a stable text format, and their comparison.
+//!
+//! [`Chip8::start_trace`](crate::Chip8::start_trace) writes a
+//! [`TraceEntry`] line before every executed instruction, with the state of
+//! the machine the instruction starts from:
+//!
+//! ```text
+//! CY=0000000042 PC=0206 OP=D015 V0=00 V1=00 V2=00 V3=12 V4=00 V5=00 V6=00 V7=00 V8=00 V9=00 VA=00 VB=00 VC=00 VD=00 VE=00 VF=00 I=022A SP=00 DT=00 ST=00 ; DRW V0, V1, 5
+//! ```
+//!
+//! The fields are the cycles since the start, the program counter, the
+//! opcode bytes, the registers, the index register, the depth of the call
+//! stack and the timers, all in hexadecimal but the cycles. The mnemonic
+//! after `;` is a comment, so that traces written by other emulators in the
+//! same format compare equal. [`first_divergence`] finds the first line
+//! where two traces differ:
+//!
+//! ```
+//! use rusty_chip8::trace;
+//!
+//! let left = "CY=0 PC=0200 V0=00\nCY=1 PC=0202 V0=12 ; LD V0, 0x12\n";
+//! let right = "CY=0 PC=0200 V0=00\nCY=1 PC=0202 V0=13\n";
+//! let divergence = trace::first_divergence(left.as_bytes(), right.as_bytes(), &[])?
+//!     .expect("the traces differ");
+//! assert_eq!(divergence.line, 2);
+//! assert_eq!(divergence.fields, ["V0"]);
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::{
+    fmt,
+    io::{self, BufRead, Write},
+};
+
+use crate::Instruction;
+
+/// State of the machine before the execution of an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// Cycles since the start: instructions, or machine cycles with
+    /// [`Speed::CosmacVip`](crate::Speed::CosmacVip).
+    pub cycles: u64,
+    /// Address of the instruction.
+    pub pc: usize,
+    /// The instruction about to be executed.
+    pub instruction: Instruction,
+    /// The V0 to VF registers.
+    pub registers: [u8; 16],
+    /// The index register.
+    pub i: usize,
+    /// Number of return addresses on the call stack.
+    pub stack_depth: usize,
+    /// The delay timer.
+    pub delay_timer: u8,
+    /// The sound timer.
+    pub sound_timer: u8,
+}
+
+impl fmt::Display for TraceEntry {
+    /// Writes the entry as a line of a trace, without the line break.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CY={:010} PC={:04X} OP=", self.cycles, self.pc)?;
+        for byte in self.instruction.to_bytes() {
+            write!(f, "{byte:02X}")?;
+        }
+        for (x, value) in self.registers.iter().enumerate() {
+            write!(f, " V{x:X}={value:02X}")?;
+        }
+        write!(
+            f,
+            " I={:04X} SP={:02X} DT={:02X} ST={:02X} ; {}",
+            self.i, self.stack_depth, self.delay_timer, self.sound_timer, self.instruction
+        )
+    }
+}
+
+/// Writer of the trace lines, keeping the first error.
+pub(crate) struct Tracer {
+    writer: Box<dyn Write + Send + Sync>,
+    error: Option<io::Error>,
+}
+
+impl Tracer {
+    /// Returns a tracer writing to `writer`.
+    pub(crate) fn new(writer: impl Write + Send + Sync + 'static) -> Self {
+        Self {
+            writer: Box::new(writer),
+            error: None,
+        }
+    }
+
+    /// Writes the line of `entry`, unless a previous write failed.
+    pub(crate) fn write(&mut self, entry: &TraceEntry) {
+        if self.error.is_none() {
+            self.error = writeln!(self.writer, "{entry}").err();
+        }
+    }
+
+    /// Flushes the writer, returning the first error of the trace.
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.error.map_or_else(|| self.writer.flush(), Err)
+    }
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tracer")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+/// First line where two traces differ, found by [`first_divergence`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Number of the line, starting at 1.
+    pub line: usize,
+    /// The line of the left trace, or `None` if it ended before.
+    pub left: Option<String>,
+    /// The line of the right trace, or `None` if it ended before.
+    pub right: Option<String>,
+    /// Keys of the fields that differ, e.g. `PC` or `V3`, or none if a
+    /// trace ended before the other.
+    pub fields: Vec<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "first divergence at line {}", self.line)?;
+        if self.fields.is_empty() {
+            writeln!(f)?;
+        } else {
+            writeln!(f, ": {}", self.fields.join(", "))?;
+        }
+        let end = "end of trace".to_string();
+        writeln!(f, "< {}", self.left.as_ref().unwrap_or(&end))?;
+        write!(f, "> {}", self.right.as_ref().unwrap_or(&end))
+    }
+}
+
+/// Returns the first line where the traces `left` and `right` differ, or
+/// `None` if they are the same.
+///
+/// The lines are compared field by field, ignoring the comments after `;`,
+/// the amount of white space and the fields whose keys are in `ignored`,
+/// e.g. `CY` for emulators counting cycles differently.
+///
+/// # Errors
+///
+/// Returns the errors of reading the traces.
+pub fn first_divergence(
+    left: impl BufRead,
+    right: impl BufRead,
+    ignored: &[&str],
+) -> io::Result<Option<Divergence>> {
+    let mut left = left.lines();
+    let mut right = right.lines();
+    let mut line = 0;
+    loop {
+        line += 1;
+        match (left.next().transpose()?, right.next().transpose()?) {
+            (None, None) => return Ok(None),
+            (Some(left), Some(right)) => {
+                let fields = differing_fields(&fields(&left, ignored), &fields(&right, ignored));
+                if !fields.is_empty() {
+                    return Ok(Some(Divergence {
+                        line,
+                        left: Some(left),
+                        right: Some(right),
+                        fields,
+                    }));
+                }
+            }
+            (left, right) => {
+                return Ok(Some(Divergence {
+                    line,
+                    left,
+                    right,
+                    fields: Vec::new(),
+                }))
+            }
+        }
+    }
+}
+
+/// Returns the fields of a trace line as keys and values, without the
+/// comment and the `ignored` keys. Fields without a key have an empty one.
+fn fields<'a>(line: &'a str, ignored: &[&str]) -> Vec<(&'a str, &'a str)> {
+    let line = line.split(';').next().unwrap_or_default();
+    line.split_whitespace()
+        .map(|field| field.split_once('=').unwrap_or(("", field)))
+        .filter(|(key, _)| !ignored.contains(key))
+        .collect()
+}
+
+/// Returns the keys of the fields that differ between two lines, in the
+/// order they first appear. The fields are matched by key, so that a field
+/// missing from a line or written in another order only reports itself.
+fn differing_fields(left: &[(&str, &str)], right: &[(&str, &str)]) -> Vec<String> {
+    let mut keys: Vec<&str> = Vec::new();
+    for (key, _) in left.iter().chain(right) {
+        if !keys.contains(key) {
+            keys.push(key);
+        }
+    }
+    keys.into_iter()
+        .filter(|key| values(left, key).ne(values(right, key)))
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// Returns the values of the fields with the key `key`, in order.
+fn values<'a>(fields: &'a [(&str, &'a str)], key: &'a str) -> impl Iterator<Item = &'a str> {
+    fields
+        .iter()
+        .filter(move |(field, _)| *field == key)
+        .map(|(_, value)| *value)
+}
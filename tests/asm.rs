@@ -0,0 +1,182 @@
+//! Assembly of the disassembler listings and of invalid sources.
+
+use std::{env, fs, path::Path, process};
+
+use rusty_chip8::{
+    asm::{self, AsmErrorKind},
+    disasm, Platform,
+};
+
+/// Returns the kind and the line of the error of assembling `source`.
+fn error(source: &str, platform: Platform) -> (AsmErrorKind, usize) {
+    let error = asm::assemble(source, platform).expect_err("the source is invalid");
+    (error.kind().clone(), error.line())
+}
+
+#[test]
+fn listings_of_the_bundled_roms_assemble_back() {
+    let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/roms");
+    let mut count = 0;
+    for entry in fs::read_dir(roms).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|extension| extension != "ch8") {
+            continue;
+        }
+        let rom = fs::read(&path).unwrap();
+        let listing = format!("{:#}", disasm::disassemble(&rom, Platform::Chip8));
+        let assembled = asm::assemble(&listing, Platform::Chip8)
+            .unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+        assert_eq!(assembled, rom, "{}", path.display());
+        count += 1;
+    }
+    assert!(count > 20, "only {count} roms found");
+}
+
+#[test]
+fn labels_constants_and_data() {
+    let source = "\
+define SPEED 2
+start:
+    LD I, sprite
+    LD V0, SPEED + 1
+    JP start
+sprite:
+    DB 0b11000011, 0x3C
+    DW 0x1234
+";
+    assert_eq!(
+        asm::assemble(source, Platform::Chip8).unwrap(),
+        [0xA2, 0x06, 0x60, 0x03, 0x12, 0x00, 0xC3, 0x3C, 0x12, 0x34]
+    );
+}
+
+#[test]
+fn unknown_mnemonic() {
+    assert_eq!(
+        error("    CLS\n    FOO V0\n", Platform::Chip8),
+        (
+            AsmErrorKind::UnknownMnemonic {
+                mnemonic: "FOO".to_string()
+            },
+            2
+        )
+    );
+}
+
+#[test]
+fn invalid_operands() {
+    assert_eq!(
+        error("    LD DT, I\n", Platform::Chip8),
+        (
+            AsmErrorKind::InvalidOperands {
+                mnemonic: "LD".to_string()
+            },
+            1
+        )
+    );
+}
+
+#[test]
+fn invalid_expression() {
+    assert_eq!(
+        error("    LD V0, 1 +\n", Platform::Chip8),
+        (AsmErrorKind::InvalidExpression, 1)
+    );
+}
+
+#[test]
+fn invalid_symbol() {
+    assert_eq!(
+        error("V1:\n    CLS\n", Platform::Chip8),
+        (
+            AsmErrorKind::InvalidSymbol {
+                name: "V1".to_string()
+            },
+            1
+        )
+    );
+}
+
+#[test]
+fn undefined_symbol() {
+    assert_eq!(
+        error("    JP nowhere\n", Platform::Chip8),
+        (
+            AsmErrorKind::UndefinedSymbol {
+                name: "nowhere".to_string()
+            },
+            1
+        )
+    );
+}
+
+#[test]
+fn duplicate_label() {
+    assert_eq!(
+        error("start:\n    CLS\nstart:\n    RET\n", Platform::Chip8),
+        (
+            AsmErrorKind::DuplicateSymbol {
+                name: "start".to_string()
+            },
+            3
+        )
+    );
+}
+
+#[test]
+fn out_of_range_operand() {
+    assert_eq!(
+        error("    LD V0, 0x100\n", Platform::Chip8),
+        (
+            AsmErrorKind::OutOfRange {
+                value: 0x100,
+                min: -0x80,
+                max: 0xFF
+            },
+            1
+        )
+    );
+}
+
+#[test]
+fn unsupported_instruction() {
+    assert_eq!(
+        error("    SCD 4\n", Platform::Chip8),
+        (
+            AsmErrorKind::UnsupportedInstruction {
+                mnemonic: "SCD".to_string(),
+                platform: Platform::Chip8
+            },
+            1
+        )
+    );
+}
+
+#[test]
+fn rom_too_large() {
+    let source = "    DB 0\n".repeat(0x1000 - 0x200 + 1);
+    let (kind, _) = error(&source, Platform::Chip8);
+    assert_eq!(kind, AsmErrorKind::RomTooLarge { capacity: 0xE00 });
+}
+
+#[test]
+fn unreadable_and_cyclic_includes() {
+    let dir = env::temp_dir().join(format!("rusty-chip8-asm-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.asm"), "include \"b.asm\"\n").unwrap();
+    fs::write(dir.join("b.asm"), "include \"a.asm\"\n").unwrap();
+    fs::write(dir.join("main.asm"), "include \"missing.asm\"\n").unwrap();
+
+    let cycle = asm::assemble_file(dir.join("a.asm"), Platform::Chip8).unwrap_err();
+    let missing = asm::assemble_file(dir.join("main.asm"), Platform::Chip8).unwrap_err();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(
+        matches!(cycle.kind(), AsmErrorKind::IncludeCycle { path } if path.ends_with("a.asm")),
+        "{cycle}"
+    );
+    assert!(
+        matches!(missing.kind(), AsmErrorKind::ReadFailed { path, .. } if path.ends_with("missing.asm")),
+        "{missing}"
+    );
+}
@@ -0,0 +1,74 @@
+//! Runs the bundled test ROMs with scripted input and compares their
+//! display with the golden images of `tests/golden`.
+//!
+//! To add a ROM, such as a test suite vendored in `resources/roms`, add a
+//! test building its [`Case`] and run it once with `UPDATE_GOLDEN=1` to write
+//! its golden image, after checking the display is the expected one.
+
+mod harness;
+
+use harness::Case;
+use rusty_chip8::{Platform, Quirks, Speed};
+
+#[test]
+fn ibm_logo() {
+    Case::new("resources/roms/IBM Logo.ch8").assert_golden("ibm_logo");
+}
+
+#[test]
+fn ibm_logo_cosmac_vip() {
+    Case::new("resources/roms/IBM Logo.ch8")
+        .quirks(Quirks::cosmac_vip())
+        .speed(Speed::CosmacVip)
+        .assert_golden("ibm_logo");
+}
+
+/// Decrements the delay from 0 twice with `8`, starts the timer with `5`,
+/// and shows it a second later, in a frame where it is fully redrawn.
+#[test]
+fn delay_timer() {
+    Case::new("resources/roms/Delay Timer Test [Matthew Mikolay, 2010].ch8")
+        .tap(5, 0x8)
+        .tap(15, 0x8)
+        .tap(25, 0x5)
+        .frames(87)
+        .assert_golden("delay_timer");
+}
+
+/// Draws a new random number on every key.
+#[test]
+fn random_number() {
+    Case::new("resources/roms/Random Number Test [Matthew Mikolay, 2010].ch8")
+        .seed(42)
+        .tap(5, 0x1)
+        .tap(15, 0x2)
+        .assert_golden("random_number");
+}
+
+/// Highlights the key of `5` and removes it, then highlights the key of `A`.
+#[test]
+fn keypad() {
+    Case::new("resources/roms/Keypad Test [Hap, 2006].ch8")
+        .tap(20, 0x5)
+        .tap(40, 0xA)
+        .frames(45)
+        .assert_golden("keypad");
+}
+
+/// Keeps the pixels when switching between the resolutions on SUPER-CHIP.
+#[test]
+fn resolution_switch_super_chip() {
+    Case::new("tests/roms/resolution_switch.8o")
+        .platform(Platform::SuperChip)
+        .quirks(Quirks::super_chip())
+        .assert_golden("resolution_switch_super_chip");
+}
+
+/// Clears the display when switching between the resolutions on XO-CHIP.
+#[test]
+fn resolution_switch_xo_chip() {
+    Case::new("tests/roms/resolution_switch.8o")
+        .platform(Platform::XoChip)
+        .quirks(Quirks::octo())
+        .assert_golden("resolution_switch_xo_chip");
+}
@@ -0,0 +1,157 @@
+//! Frame limit and watchpoints of the debugger runs.
+
+// Only the headless stub of the harness is used.
+#[allow(dead_code)]
+mod harness;
+
+use harness::Headless;
+use rusty_chip8::{
+    debugger::{Access, Debugger, Location, Stop, Watchpoint},
+    Chip8, Quirks,
+};
+
+/// Returns a debugger on a program calling a subroutine which never returns.
+fn endless_subroutine() -> Debugger {
+    // CALL 0x204; RET; JP 0x204
+    let rom = [0x22, 0x04, 0x00, 0xEE, 0x12, 0x04];
+    let mut chip8 = Chip8::new(700);
+    chip8.store_in_ram(rom).unwrap();
+    Debugger::new(chip8)
+}
+
+#[test]
+fn step_over_stops_at_the_default_limit() {
+    let mut debugger = endless_subroutine();
+    let limit = debugger.frame_limit().expect("runs are limited by default");
+    assert_eq!(
+        debugger.step_over(&mut Headless, &mut Headless),
+        Ok(Stop::Limit)
+    );
+    assert_eq!(debugger.chip8().frame_count(), limit);
+    assert_eq!(debugger.chip8().pc(), 0x204);
+}
+
+#[test]
+fn step_out_stops_at_the_limit() {
+    let mut debugger = endless_subroutine();
+    debugger.set_frame_limit(Some(5));
+    assert_eq!(debugger.step(&mut Headless, &mut Headless), Ok(Stop::Step));
+    assert_eq!(
+        debugger.step_out(&mut Headless, &mut Headless),
+        Ok(Stop::Limit)
+    );
+    assert_eq!(debugger.chip8().frame_count(), 5);
+    assert_eq!(
+        debugger.step_out(&mut Headless, &mut Headless),
+        Ok(Stop::Limit)
+    );
+    assert_eq!(debugger.chip8().frame_count(), 10);
+}
+
+#[test]
+fn run_to_frame_stops_at_the_limit_unless_disabled() {
+    let mut debugger = endless_subroutine();
+    debugger.set_frame_limit(Some(10));
+    assert_eq!(
+        debugger.run_to_frame(10, &mut Headless, &mut Headless),
+        Ok(Stop::Frame)
+    );
+    assert_eq!(
+        debugger.run_to_frame(100, &mut Headless, &mut Headless),
+        Ok(Stop::Limit)
+    );
+    assert_eq!(debugger.chip8().frame_count(), 20);
+
+    debugger.set_frame_limit(None);
+    assert_eq!(
+        debugger.run_to_frame(100, &mut Headless, &mut Headless),
+        Ok(Stop::Frame)
+    );
+    assert_eq!(debugger.chip8().frame_count(), 100);
+}
+
+/// Returns a debugger on `rom` watching `access` to `location`.
+fn watching(rom: &[u8], quirks: Quirks, location: Location, access: Access) -> Debugger {
+    let mut chip8 = Chip8::with_quirks(700, quirks);
+    chip8.store_in_ram(rom).unwrap();
+    let mut debugger = Debugger::new(chip8);
+    debugger.add_watchpoint(Watchpoint { location, access });
+    debugger
+}
+
+#[test]
+fn watchpoints_follow_the_quirks() {
+    // SHR V0, V1
+    let rom = [0x80, 0x16];
+    let mut debugger = watching(
+        &rom,
+        Quirks::cosmac_vip(),
+        Location::Register(1),
+        Access::Read,
+    );
+    assert_eq!(
+        debugger.step(&mut Headless, &mut Headless),
+        Ok(Stop::Watchpoint {
+            id: 1,
+            access: Access::Read
+        })
+    );
+
+    let mut debugger = watching(
+        &rom,
+        Quirks::super_chip(),
+        Location::Register(1),
+        Access::Read,
+    );
+    assert_eq!(debugger.step(&mut Headless, &mut Headless), Ok(Stop::Step));
+}
+
+#[test]
+fn memory_watchpoints_stop_after_the_access() {
+    // LD I, 0x300; LD [I], V1; LD V1, [I]
+    let rom = [0xA3, 0x00, 0xF1, 0x55, 0xF1, 0x65];
+    let location = Location::Memory {
+        address: 0x301,
+        len: 1,
+    };
+    let mut debugger = watching(&rom, Quirks::super_chip(), location, Access::ReadWrite);
+    assert_eq!(debugger.step(&mut Headless, &mut Headless), Ok(Stop::Step));
+    assert_eq!(
+        debugger.step(&mut Headless, &mut Headless),
+        Ok(Stop::Watchpoint {
+            id: 1,
+            access: Access::Write
+        })
+    );
+    assert_eq!(
+        debugger.step(&mut Headless, &mut Headless),
+        Ok(Stop::Watchpoint {
+            id: 1,
+            access: Access::Read
+        })
+    );
+}
+
+#[test]
+fn key_stored_by_fx0a_is_a_write() {
+    // LD V3, K; JP 0x202
+    let rom = [0xF3, 0x0A, 0x12, 0x02];
+    let mut debugger = watching(
+        &rom,
+        Quirks::default(),
+        Location::Register(3),
+        Access::Write,
+    );
+    assert_eq!(debugger.step(&mut Headless, &mut Headless), Ok(Stop::Step));
+    let chip8 = debugger.chip8_mut();
+    chip8.handle_key_pressed(7).unwrap();
+    chip8.handle_key_released(7).unwrap();
+    assert_eq!(
+        debugger.step(&mut Headless, &mut Headless),
+        Ok(Stop::Watchpoint {
+            id: 1,
+            access: Access::Write
+        })
+    );
+    assert_eq!(debugger.chip8().registers()[3], 7);
+}
@@ -0,0 +1,97 @@
+//! Errors returned instead of panicking on faulty programs.
+
+// Only the headless stub of the harness is used.
+#[allow(dead_code)]
+mod harness;
+
+use harness::Headless;
+use rusty_chip8::{Chip8, Chip8Error, PROGRAM_START, STACK_SIZE};
+
+/// Runs `rom` until it fails, and returns the machine and the error.
+fn fail(rom: &[u8]) -> (Chip8, Chip8Error) {
+    let mut chip8 = Chip8::new(700);
+    chip8.store_in_ram(rom).unwrap();
+    for _ in 0..100 {
+        if let Err(error) = chip8.step(&mut Headless, &mut Headless) {
+            return (chip8, error);
+        }
+    }
+    panic!("no error in 100 instructions");
+}
+
+#[test]
+fn unknown_opcodes_are_errors() {
+    // LD V0, 1; then 0xFFFF
+    let (chip8, error) = fail(&[0x60, 0x01, 0xFF, 0xFF]);
+    assert_eq!(
+        error,
+        Chip8Error::UnknownOpcode {
+            pc: 0x202,
+            opcode: 0xFFFF
+        }
+    );
+    assert_eq!(chip8.pc(), 0x202);
+    assert_eq!(error.to_string(), "unknown instruction FFFF at 0x202");
+}
+
+#[test]
+fn returning_from_the_main_program_underflows() {
+    // RET
+    let (_, error) = fail(&[0x00, 0xEE]);
+    assert_eq!(error, Chip8Error::StackUnderflow { pc: 0x200 });
+}
+
+#[test]
+fn endless_recursion_overflows() {
+    // CALL 0x200
+    let (chip8, error) = fail(&[0x22, 0x00]);
+    assert_eq!(error, Chip8Error::StackOverflow { pc: 0x200 });
+    assert_eq!(chip8.stack().len(), STACK_SIZE);
+}
+
+#[test]
+fn memory_accesses_past_the_ram_are_errors() {
+    // LD I, 0xFFF; LD [I], V1
+    let (chip8, error) = fail(&[0xAF, 0xFF, 0xF1, 0x55]);
+    assert_eq!(
+        error,
+        Chip8Error::AddressOutOfRange {
+            pc: 0x202,
+            opcode: 0xF155,
+            address: 0x1000
+        }
+    );
+    assert_eq!(chip8.ram()[0xFFF], 0);
+}
+
+#[test]
+fn fetching_past_the_ram_is_an_error() {
+    // JP 0xFFF
+    let (_, error) = fail(&[0x1F, 0xFF]);
+    assert_eq!(
+        error,
+        Chip8Error::AddressOutOfRange {
+            pc: 0xFFF,
+            opcode: 0,
+            address: 0x1000
+        }
+    );
+}
+
+#[test]
+fn loading_rejects_invalid_input() {
+    let mut chip8 = Chip8::new(700);
+    let capacity = chip8.ram().len() - PROGRAM_START;
+    assert_eq!(
+        chip8.store_in_ram(vec![0; capacity + 1]),
+        Err(Chip8Error::RomTooLarge {
+            size: capacity + 1,
+            capacity
+        })
+    );
+    assert_eq!(chip8.store_in_ram(vec![0; capacity]), Ok(()));
+    assert_eq!(
+        chip8.handle_key_pressed(16),
+        Err(Chip8Error::InvalidKey { key: 16 })
+    );
+}
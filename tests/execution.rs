@@ -0,0 +1,71 @@
+//! Running instructions, cycles and frames without blocking.
+
+// Only the headless stub of the harness is used.
+#[allow(dead_code)]
+mod harness;
+
+use std::time::{Duration, Instant};
+
+use harness::Headless;
+use rusty_chip8::Chip8;
+
+/// Returns a profiled machine counting its loops in V0, at 700 instructions
+/// per second.
+fn counter() -> Chip8 {
+    // ADD V0, 1; JP 0x200
+    let mut chip8 = Chip8::new(700);
+    chip8.store_in_ram([0x70, 0x01, 0x12, 0x00]).unwrap();
+    chip8.set_profiling(true);
+    chip8
+}
+
+#[test]
+fn step_executes_one_instruction() {
+    let mut chip8 = counter();
+    chip8.step(&mut Headless, &mut Headless).unwrap();
+    assert_eq!(chip8.registers()[0], 1);
+    assert_eq!(chip8.pc(), 0x202);
+    chip8.step(&mut Headless, &mut Headless).unwrap();
+    assert_eq!(chip8.pc(), 0x200);
+    assert_eq!(chip8.frame_count(), 0);
+}
+
+#[test]
+fn run_cycles_crosses_frames() {
+    let mut chip8 = counter();
+    chip8.set_delay_timer(10);
+    // The first frames run 11 and 12 instructions.
+    chip8.run_cycles(25, &mut Headless, &mut Headless).unwrap();
+    assert_eq!(chip8.profile().unwrap().instruction_count(), 25);
+    assert_eq!(chip8.registers()[0], 13);
+    assert_eq!(chip8.frame_count(), 2);
+    assert_eq!(chip8.delay_timer(), 8);
+}
+
+#[test]
+fn run_for_runs_whole_frames() {
+    let mut chip8 = counter();
+    chip8.set_delay_timer(100);
+    chip8
+        .run_for(Duration::from_millis(10), &mut Headless, &mut Headless)
+        .unwrap();
+    assert_eq!(chip8.frame_count(), 0);
+
+    chip8
+        .run_for(Duration::from_secs(1), &mut Headless, &mut Headless)
+        .unwrap();
+    assert_eq!(chip8.frame_count(), 60);
+    assert_eq!(chip8.delay_timer(), 40);
+    assert_eq!(chip8.profile().unwrap().instruction_count(), 700);
+}
+
+#[test]
+fn emulated_time_does_not_block() {
+    let mut chip8 = counter();
+    let start = Instant::now();
+    chip8
+        .run_for(Duration::from_mins(1), &mut Headless, &mut Headless)
+        .unwrap();
+    assert_eq!(chip8.frame_count(), 3600);
+    assert!(start.elapsed() < Duration::from_mins(1));
+}
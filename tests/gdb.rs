@@ -0,0 +1,93 @@
+//! Session of a scripted client with the GDB stub over a local TCP socket.
+
+#![cfg(feature = "gdb")]
+
+// Only the headless stub of the harness is used.
+#[allow(dead_code)]
+mod harness;
+
+use std::{
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+use harness::Headless;
+use rusty_chip8::{debugger::Debugger, gdb, Chip8};
+
+/// Sends a packet and returns the reply, acknowledging both.
+fn request(stream: &mut TcpStream, data: &str) -> io::Result<String> {
+    let checksum = data.bytes().fold(0u8, u8::wrapping_add);
+    write!(stream, "${data}#{checksum:02x}")?;
+    let mut reply = Vec::new();
+    let mut byte = [0];
+    while !reply.ends_with(b"#") {
+        stream.read_exact(&mut byte)?;
+        match byte[0] {
+            b'+' if reply.is_empty() => {}
+            b'-' if reply.is_empty() => panic!("the stub rejected {data:?}"),
+            byte => reply.push(byte),
+        }
+    }
+    stream.read_exact(&mut [0; 2])?;
+    stream.write_all(b"+")?;
+    Ok(String::from_utf8_lossy(&reply[1..reply.len() - 1]).into_owned())
+}
+
+/// Serves the packets of `requests` on a fresh machine and returns the
+/// replies.
+fn session(requests: &[&str]) -> Vec<String> {
+    // LD V0, 5; CALL 0x208; JP 0x204; ADD V1, 1; RET
+    let rom = [
+        0x60, 0x05, 0x22, 0x08, 0x12, 0x04, 0x00, 0x00, 0x71, 0x01, 0x00, 0xEE,
+    ];
+    let mut chip8 = Chip8::new(700);
+    chip8.store_in_ram(rom).unwrap();
+    let mut debugger = Debugger::new(chip8);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let requests: Vec<String> = requests.iter().map(ToString::to_string).collect();
+    let client = thread::spawn(move || -> io::Result<Vec<String>> {
+        let mut stream = TcpStream::connect(("127.0.0.1", port))?;
+        requests
+            .iter()
+            .map(|data| request(&mut stream, data))
+            .collect()
+    });
+    let (stream, _) = listener.accept().unwrap();
+    gdb::serve(&mut debugger, stream, &mut Headless, &mut Headless).unwrap();
+    client.join().unwrap().unwrap()
+}
+
+#[test]
+fn registers_memory_breakpoints_step_and_continue() {
+    let replies = session(&[
+        "g", "m200,4", "Z0,208,2", "s", "p0", "p11", "c", "p11", "g", "z0,208,2", "D",
+    ]);
+    let registers =
+        |v0: &str, pc: &str, sp: &str| format!("{v0}{}0000{pc}{sp}0000", "00".repeat(15));
+    assert_eq!(
+        replies,
+        [
+            registers("00", "0200", "00"),
+            "60052208".to_string(),
+            "OK".to_string(),
+            "S05".to_string(),
+            "05".to_string(),
+            "0202".to_string(),
+            "S05".to_string(),
+            "0208".to_string(),
+            registers("05", "0208", "01"),
+            "OK".to_string(),
+            "OK".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn oversized_packet_is_answered_with_an_error() {
+    let oversized = format!("M200,1:{}", "0".repeat(0x2000));
+    let replies = session(&[&oversized, "p11", "D"]);
+    assert_eq!(replies, ["E01", "0200", "OK"]);
+}
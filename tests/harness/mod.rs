@@ -0,0 +1,220 @@
+//! Headless runner of ROMs comparing the display with golden images.
+//!
+//! A golden image is a text file in `tests/golden`, with a line per row of
+//! the display and a character per pixel: `.` for the background, then `#`,
+//! `+` and `@` for the XO-CHIP colours 1 to 3. Running the tests with the
+//! `UPDATE_GOLDEN` environment variable set writes the images instead of
+//! comparing them, to add a case or accept a change of the display:
+//!
+//! ```text
+//! UPDATE_GOLDEN=1 cargo test --test conformance
+//! ```
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use rusty_chip8::{octo, Audio, Chip8, Graphics, Platform, Quirks, SeededRandom, Speed};
+
+/// Environment variable to set to write the golden images.
+const UPDATE_VARIABLE: &str = "UPDATE_GOLDEN";
+
+/// Characters of the pixels in the golden images, by colour.
+const PIXELS: [char; 4] = ['.', '#', '+', '@'];
+
+/// Graphics and audio discarding everything, the display being read from
+/// the framebuffer.
+pub struct Headless;
+
+impl Graphics for Headless {}
+
+impl Audio for Headless {
+    fn start_beep(&mut self) {}
+
+    fn stop_beep(&mut self) {}
+}
+
+/// Key event of the scripted input.
+#[derive(Debug, Clone, Copy)]
+enum Input {
+    Press(u8),
+    Release(u8),
+}
+
+/// ROM run for a number of frames with scripted input.
+#[derive(Debug, Clone)]
+pub struct Case {
+    rom: PathBuf,
+    platform: Platform,
+    quirks: Quirks,
+    clock: u64,
+    speed: Option<Speed>,
+    seed: u64,
+    frames: u64,
+    /// Key events, by the frame before which they happen.
+    inputs: Vec<(u64, Input)>,
+}
+
+impl Case {
+    /// Returns a case running the ROM at `rom`, relative to the root of the
+    /// crate, for a second on CHIP-8 at 700 instructions per second.
+    ///
+    /// A `.8o` file is compiled as Octo source for the platform of the case.
+    pub fn new(rom: impl AsRef<Path>) -> Self {
+        Self {
+            rom: rom.as_ref().to_path_buf(),
+            platform: Platform::Chip8,
+            quirks: Quirks::default(),
+            clock: 700,
+            speed: None,
+            seed: 0,
+            frames: 60,
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Sets the platform.
+    pub const fn platform(mut self, platform: Platform) -> Self {
+        self.platform = platform;
+        self
+    }
+
+    /// Sets the quirks.
+    pub const fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Sets the speed instead of 700 instructions per second.
+    pub const fn speed(mut self, speed: Speed) -> Self {
+        self.speed = Some(speed);
+        self
+    }
+
+    /// Sets the seed of the random numbers.
+    pub const fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets the number of frames to run.
+    pub const fn frames(mut self, frames: u64) -> Self {
+        self.frames = frames;
+        self
+    }
+
+    /// Presses `key` before running the frame `frame`, counting from 0.
+    pub fn press(mut self, frame: u64, key: u8) -> Self {
+        self.inputs.push((frame, Input::Press(key)));
+        self
+    }
+
+    /// Releases `key` before running the frame `frame`, counting from 0.
+    pub fn release(mut self, frame: u64, key: u8) -> Self {
+        self.inputs.push((frame, Input::Release(key)));
+        self
+    }
+
+    /// Presses `key` before the frame `frame` and releases it 5 frames later.
+    pub fn tap(self, frame: u64, key: u8) -> Self {
+        self.press(frame, key).release(frame + 5, key)
+    }
+
+    /// Runs the ROM until the last frame or its exit, and returns the machine.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the ROM cannot be loaded or crashes.
+    pub fn run(&self) -> Chip8 {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(&self.rom);
+        let rom = if path.extension().is_some_and(|extension| extension == "8o") {
+            let source = fs::read_to_string(&path)
+                .unwrap_or_else(|err| panic!("failed to read {}: {err}", path.display()));
+            octo::compile(&source, self.platform)
+                .unwrap_or_else(|err| panic!("failed to compile {}: {err}", path.display()))
+                .rom()
+                .to_vec()
+        } else {
+            fs::read(&path).unwrap_or_else(|err| panic!("failed to read {}: {err}", path.display()))
+        };
+        let mut chip8 = Chip8::with_platform(self.clock, self.platform, self.quirks);
+        if let Some(speed) = self.speed {
+            chip8.set_speed(speed);
+        }
+        chip8.set_random(SeededRandom::new(self.seed));
+        chip8
+            .store_in_ram(rom)
+            .unwrap_or_else(|err| panic!("failed to load {}: {err}", path.display()));
+        for frame in 0..self.frames {
+            for (_, input) in self.inputs.iter().filter(|(at, _)| *at == frame) {
+                match *input {
+                    Input::Press(key) => chip8.handle_key_pressed(key),
+                    Input::Release(key) => chip8.handle_key_released(key),
+                }
+                .unwrap_or_else(|err| panic!("invalid input in frame {frame}: {err}"));
+            }
+            chip8
+                .run_frame(&mut Headless, &mut Headless)
+                .unwrap_or_else(|err| panic!("{} crashed in frame {frame}: {err}", path.display()));
+            if chip8.has_exited() {
+                break;
+            }
+        }
+        chip8
+    }
+
+    /// Runs the ROM and compares the display with the golden image `name`,
+    /// or writes it if `UPDATE_GOLDEN` is set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the display differs from the golden image, or if the ROM
+    /// cannot be run.
+    pub fn assert_golden(&self, name: &str) {
+        let actual = render(&self.run());
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(name)
+            .with_extension("txt");
+        if env::var_os(UPDATE_VARIABLE).is_some() {
+            fs::write(&path, &actual)
+                .unwrap_or_else(|err| panic!("failed to write {}: {err}", path.display()));
+            return;
+        }
+        let expected = fs::read_to_string(&path).unwrap_or_else(|err| {
+            panic!(
+                "failed to read {}: {err}\nrun with {UPDATE_VARIABLE}=1 to create it",
+                path.display()
+            )
+        });
+        if actual != expected {
+            let differing = actual
+                .chars()
+                .zip(expected.chars())
+                .filter(|(actual, expected)| actual != expected)
+                .count();
+            panic!(
+                "the display differs from {} in {differing} pixels\n\
+                 expected:\n{expected}\nactual:\n{actual}",
+                path.display()
+            );
+        }
+    }
+}
+
+/// Returns the display of `chip8` in the format of the golden images.
+pub fn render(chip8: &Chip8) -> String {
+    chip8
+        .framebuffer()
+        .rows()
+        .map(|row| {
+            let mut line: String = row
+                .iter()
+                .map(|color| PIXELS[usize::from(*color & 3)])
+                .collect();
+            line.push('\n');
+            line
+        })
+        .collect()
+}
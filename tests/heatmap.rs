@@ -0,0 +1,105 @@
+//! Memory accesses counted by the heatmap, and its self-modifications.
+
+// Only the headless stub of the harness is used.
+#[allow(dead_code)]
+mod harness;
+
+use harness::Headless;
+use rusty_chip8::{Chip8, MemoryHeatmap, SelfModification};
+
+/// Runs `n` instructions of `rom` with the heatmap enabled.
+fn run(rom: &[u8], n: usize) -> Chip8 {
+    let mut chip8 = Chip8::new(700);
+    chip8.store_in_ram(rom).unwrap();
+    chip8.set_memory_heatmap(true);
+    for _ in 0..n {
+        chip8.step(&mut Headless, &mut Headless).unwrap();
+    }
+    chip8
+}
+
+/// Returns the heatmap of `chip8`.
+const fn heatmap(chip8: &Chip8) -> &MemoryHeatmap {
+    chip8.memory_heatmap().unwrap()
+}
+
+#[test]
+fn write_then_execute_is_a_self_modification() {
+    // LD V0, 0x12; LD V1, 0x08; LD I, 0x208; LD [I], V1; then 0x208 becomes
+    // JP 0x208
+    let rom = [0x60, 0x12, 0x61, 0x08, 0xA2, 0x08, 0xF1, 0x55, 0x00, 0xE0];
+    let chip8 = run(&rom, 5);
+    assert_eq!(chip8.pc(), 0x208);
+    let heatmap = heatmap(&chip8);
+    assert_eq!(heatmap.writes()[0x208..0x20A], [1, 1]);
+    assert_eq!(heatmap.executes()[0x208..0x20A], [1, 1]);
+    assert_eq!(
+        heatmap.self_modifications().collect::<Vec<_>>(),
+        [0x208, 0x209].map(|address| SelfModification {
+            writer: 0x206,
+            address,
+            frame: 0
+        })
+    );
+}
+
+#[test]
+fn execute_then_write_is_a_self_modification() {
+    // LD V0, 0x12; LD V1, 0x00; LD I, 0x200; LD [I], V1
+    let rom = [0x60, 0x12, 0x61, 0x00, 0xA2, 0x00, 0xF1, 0x55];
+    let chip8 = run(&rom, 4);
+    assert_eq!(
+        heatmap(&chip8).self_modifications().collect::<Vec<_>>(),
+        [0x200, 0x201].map(|address| SelfModification {
+            writer: 0x206,
+            address,
+            frame: 0
+        })
+    );
+}
+
+#[test]
+fn data_writes_are_not_self_modifications() {
+    // LD I, 0x300; LD [I], V1
+    let rom = [0xA3, 0x00, 0xF1, 0x55];
+    let chip8 = run(&rom, 2);
+    let heatmap = heatmap(&chip8);
+    assert_eq!(heatmap.writes()[0x300..0x302], [1, 1]);
+    assert_eq!(heatmap.self_modifications().count(), 0);
+}
+
+#[test]
+fn font_loads_and_sprites_are_reads() {
+    // LD V0, 0xA; LD F, V0; LD I, 0x300; LD V1, [I]; LD I, 0x310;
+    // DRW V0, V0, 3
+    let rom = [
+        0x60, 0x0A, 0xF0, 0x29, 0xA3, 0x00, 0xF1, 0x65, 0xA3, 0x10, 0xD0, 0x03,
+    ];
+    let mut chip8 = run(&rom, 2);
+    let font = chip8.index();
+    assert_eq!(heatmap(&chip8).reads()[font..font + 5], [1; 5]);
+
+    chip8.step(&mut Headless, &mut Headless).unwrap();
+    chip8.step(&mut Headless, &mut Headless).unwrap();
+    assert_eq!(heatmap(&chip8).reads()[0x2FF..0x303], [0, 1, 1, 0]);
+
+    chip8.step(&mut Headless, &mut Headless).unwrap();
+    chip8.step(&mut Headless, &mut Headless).unwrap();
+    let reads = heatmap(&chip8).reads();
+    assert_eq!(reads[0x30F..0x314], [0, 1, 1, 1, 0]);
+    assert_eq!(reads.iter().sum::<u64>(), 10);
+}
+
+#[test]
+fn ppm_has_a_pixel_per_byte() {
+    let chip8 = run(&[0x12, 0x00], 10);
+    let mut ppm = Vec::new();
+    heatmap(&chip8).write_ppm(&mut ppm).unwrap();
+    let header = b"P6\n64 64\n255\n";
+    assert_eq!(ppm[..header.len()], *header);
+    assert_eq!(ppm.len(), header.len() + 64 * 64 * 3);
+    // The jump is the only byte executed, at the largest count.
+    let pixel = |address: usize| &ppm[header.len() + address * 3..][..3];
+    assert_eq!(pixel(0x200), [0, 255, 0]);
+    assert_eq!(pixel(0x202), [0, 0, 0]);
+}
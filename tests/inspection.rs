@@ -0,0 +1,107 @@
+//! Reading and writing the state of the machine.
+
+// Only the headless stub of the harness is used.
+#[allow(dead_code)]
+mod harness;
+
+use harness::Headless;
+use rusty_chip8::{Chip8, Chip8Error, STACK_SIZE};
+
+#[test]
+fn addresses_stay_in_the_ram() {
+    let mut chip8 = Chip8::new(700);
+    let end = chip8.ram().len();
+    chip8.set_pc(0x300).unwrap();
+    chip8.set_index(end - 1).unwrap();
+    assert_eq!(
+        chip8.set_pc(end),
+        Err(Chip8Error::InvalidAddress { address: end })
+    );
+    assert_eq!(
+        chip8.set_index(end),
+        Err(Chip8Error::InvalidAddress { address: end })
+    );
+    assert_eq!(chip8.pc(), 0x300);
+    assert_eq!(chip8.index(), end - 1);
+}
+
+#[test]
+fn registers_and_timers_are_read_by_the_program() {
+    // ADD V2, 1; LD V0, DT
+    let mut chip8 = Chip8::new(700);
+    chip8.store_in_ram([0x72, 0x01, 0xF0, 0x07]).unwrap();
+    chip8.registers_mut()[2] = 41;
+    chip8.set_delay_timer(7);
+    chip8.set_sound_timer(9);
+    chip8.run_cycles(2, &mut Headless, &mut Headless).unwrap();
+    assert_eq!(chip8.registers()[..3], [7, 0, 42]);
+    assert_eq!(chip8.delay_timer(), 7);
+    assert_eq!(chip8.sound_timer(), 9);
+}
+
+#[test]
+fn stack_keeps_its_depth_and_addresses() {
+    let mut chip8 = Chip8::new(700);
+    let end = chip8.ram().len();
+    assert_eq!(
+        chip8.set_stack(&[0x200; STACK_SIZE + 1]),
+        Err(Chip8Error::StackTooDeep {
+            depth: STACK_SIZE + 1
+        })
+    );
+    assert_eq!(
+        chip8.set_stack(&[0x200, end]),
+        Err(Chip8Error::InvalidAddress { address: end })
+    );
+    assert!(chip8.stack().is_empty());
+
+    // RET
+    chip8.store_in_ram([0x00, 0xEE]).unwrap();
+    // The stack holds the addresses of the calls, returning after them.
+    chip8.set_stack(&[0x400, 0x300]).unwrap();
+    chip8.step(&mut Headless, &mut Headless).unwrap();
+    assert_eq!(chip8.pc(), 0x302);
+    assert_eq!(chip8.stack(), [0x400]);
+}
+
+#[test]
+fn memory_ranges_stay_in_the_ram() {
+    let mut chip8 = Chip8::new(700);
+    let end = chip8.ram().len();
+    chip8.write_memory(end - 2, &[1, 2]).unwrap();
+    assert_eq!(chip8.read_memory(end - 3, 3), Ok(&[0, 1, 2][..]));
+    assert_eq!(
+        chip8.write_memory(end - 1, &[1, 2]),
+        Err(Chip8Error::InvalidAddress { address: end + 1 })
+    );
+    assert_eq!(
+        chip8.read_memory(usize::MAX, 2),
+        Err(Chip8Error::InvalidAddress {
+            address: usize::MAX
+        })
+    );
+    assert_eq!(chip8.ram()[end - 1], 2);
+}
+
+#[test]
+fn pixels_stay_in_the_display() {
+    let mut chip8 = Chip8::new(700);
+    chip8.set_pixel(63, 31, 1).unwrap();
+    assert_eq!(chip8.framebuffer().pixel(63, 31), 1);
+    assert_eq!(
+        chip8.set_pixel(64, 0, 1),
+        Err(Chip8Error::InvalidPixel {
+            x: 64,
+            y: 0,
+            color: 1
+        })
+    );
+    assert_eq!(
+        chip8.set_pixel(0, 0, 4),
+        Err(Chip8Error::InvalidPixel {
+            x: 0,
+            y: 0,
+            color: 4
+        })
+    );
+}
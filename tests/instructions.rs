@@ -0,0 +1,76 @@
+//! Decoding and execution of the base instructions.
+
+// Only the headless stub of the harness is used.
+#[allow(dead_code)]
+mod harness;
+
+use harness::Headless;
+use rusty_chip8::{Chip8, Chip8Error, Instruction, MachineCodeCall, Platform, Quirks};
+
+/// Runs `n` instructions of `rom` with `quirks`.
+fn run(rom: &[u8], quirks: Quirks, n: u64) -> Chip8 {
+    let mut chip8 = Chip8::with_quirks(700, quirks);
+    chip8.store_in_ram(rom).unwrap();
+    chip8.run_cycles(n, &mut Headless, &mut Headless).unwrap();
+    chip8
+}
+
+#[test]
+fn addresses_decode_big_endian() {
+    let decode = |bytes: [u8; 2]| Instruction::decode(&bytes, Platform::Chip8);
+    assert_eq!(decode([0x81, 0x21]), Some(Instruction::Or8XY1(1, 2)));
+    assert_eq!(
+        decode([0xB3, 0x45]),
+        Some(Instruction::JumpWithOffsetBNNN(0x345))
+    );
+    assert_eq!(
+        decode([0x01, 0x23]),
+        Some(Instruction::MachineCodeCall0NNN(0x123))
+    );
+    assert_eq!(
+        Instruction::MachineCodeCall0NNN(0x123).to_bytes(),
+        [0x01, 0x23]
+    );
+}
+
+#[test]
+fn or_combines_the_registers() {
+    // LD V1, 0x0C; LD V2, 0x0A; OR V1, V2
+    let chip8 = run(
+        &[0x61, 0x0C, 0x62, 0x0A, 0x81, 0x21],
+        Quirks::super_chip(),
+        3,
+    );
+    assert_eq!(chip8.registers()[1], 0x0E);
+    assert_eq!(chip8.registers()[2], 0x0A);
+}
+
+#[test]
+fn jump_with_offset_adds_v0() {
+    // LD V0, 4; JP V0, 0x300
+    let chip8 = run(&[0x60, 0x04, 0xB3, 0x00], Quirks::cosmac_vip(), 2);
+    assert_eq!(chip8.pc(), 0x304);
+}
+
+#[test]
+fn machine_code_calls_are_skipped_by_default() {
+    // SYS 0x123
+    let chip8 = run(&[0x01, 0x23], Quirks::super_chip(), 1);
+    assert_eq!(chip8.pc(), 0x202);
+}
+
+#[test]
+fn machine_code_calls_can_fail() {
+    // SYS 0x123
+    let mut chip8 = Chip8::new(700);
+    chip8.store_in_ram([0x01, 0x23]).unwrap();
+    chip8.set_machine_code_call(MachineCodeCall::Error);
+    assert_eq!(
+        chip8.step(&mut Headless, &mut Headless),
+        Err(Chip8Error::MachineCodeCall {
+            pc: 0x200,
+            opcode: 0x123
+        })
+    );
+    assert_eq!(chip8.pc(), 0x200);
+}
@@ -0,0 +1,121 @@
+//! State of the 16-key keypad and the instructions reading it.
+
+// Only the headless stub of the harness is used.
+#[allow(dead_code)]
+mod harness;
+
+use harness::Headless;
+use rusty_chip8::{Chip8, Chip8Error, Quirks};
+
+/// Runs `rom` from the start for `n` instructions and returns the program counter.
+fn pc_after(chip8: &mut Chip8, rom: &[u8], n: u64) -> usize {
+    chip8.store_in_ram(rom).unwrap();
+    chip8.set_pc(0x200).unwrap();
+    chip8.run_cycles(n, &mut Headless, &mut Headless).unwrap();
+    chip8.pc()
+}
+
+#[test]
+fn keys_are_held_down_together() {
+    let mut chip8 = Chip8::new(700);
+    chip8.handle_key_pressed(1).unwrap();
+    chip8.handle_key_pressed(0xF).unwrap();
+    assert_eq!(chip8.keypad(), 0b1000_0000_0000_0010);
+
+    chip8.handle_key_released(1).unwrap();
+    assert_eq!(chip8.keypad(), 0b1000_0000_0000_0000);
+    assert!(!chip8.is_key_pressed(1));
+    assert!(chip8.is_key_pressed(0xF));
+    // Releasing a key that is not held down changes nothing.
+    chip8.handle_key_released(2).unwrap();
+    assert_eq!(chip8.keypad(), 0b1000_0000_0000_0000);
+}
+
+#[test]
+fn keys_out_of_range_are_rejected() {
+    let mut chip8 = Chip8::new(700);
+    assert_eq!(
+        chip8.handle_key_pressed(16),
+        Err(Chip8Error::InvalidKey { key: 16 })
+    );
+    assert_eq!(
+        chip8.handle_key_released(0xFF),
+        Err(Chip8Error::InvalidKey { key: 0xFF })
+    );
+    assert_eq!(chip8.keypad(), 0);
+    assert!(!chip8.is_key_pressed(16));
+}
+
+#[test]
+fn skips_test_their_own_key() {
+    // LD V0, 2; SKP V0
+    let skip_pressed = [0x60, 0x02, 0xE0, 0x9E];
+    // LD V0, 2; SKNP V0
+    let skip_not_pressed = [0x60, 0x02, 0xE0, 0xA1];
+    let mut chip8 = Chip8::new(700);
+    chip8.handle_key_pressed(1).unwrap();
+    chip8.handle_key_pressed(2).unwrap();
+    assert_eq!(pc_after(&mut chip8, &skip_pressed, 2), 0x206);
+    assert_eq!(pc_after(&mut chip8, &skip_not_pressed, 2), 0x204);
+
+    chip8.handle_key_released(2).unwrap();
+    assert_eq!(pc_after(&mut chip8, &skip_pressed, 2), 0x204);
+    assert_eq!(pc_after(&mut chip8, &skip_not_pressed, 2), 0x206);
+}
+
+/// Returns a machine waiting for a key in V3 with `quirks`, then counting in V1.
+fn waiting(quirks: Quirks) -> Chip8 {
+    // LD V3, K; ADD V1, 1; JP 0x202
+    let mut chip8 = Chip8::with_quirks(700, quirks);
+    chip8
+        .store_in_ram([0xF3, 0x0A, 0x71, 0x01, 0x12, 0x02])
+        .unwrap();
+    chip8.step(&mut Headless, &mut Headless).unwrap();
+    assert!(chip8.is_waiting_for_key());
+    chip8
+}
+
+#[test]
+fn key_wait_completes_on_release() {
+    let mut chip8 = waiting(Quirks::cosmac_vip());
+    chip8.set_delay_timer(10);
+    chip8.handle_key_pressed(5).unwrap();
+    chip8.handle_key_pressed(6).unwrap();
+    chip8.run_frame(&mut Headless, &mut Headless).unwrap();
+    assert!(chip8.is_waiting_for_key());
+    assert_eq!(chip8.registers()[1], 0);
+    // The timers keep running while waiting.
+    assert_eq!(chip8.delay_timer(), 9);
+
+    // Only the release of the first key pressed completes the wait.
+    chip8.handle_key_released(6).unwrap();
+    assert!(chip8.is_waiting_for_key());
+    chip8.handle_key_released(5).unwrap();
+    assert!(!chip8.is_waiting_for_key());
+    assert_eq!(chip8.registers()[3], 5);
+    chip8.step(&mut Headless, &mut Headless).unwrap();
+    assert_eq!(chip8.registers()[1], 1);
+}
+
+#[test]
+fn keys_held_before_the_wait_do_not_complete_it() {
+    let mut chip8 = Chip8::with_quirks(700, Quirks::cosmac_vip());
+    chip8.handle_key_pressed(5).unwrap();
+    // LD V3, K
+    chip8.store_in_ram([0xF3, 0x0A]).unwrap();
+    chip8.step(&mut Headless, &mut Headless).unwrap();
+    chip8.handle_key_released(5).unwrap();
+    assert!(chip8.is_waiting_for_key());
+
+    chip8.handle_key_pressed(5).unwrap();
+    chip8.handle_key_released(5).unwrap();
+    assert!(!chip8.is_waiting_for_key());
+}
+
+#[test]
+fn key_wait_completes_on_press_without_the_quirk() {
+    let mut chip8 = waiting(Quirks::super_chip());
+    chip8.handle_key_pressed(5).unwrap();
+    assert!(!chip8.is_waiting_for_key());
+    assert_eq!(chip8.registers()[3], 5);
+}
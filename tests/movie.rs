@@ -0,0 +1,123 @@
+//! Recording and playback of input movies.
+
+// Only the headless stub of the harness is used.
+#[allow(dead_code)]
+mod harness;
+
+use harness::Headless;
+use rusty_chip8::{Chip8, Chip8Error, CosmacVipRandom, Movie, Platform, Quirks, Speed};
+
+/// LD V0, K; ADD V1, 1; JP 0x202
+const KEY_WAIT: [u8; 6] = [0xF0, 0x0A, 0x71, 0x01, 0x12, 0x02];
+
+/// Records `frames` frames of `rom`, tapping key 5 within the frame `tap`.
+fn record(rom: &[u8], frames: usize, tap: usize) -> (Movie, Chip8) {
+    let mut movie = Movie::new(
+        rom,
+        Platform::Chip8,
+        Quirks::cosmac_vip(),
+        Speed::default(),
+        7,
+    );
+    let mut chip8 = movie.start(rom).unwrap();
+    for frame in 0..frames {
+        if frame == tap {
+            movie.handle_key_pressed(&mut chip8, 5).unwrap();
+            movie.handle_key_released(&mut chip8, 5).unwrap();
+        }
+        movie.record(&chip8).unwrap();
+        chip8.run_frame(&mut Headless, &mut Headless).unwrap();
+    }
+    (movie, chip8)
+}
+
+/// Plays `movie` back, and returns the machine.
+fn play(movie: &Movie, rom: &[u8]) -> Chip8 {
+    let mut chip8 = movie.start(rom).unwrap();
+    let mut player = movie.player();
+    while player.play(&mut chip8).unwrap() {
+        chip8.run_frame(&mut Headless, &mut Headless).unwrap();
+    }
+    assert_eq!(player.frame(), movie.frame_count());
+    chip8
+}
+
+#[test]
+fn key_tapped_within_a_frame_is_played_back() {
+    let (movie, recorded) = record(&KEY_WAIT, 130, 3);
+    assert_eq!(recorded.registers()[0], 5);
+
+    let played = play(&movie, &KEY_WAIT);
+    assert_eq!(played.registers()[..2], recorded.registers()[..2]);
+    assert_eq!(played.state_hash(), recorded.state_hash());
+}
+
+#[test]
+fn keys_handled_directly_are_recorded_by_state() {
+    let mut movie = Movie::new(
+        &KEY_WAIT,
+        Platform::Chip8,
+        Quirks::default(),
+        Speed::default(),
+        7,
+    );
+    let mut chip8 = movie.start(&KEY_WAIT).unwrap();
+    for frame in 0..10 {
+        match frame {
+            2 => chip8.handle_key_pressed(9).unwrap(),
+            6 => chip8.handle_key_released(9).unwrap(),
+            _ => {}
+        }
+        movie.record(&chip8).unwrap();
+        chip8.run_frame(&mut Headless, &mut Headless).unwrap();
+    }
+    assert_eq!(chip8.registers()[0], 9);
+
+    let played = play(&movie, &KEY_WAIT);
+    assert_eq!(played.state_hash(), chip8.state_hash());
+}
+
+#[test]
+fn first_frame_runs_at_the_speed_of_the_movie() {
+    // ADD V0, 1; JP 0x200
+    let rom = [0x70, 0x01, 0x12, 0x00];
+    let movie = Movie::new(
+        &rom,
+        Platform::Chip8,
+        Quirks::default(),
+        Speed::CyclesPerFrame(10),
+        0,
+    );
+    let mut chip8 = movie.start(&rom).unwrap();
+    chip8.run_frame(&mut Headless, &mut Headless).unwrap();
+    assert_eq!(chip8.registers()[0], 5);
+}
+
+#[test]
+fn round_trip() {
+    let (movie, _) = record(&KEY_WAIT, 70, 3);
+    assert_eq!(Movie::from_bytes(&movie.to_bytes()), Ok(movie));
+}
+
+#[test]
+fn other_random_generators_are_rejected() {
+    let (movie, _) = record(&KEY_WAIT, 3, 0);
+    let mut chip8 = movie.start(&KEY_WAIT).unwrap();
+    chip8.set_random(CosmacVipRandom::new(0, [0; 256]));
+    assert_eq!(
+        movie.player().play(&mut chip8),
+        Err(Chip8Error::MovieRandomMismatch)
+    );
+
+    let mut movie = Movie::new(
+        &KEY_WAIT,
+        Platform::Chip8,
+        Quirks::default(),
+        Speed::default(),
+        7,
+    );
+    let mut chip8 = movie.start(&KEY_WAIT).unwrap();
+    chip8.set_random(CosmacVipRandom::new(0, [0; 256]));
+    assert_eq!(movie.record(&chip8), Err(Chip8Error::MovieRandomMismatch));
+    assert_eq!(movie.frame_count(), 0);
+}
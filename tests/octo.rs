@@ -0,0 +1,232 @@
+//! Compilation of Octo snippets into exact bytes and source maps.
+
+use rusty_chip8::{
+    asm::AsmErrorKind,
+    octo::{self, Program},
+    Platform,
+};
+
+fn compile(source: &str) -> Program {
+    octo::compile(source, Platform::Chip8).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// Returns the kind and the line of the error of compiling `source`.
+fn error(source: &str) -> (AsmErrorKind, usize) {
+    let error = octo::compile(source, Platform::Chip8).expect_err("the source is invalid");
+    (error.kind().clone(), error.line())
+}
+
+#[test]
+fn jump_to_main_is_inserted_unless_main_comes_first() {
+    let program = compile(": main\n    clear\n");
+    assert_eq!(program.rom(), [0x00, 0xE0]);
+
+    let program = compile(": helper\n    return\n: main\n    helper\n");
+    assert_eq!(program.rom(), [0x12, 0x04, 0x00, 0xEE, 0x22, 0x02]);
+    assert_eq!(program.source_map().label("helper"), Some(0x202));
+    assert_eq!(program.source_map().label("main"), Some(0x204));
+}
+
+#[test]
+fn loop_while_again() {
+    let program = compile(
+        "\
+: main
+    v0 := 0
+    loop
+        v0 += 1
+        while v0 != 10
+    again
+: done
+    jump done
+",
+    );
+    assert_eq!(
+        program.rom(),
+        [0x60, 0x00, 0x70, 0x01, 0x40, 0x0A, 0x12, 0x0A, 0x12, 0x02, 0x12, 0x0A]
+    );
+
+    let map = program.source_map();
+    assert_eq!(map.line(0x200), Some(2));
+    assert_eq!(map.line(0x201), Some(2));
+    assert_eq!(map.line(0x202), Some(4));
+    // The skip and the jump out of the loop both come from the `while`.
+    assert_eq!(map.line(0x204), Some(5));
+    assert_eq!(map.line(0x206), Some(5));
+    assert_eq!(map.line(0x208), Some(6));
+    assert_eq!(map.line(0x20A), Some(8));
+    assert_eq!(map.line(0x20C), None);
+    assert_eq!(map.address(2), Some(0x200));
+    assert_eq!(map.address(3), None);
+    assert_eq!(map.address(5), Some(0x204));
+    assert_eq!(map.address(6), Some(0x208));
+    assert_eq!(map.label("done"), Some(0x20A));
+}
+
+#[test]
+fn if_then() {
+    let program = compile(
+        "\
+: main
+    if v1 == 2 then v2 := 3
+    if v1 != v4 then v2 := 3
+    if v1 key then v2 := 3
+    if v1 -key then v2 := 3
+",
+    );
+    assert_eq!(
+        program.rom(),
+        [
+            0x41, 0x02, 0x62, 0x03, // skip unless v1 == 2
+            0x51, 0x40, 0x62, 0x03, // skip unless v1 != v4
+            0xE1, 0xA1, 0x62, 0x03, // skip unless v1 is pressed
+            0xE1, 0x9E, 0x62, 0x03, // skip unless v1 is released
+        ]
+    );
+    assert_eq!(program.source_map().address(4), Some(0x208));
+    assert_eq!(program.source_map().line(0x20A), Some(4));
+}
+
+#[test]
+fn if_begin_else_end() {
+    let program = compile(
+        "\
+: main
+    if v1 key begin
+        v3 := 4
+    else
+        v3 := 5
+    end
+    v3 += 1
+",
+    );
+    assert_eq!(
+        program.rom(),
+        [
+            0xE1, 0x9E, 0x12, 0x08, // skip the jump to `else` if v1 is pressed
+            0x63, 0x04, 0x12, 0x0A, // then jump over `else`
+            0x63, 0x05, // else
+            0x73, 0x01,
+        ]
+    );
+
+    let map = program.source_map();
+    assert_eq!(map.address(2), Some(0x200));
+    assert_eq!(map.line(0x206), Some(4));
+    assert_eq!(map.address(5), Some(0x208));
+    assert_eq!(map.address(6), None);
+    assert_eq!(map.address(7), Some(0x20A));
+}
+
+#[test]
+fn comparisons_compute_vf() {
+    // vf := rhs, then `vf =- v1` or `vf -= v1` leave vf = 1 if v1 >= rhs
+    // or v1 <= rhs, and `then` skips the statement unless vf matches.
+    let cases: [(&str, [u8; 8]); 5] = [
+        ("v1 < 5", [0x6F, 0x05, 0x8F, 0x17, 0x4F, 0x00, 0x62, 0x01]),
+        ("v1 > 5", [0x6F, 0x05, 0x8F, 0x15, 0x4F, 0x00, 0x62, 0x01]),
+        ("v1 <= 5", [0x6F, 0x05, 0x8F, 0x15, 0x4F, 0x01, 0x62, 0x01]),
+        ("v1 >= 5", [0x6F, 0x05, 0x8F, 0x17, 0x4F, 0x01, 0x62, 0x01]),
+        ("v1 < v3", [0x8F, 0x30, 0x8F, 0x17, 0x4F, 0x00, 0x62, 0x01]),
+    ];
+    for (condition, bytes) in cases {
+        let source = format!(": main\n    if {condition} then v2 := 1\n");
+        assert_eq!(compile(&source).rom(), bytes, "if {condition}");
+    }
+
+    let program = compile(": main\n    loop\n        while v1 < 5\n    again\n");
+    assert_eq!(
+        program.rom(),
+        [0x6F, 0x05, 0x8F, 0x17, 0x3F, 0x00, 0x12, 0x0A, 0x12, 0x00]
+    );
+
+    assert_eq!(
+        error(": main\n    if vf < 1 then return\n"),
+        (
+            AsmErrorKind::InvalidOperands {
+                mnemonic: "<".to_string()
+            },
+            2
+        )
+    );
+}
+
+#[test]
+fn macros_are_expanded_with_their_arguments() {
+    let program = compile(
+        "\
+:macro set-both register value {
+    register := value
+    v9 := value
+}
+: main
+    set-both v4 7
+    set-both v5 0x10
+",
+    );
+    assert_eq!(
+        program.rom(),
+        [0x64, 0x07, 0x69, 0x07, 0x65, 0x10, 0x69, 0x10]
+    );
+
+    assert_eq!(
+        error(":macro forever { forever }\n: main\n    forever\n").0,
+        AsmErrorKind::RecursiveMacro {
+            name: "forever".to_string()
+        }
+    );
+}
+
+#[test]
+fn calc_evaluates_from_right_to_left() {
+    let program = compile(
+        "\
+:calc SPEED { 2 * 3 + 1 }
+:calc DIFFERENCE { 10 - 4 - 3 }
+:calc GROUPED { ( 2 * 3 ) + 1 }
+: main
+    v0 := SPEED
+    v1 := DIFFERENCE
+    v2 := GROUPED
+    :byte { SPEED << 2 }
+",
+    );
+    assert_eq!(program.rom(), [0x60, 0x08, 0x61, 0x09, 0x62, 0x07, 0x20]);
+
+    assert_eq!(
+        error(":calc BROKEN { 1 / 0 }\n").0,
+        AsmErrorKind::InvalidExpression
+    );
+}
+
+#[test]
+fn unbalanced_blocks_and_unexpected_end() {
+    assert_eq!(
+        error(": main\n    v0 := 1\n    again\n"),
+        (
+            AsmErrorKind::UnbalancedBlock {
+                keyword: "again".to_string()
+            },
+            3
+        )
+    );
+    assert_eq!(
+        error(": main\n    loop\n        v0 += 1\n"),
+        (
+            AsmErrorKind::UnbalancedBlock {
+                keyword: "loop".to_string()
+            },
+            2
+        )
+    );
+    assert_eq!(
+        error(": main\n    if v0 == 1 begin\n    else\n    else\n    end\n"),
+        (
+            AsmErrorKind::UnbalancedBlock {
+                keyword: "else".to_string()
+            },
+            4
+        )
+    );
+    assert_eq!(error(": main\n    v0 :=\n").0, AsmErrorKind::UnexpectedEnd);
+}
@@ -0,0 +1,75 @@
+//! Presentation of the whole framebuffer at the end of every frame.
+
+// Only the headless stub of the harness is used.
+#[allow(dead_code)]
+mod harness;
+
+use harness::Headless;
+use rusty_chip8::{Chip8, Framebuffer, Graphics, Rect, TERMINAL_HEIGHT, TERMINAL_WIDTH};
+
+/// Graphics recording the presented frames.
+#[derive(Default)]
+struct Recorder {
+    /// Changed region and lit pixel count of every presented frame.
+    frames: Vec<(Option<Rect>, usize)>,
+}
+
+impl Graphics for Recorder {
+    fn present(&mut self, framebuffer: &Framebuffer) {
+        let lit = framebuffer
+            .pixels()
+            .iter()
+            .filter(|pixel| **pixel != 0)
+            .count();
+        self.frames.push((framebuffer.dirty_rect(), lit));
+    }
+}
+
+/// Returns a machine drawing a row of 8 pixels at (8, 4), then looping.
+fn drawing() -> Chip8 {
+    // LD I, 0x300; LD V0, 8; LD V1, 4; DRW V0, V1, 1; JP 0x208
+    let rom = [0xA3, 0x00, 0x60, 0x08, 0x61, 0x04, 0xD0, 0x11, 0x12, 0x08];
+    let mut chip8 = Chip8::new(700);
+    chip8.store_in_ram(rom).unwrap();
+    chip8.write_memory(0x300, &[0xFF]).unwrap();
+    chip8
+}
+
+#[test]
+fn frames_are_presented_once() {
+    let mut chip8 = drawing();
+    let mut recorder = Recorder::default();
+    chip8.run_frame(&mut recorder, &mut Headless).unwrap();
+    chip8.run_frame(&mut recorder, &mut Headless).unwrap();
+    chip8.run_cycles(30, &mut recorder, &mut Headless).unwrap();
+    assert_eq!(recorder.frames.len(), 4);
+    assert!(!chip8.framebuffer().is_dirty());
+}
+
+#[test]
+fn presents_carry_the_changed_region() {
+    let mut chip8 = drawing();
+    let mut recorder = Recorder::default();
+    chip8.run_frame(&mut recorder, &mut Headless).unwrap();
+    chip8.run_frame(&mut recorder, &mut Headless).unwrap();
+    // Draws the row again, erasing it.
+    chip8.set_pc(0x206).unwrap();
+    chip8.run_frame(&mut recorder, &mut Headless).unwrap();
+
+    let row = Rect {
+        x: 8,
+        y: 4,
+        width: 8,
+        height: 1,
+    };
+    let whole = Rect {
+        x: 0,
+        y: 0,
+        width: TERMINAL_WIDTH,
+        height: TERMINAL_HEIGHT,
+    };
+    assert_eq!(
+        recorder.frames,
+        [(Some(whole), 8), (None, 8), (Some(row), 0)]
+    );
+}
@@ -0,0 +1,141 @@
+//! Instruction, subroutine and sprite statistics of the profiler.
+
+// Only the headless stub of the harness is used.
+#[allow(dead_code)]
+mod harness;
+
+use harness::Headless;
+use rusty_chip8::{Chip8, FramePixels, Quirks, SubroutineProfile};
+
+/// Returns a machine running `rom` with the quirks of the COSMAC VIP.
+fn chip8(rom: &[u8]) -> Chip8 {
+    let mut chip8 = Chip8::with_quirks(700, Quirks::cosmac_vip());
+    chip8.store_in_ram(rom).unwrap();
+    chip8
+}
+
+/// Executes `n` instructions.
+fn step(chip8: &mut Chip8, n: usize) {
+    for _ in 0..n {
+        chip8.step(&mut Headless, &mut Headless).unwrap();
+    }
+}
+
+/// Returns the folded call stacks of the profile.
+fn folded(chip8: &Chip8) -> String {
+    let mut folded = Vec::new();
+    chip8.profile().unwrap().write_folded(&mut folded).unwrap();
+    String::from_utf8(folded).unwrap()
+}
+
+#[test]
+fn calls_and_cycles_are_counted() {
+    // LD V0, 5; CALL 0x208; CALL 0x208; JP 0x206; ADD V0, 1; RET
+    let rom = [
+        0x60, 0x05, 0x22, 0x08, 0x22, 0x08, 0x12, 0x06, 0x70, 0x01, 0x00, 0xEE,
+    ];
+    let mut chip8 = chip8(&rom);
+    chip8.set_profiling(true);
+    step(&mut chip8, 8);
+
+    let profile = chip8.profile().unwrap();
+    assert_eq!(profile.instruction_count(), 8);
+    assert_eq!(profile.cycle_count(), 8);
+    assert_eq!(
+        profile.address_counts().collect::<Vec<_>>(),
+        [
+            (0x200, 1),
+            (0x202, 1),
+            (0x204, 1),
+            (0x206, 1),
+            (0x208, 2),
+            (0x20A, 2)
+        ]
+    );
+    let variants = profile.variant_counts();
+    let variants: Vec<_> = variants
+        .iter()
+        .map(|(name, count)| (name.as_str(), *count))
+        .collect();
+    assert_eq!(
+        variants[..3],
+        [
+            ("Add7XNN", 2),
+            ("SubroutineCall2NNN", 2),
+            ("SubroutineReturn00EE", 2)
+        ]
+    );
+    assert_eq!(
+        profile.subroutines().collect::<Vec<_>>(),
+        [(
+            0x208,
+            SubroutineProfile {
+                calls: 2,
+                cycles: 4
+            }
+        )]
+    );
+    // The calls are spent in the caller, the returns in the callee.
+    assert_eq!(folded(&chip8), "main 4\nmain;L208 4\n");
+}
+
+#[test]
+fn profiling_can_start_inside_a_subroutine() {
+    // CALL 0x206; CALL 0x20A; JP 0x204; CALL 0x20A; RET; RET
+    let rom = [
+        0x22, 0x06, 0x22, 0x0A, 0x12, 0x04, 0x22, 0x0A, 0x00, 0xEE, 0x00, 0xEE,
+    ];
+    let mut chip8 = chip8(&rom);
+    step(&mut chip8, 1);
+    chip8.set_profiling(true);
+    step(&mut chip8, 6);
+
+    assert_eq!(chip8.pc(), 0x204);
+    // The return from the subroutine called before the profiling is spent
+    // in `main`, not in the last callee.
+    assert_eq!(folded(&chip8), "main 4\nmain;L20A 2\n");
+    assert_eq!(
+        chip8.profile().unwrap().subroutines().collect::<Vec<_>>(),
+        [(
+            0x20A,
+            SubroutineProfile {
+                calls: 2,
+                cycles: 2
+            }
+        )]
+    );
+}
+
+#[test]
+fn sprite_pixels_are_counted_by_frame() {
+    // LD I, 0x20A; DRW V0, V0, 1 three times; JP 0x208; the sprite
+    let rom = [
+        0xA2, 0x0A, 0xD0, 0x01, 0xD0, 0x01, 0xD0, 0x01, 0x12, 0x08, 0xF0,
+    ];
+    let mut chip8 = chip8(&rom);
+    chip8.set_profiling(true);
+    // Each sprite waits for the next frame.
+    for _ in 0..3 {
+        chip8.run_frame(&mut Headless, &mut Headless).unwrap();
+    }
+
+    let profile = chip8.profile().unwrap();
+    assert_eq!(profile.first_frame(), 0);
+    assert_eq!(
+        profile.frame_pixels()[..3],
+        [
+            FramePixels {
+                drawn: 4,
+                erased: 0
+            },
+            FramePixels {
+                drawn: 0,
+                erased: 4
+            },
+            FramePixels {
+                drawn: 4,
+                erased: 0
+            },
+        ]
+    );
+}
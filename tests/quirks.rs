@@ -0,0 +1,69 @@
+//! Behaviours differing between the quirks presets.
+
+// Only the headless stub of the harness is used.
+#[allow(dead_code)]
+mod harness;
+
+use harness::Headless;
+use rusty_chip8::{Chip8, Quirks};
+
+/// Runs `n` instructions of `rom` with `quirks`.
+fn run(rom: &[u8], quirks: Quirks, n: u64) -> Chip8 {
+    let mut chip8 = Chip8::with_quirks(700, quirks);
+    chip8.store_in_ram(rom).unwrap();
+    chip8.write_memory(0x300, &[0xFF]).unwrap();
+    chip8.run_cycles(n, &mut Headless, &mut Headless).unwrap();
+    chip8
+}
+
+#[test]
+fn shifts_use_vy_on_the_cosmac_vip() {
+    // LD V0, 0x10; LD V1, 0x03; SHR V0, V1
+    let rom = [0x60, 0x10, 0x61, 0x03, 0x80, 0x16];
+    let vip = run(&rom, Quirks::cosmac_vip(), 3);
+    assert_eq!(vip.registers()[0], 0x01);
+    assert_eq!(vip.registers()[15], 1);
+
+    let super_chip = run(&rom, Quirks::super_chip(), 3);
+    assert_eq!(super_chip.registers()[0], 0x08);
+    assert_eq!(super_chip.registers()[15], 0);
+}
+
+#[test]
+fn memory_instructions_increment_the_index() {
+    // LD I, 0x300; LD [I], V2
+    let rom = [0xA3, 0x00, 0xF2, 0x55];
+    assert_eq!(run(&rom, Quirks::cosmac_vip(), 2).index(), 0x303);
+    assert_eq!(run(&rom, Quirks::chip48(), 2).index(), 0x302);
+    assert_eq!(run(&rom, Quirks::super_chip(), 2).index(), 0x300);
+}
+
+#[test]
+fn logic_resets_vf_on_the_cosmac_vip() {
+    // LD VF, 5; OR V0, V0
+    let rom = [0x6F, 0x05, 0x80, 0x01];
+    assert_eq!(run(&rom, Quirks::cosmac_vip(), 2).registers()[15], 0);
+    assert_eq!(run(&rom, Quirks::super_chip(), 2).registers()[15], 5);
+}
+
+#[test]
+fn sprites_wrap_around_without_clipping() {
+    // LD I, 0x300; LD V0, 60; DRW V0, V1, 1
+    let rom = [0xA3, 0x00, 0x60, 0x3C, 0xD0, 0x11];
+    let vip = run(&rom, Quirks::cosmac_vip(), 3);
+    assert_eq!(vip.framebuffer().pixel(63, 0), 1);
+    assert_eq!(vip.framebuffer().pixel(0, 0), 0);
+
+    let octo = run(&rom, Quirks::octo(), 3);
+    assert_eq!(octo.framebuffer().pixel(63, 0), 1);
+    assert_eq!(octo.framebuffer().pixel(0, 0), 1);
+    assert_eq!(octo.framebuffer().pixel(4, 0), 0);
+}
+
+#[test]
+fn jumps_with_offset_use_vx_on_the_super_chip() {
+    // LD V3, 4; JP V0, 0x300
+    let rom = [0x63, 0x04, 0xB3, 0x00];
+    assert_eq!(run(&rom, Quirks::cosmac_vip(), 2).pc(), 0x300);
+    assert_eq!(run(&rom, Quirks::super_chip(), 2).pc(), 0x304);
+}
@@ -0,0 +1,82 @@
+//! Sequences of the random number generators.
+
+// Only the headless stub of the harness is used.
+#[allow(dead_code)]
+mod harness;
+
+use harness::Headless;
+use rusty_chip8::{Chip8, CosmacVipRandom, Random, SeededRandom};
+
+/// Returns the next `n` bytes of `random`.
+fn bytes(random: &mut impl Random, n: usize) -> Vec<u8> {
+    (0..n).map(|_| random.next_byte()).collect()
+}
+
+/// Returns a table holding the complement of every index.
+fn complements() -> [u8; 256] {
+    let mut table = [0; 256];
+    for (value, byte) in (0..=u8::MAX).rev().zip(&mut table) {
+        *byte = value;
+    }
+    table
+}
+
+#[test]
+fn seeded_random_repeats_its_sequence() {
+    let sequence = bytes(&mut SeededRandom::new(42), 16);
+    assert_eq!(bytes(&mut SeededRandom::new(42), 16), sequence);
+    assert_ne!(bytes(&mut SeededRandom::new(43), 16), sequence);
+    // A zero seed would get xorshift stuck.
+    assert_eq!(
+        bytes(&mut SeededRandom::new(0), 16),
+        bytes(&mut SeededRandom::default(), 16)
+    );
+}
+
+#[test]
+fn seeded_random_restores_its_state() {
+    let mut random = SeededRandom::new(42);
+    bytes(&mut random, 5);
+    let state = random.state();
+    let sequence = bytes(&mut random, 8);
+    assert!(random.restore(&state));
+    assert_eq!(bytes(&mut random, 8), sequence);
+    assert!(!random.restore(&[0; 8]));
+    assert!(!random.restore(&[1; 2]));
+}
+
+#[test]
+fn cosmac_vip_random_adds_the_table_byte_of_the_high_counter_byte() {
+    // 0x00FF + 1 = 0x0100: table[0x01] + 0x00 = 0xFE, the counter is 0xFE00.
+    // 0xFE01: table[0xFE] + 0x01 = 0x02, the counter is 0x0201, and so on.
+    let mut random = CosmacVipRandom::new(0x00FF, complements());
+    assert_eq!(bytes(&mut random, 4), [0xFE, 0x02, 0xFF, 0x03]);
+}
+
+#[test]
+fn cosmac_vip_random_depends_on_the_frames() {
+    let mut random = CosmacVipRandom::new(0x00FF, complements());
+    random.next_frame();
+    // 0x0101: table[0x01] + 0x01.
+    assert_eq!(random.next_byte(), 0xFF);
+
+    let state = random.state();
+    let sequence = bytes(&mut random, 4);
+    assert!(random.restore(&state));
+    assert_eq!(bytes(&mut random, 4), sequence);
+}
+
+#[test]
+fn frames_advance_the_cosmac_vip_counter() {
+    // JP 0x200
+    let mut chip8 = Chip8::new(700);
+    chip8.store_in_ram([0x12, 0x00]).unwrap();
+    chip8.set_random(CosmacVipRandom::new(0x00FF, complements()));
+    chip8.run_frame(&mut Headless, &mut Headless).unwrap();
+
+    // RND V0, 0xFF
+    chip8.store_in_ram([0xC0, 0xFF]).unwrap();
+    chip8.set_pc(0x200).unwrap();
+    chip8.step(&mut Headless, &mut Headless).unwrap();
+    assert_eq!(chip8.registers()[0], 0xFF);
+}
@@ -0,0 +1,79 @@
+//! Rewinding to the recorded states within the memory budget.
+
+// Only the headless stub of the harness is used.
+#[allow(dead_code)]
+mod harness;
+
+use harness::Headless;
+use rusty_chip8::{Chip8, Rewind};
+
+/// Returns a machine counting the instructions in V0 and V1.
+fn counter() -> Chip8 {
+    // ADD V0, 1; SE V0, 0; JP 0x200; ADD V1, 1; JP 0x200
+    let rom = [0x70, 0x01, 0x30, 0x00, 0x12, 0x00, 0x71, 0x01, 0x12, 0x00];
+    let mut chip8 = Chip8::new(700);
+    chip8.store_in_ram(rom).unwrap();
+    chip8
+}
+
+/// Runs `frames` frames recording every state, and returns the state hash
+/// of every frame.
+fn run(chip8: &mut Chip8, rewind: &mut Rewind, frames: usize) -> Vec<u64> {
+    let mut hashes = Vec::new();
+    for _ in 0..frames {
+        rewind.record(chip8);
+        hashes.push(chip8.state_hash());
+        chip8.run_frame(&mut Headless, &mut Headless).unwrap();
+    }
+    hashes
+}
+
+#[test]
+fn rewind_restores_the_exact_state() {
+    let mut chip8 = counter();
+    let mut rewind = Rewind::new(1, usize::MAX);
+    let hashes = run(&mut chip8, &mut rewind, 20);
+    assert_eq!(rewind.len(), 20);
+
+    assert_eq!(rewind.rewind(&mut chip8, 5), Ok(15));
+    assert_eq!(chip8.frame_count(), 15);
+    assert_eq!(chip8.state_hash(), hashes[15]);
+    assert_eq!(rewind.len(), 16);
+
+    assert_eq!(rewind.rewind(&mut chip8, 3), Ok(12));
+    assert_eq!(chip8.state_hash(), hashes[12]);
+}
+
+#[test]
+fn rewind_goes_back_to_the_recorded_interval() {
+    let mut chip8 = counter();
+    let mut rewind = Rewind::new(4, usize::MAX);
+    let hashes = run(&mut chip8, &mut rewind, 10);
+    assert_eq!(rewind.len(), 3);
+
+    assert_eq!(rewind.rewind(&mut chip8, 3), Ok(4));
+    assert_eq!(chip8.state_hash(), hashes[4]);
+}
+
+#[test]
+fn eviction_keeps_the_newest_states_within_the_budget() {
+    let state = counter().save_state().len();
+    let budget = state + 300;
+    let mut chip8 = counter();
+    let mut rewind = Rewind::new(1, budget);
+    let mut hashes = Vec::new();
+    for _ in 0..100 {
+        hashes.extend(run(&mut chip8, &mut rewind, 1));
+        assert!(rewind.memory_usage() <= budget);
+    }
+    let kept = rewind.len();
+    assert!(kept > 1 && kept < 100, "{kept} states kept");
+
+    let oldest = 100 - kept;
+    assert_eq!(
+        rewind.rewind(&mut chip8, u64::MAX),
+        Ok(u64::try_from(oldest).unwrap())
+    );
+    assert_eq!(chip8.state_hash(), hashes[oldest]);
+    assert_eq!(rewind.len(), 1);
+}
@@ -0,0 +1,142 @@
+//! Instructions and cycles run in each frame at the different speeds.
+
+// Only the headless stub of the harness is used.
+#[allow(dead_code)]
+mod harness;
+
+use harness::Headless;
+use rusty_chip8::{Chip8, Quirks, Speed};
+
+/// JP 0x200
+const JUMP: [u8; 2] = [0x12, 0x00];
+
+/// Returns a profiled machine running `rom`, at 700 instructions per
+/// second in the first frame.
+fn profiled(rom: &[u8], quirks: Quirks, speed: Speed) -> Chip8 {
+    let mut chip8 = Chip8::with_quirks(700, quirks);
+    if speed != chip8.speed() {
+        chip8.set_speed(speed);
+    }
+    chip8.store_in_ram(rom).unwrap();
+    chip8.set_profiling(true);
+    chip8
+}
+
+/// Runs `frames` frames and returns the number of instructions of each.
+fn instructions_per_frame(chip8: &mut Chip8, frames: usize) -> Vec<u64> {
+    let mut counts = Vec::new();
+    for _ in 0..frames {
+        let before = chip8.profile().unwrap().instruction_count();
+        chip8.run_frame(&mut Headless, &mut Headless).unwrap();
+        counts.push(chip8.profile().unwrap().instruction_count() - before);
+    }
+    counts
+}
+
+/// Returns the cycles of each of the next `n` instructions.
+fn cycles_per_instruction(chip8: &mut Chip8, n: usize) -> Vec<u64> {
+    let mut cycles = Vec::new();
+    for _ in 0..n {
+        let before = chip8.profile().unwrap().cycle_count();
+        chip8.step(&mut Headless, &mut Headless).unwrap();
+        cycles.push(chip8.profile().unwrap().cycle_count() - before);
+    }
+    cycles
+}
+
+#[test]
+fn instructions_per_second_carry_the_fractions_over() {
+    let mut chip8 = profiled(
+        &JUMP,
+        Quirks::super_chip(),
+        Speed::InstructionsPerSecond(700),
+    );
+    let counts = instructions_per_frame(&mut chip8, 60);
+    // 700 / 60 = 11.67: the fractions add up to a 12th instruction in two
+    // frames out of three.
+    assert_eq!(counts[..3], [11, 12, 12]);
+    assert_eq!(counts.iter().sum::<u64>(), 700);
+    assert_eq!(chip8.profile().unwrap().cycle_count(), 700);
+}
+
+#[test]
+fn cycles_per_frame_run_a_fixed_number_of_instructions() {
+    let mut chip8 = profiled(&JUMP, Quirks::super_chip(), Speed::CyclesPerFrame(10));
+    // The speed changes from the second frame on.
+    assert_eq!(instructions_per_frame(&mut chip8, 4), [11, 10, 10, 10]);
+}
+
+#[test]
+fn cosmac_vip_frames_spend_the_cycles_of_the_interpreter() {
+    let mut chip8 = profiled(&JUMP, Quirks::cosmac_vip(), Speed::CosmacVip);
+    // The first frame runs at the 700 instructions per second of the
+    // machine, then 2594 cycles per frame buy 113 jumps of 23 cycles, the
+    // overrun of the last one being taken from the next frame.
+    assert_eq!(instructions_per_frame(&mut chip8, 3), [1, 113, 113]);
+    assert_eq!(chip8.profile().unwrap().cycle_count(), 227 * 23);
+}
+
+#[test]
+fn cosmac_vip_instructions_and_sprites_have_their_own_cycles() {
+    // LD V0, 1; LD I, 0x300; ADD V1, V2; LD [I], V2; DRW V1, V1, 5 twice;
+    // DRW V0, V0, 5 on the cleared display
+    let rom = [
+        0x60, 0x01, 0xA3, 0x00, 0x81, 0x24, 0xF2, 0x55, 0xD1, 0x15, 0xD1, 0x15, 0xD0, 0x05,
+    ];
+    let mut chip8 = profiled(&rom, Quirks::super_chip(), Speed::CosmacVip);
+    chip8.write_memory(0x300, &[0xFF; 5]).unwrap();
+    assert_eq!(
+        cycles_per_instruction(&mut chip8, 7),
+        [
+            6,
+            12,
+            44,
+            20 + 14 * 3,
+            // 17 cycles per row of an aligned sprite, 4 more on collision,
+            // and 25 per row of an unaligned one.
+            26 + 5 * 17,
+            26 + 5 * 17 + 4,
+            26 + 5 * 25,
+        ]
+    );
+}
+
+#[test]
+fn display_wait_ends_the_frame_after_a_sprite() {
+    // DRW V0, V0, 1; JP 0x200, the jump running after the wait
+    let rom = [0xD0, 0x01, 0x12, 0x00];
+    let mut chip8 = profiled(
+        &rom,
+        Quirks::cosmac_vip(),
+        Speed::InstructionsPerSecond(700),
+    );
+    assert_eq!(instructions_per_frame(&mut chip8, 3), [1, 2, 2]);
+
+    let mut chip8 = profiled(
+        &rom,
+        Quirks::super_chip(),
+        Speed::InstructionsPerSecond(700),
+    );
+    assert_eq!(instructions_per_frame(&mut chip8, 3), [11, 12, 12]);
+}
+
+#[test]
+fn steps_at_speed_zero_only_end_frames() {
+    // LD V0, 60; LD DT, V0
+    let rom = [0x60, 0x3C, 0xF0, 0x15];
+    let mut chip8 = profiled(
+        &rom,
+        Quirks::super_chip(),
+        Speed::InstructionsPerSecond(700),
+    );
+    chip8.step(&mut Headless, &mut Headless).unwrap();
+    chip8.step(&mut Headless, &mut Headless).unwrap();
+    chip8.set_speed(Speed::InstructionsPerSecond(0));
+
+    chip8.step(&mut Headless, &mut Headless).unwrap();
+    chip8.step(&mut Headless, &mut Headless).unwrap();
+    assert_eq!(chip8.pc(), 0x204);
+    assert_eq!(chip8.frame_count(), 2);
+    assert_eq!(chip8.delay_timer(), 58);
+    assert_eq!(chip8.profile().unwrap().instruction_count(), 2);
+}
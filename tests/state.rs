@@ -0,0 +1,123 @@
+//! Round trips of the save states, and rejection of the invalid ones.
+
+// Only the headless stub of the harness is used.
+#[allow(dead_code)]
+mod harness;
+
+use harness::Headless;
+use rusty_chip8::{Chip8, Chip8Error, Platform, Quirks, SeededRandom};
+
+/// Returns a machine which ran the IBM logo for a few frames.
+fn running_chip8() -> Chip8 {
+    let mut chip8 = Chip8::new(700);
+    chip8.set_random(SeededRandom::new(1));
+    chip8
+        .store_in_ram(include_bytes!("../resources/roms/IBM Logo.ch8"))
+        .expect("the rom fits");
+    for _ in 0..10 {
+        chip8
+            .run_frame(&mut Headless, &mut Headless)
+            .expect("the rom runs");
+    }
+    chip8
+}
+
+/// Returns the offset of the RAM size in a CHIP-8 snapshot.
+fn ram_size_offset(state: &[u8]) -> usize {
+    let size = 4096u64.to_le_bytes();
+    state
+        .windows(size.len())
+        .position(|window| window == size)
+        .expect("the snapshot holds the RAM size")
+}
+
+#[test]
+fn round_trip() {
+    let mut chip8 = running_chip8();
+    let state = chip8.save_state();
+    let mut restored = Chip8::new(700);
+    restored.set_random(SeededRandom::new(2));
+    restored.load_state(&state).expect("the snapshot is valid");
+    assert_eq!(restored.save_state(), state);
+    assert_eq!(
+        restored.framebuffer().pixels(),
+        chip8.framebuffer().pixels()
+    );
+
+    for _ in 0..10 {
+        chip8.run_frame(&mut Headless, &mut Headless).unwrap();
+        restored.run_frame(&mut Headless, &mut Headless).unwrap();
+    }
+    assert_eq!(restored.state_hash(), chip8.state_hash());
+}
+
+#[test]
+fn truncated_snapshots_are_rejected() {
+    let state = running_chip8().save_state();
+    let mut chip8 = Chip8::new(700);
+    let before = chip8.save_state();
+    for len in 0..state.len() {
+        assert_eq!(
+            chip8.load_state(&state[..len]),
+            Err(Chip8Error::CorruptSaveState),
+            "snapshot truncated to {len} bytes"
+        );
+    }
+    assert_eq!(chip8.save_state(), before);
+}
+
+#[test]
+fn smaller_ram_is_rejected() {
+    let mut chip8 = running_chip8();
+    chip8.set_pc(0x10).unwrap();
+    let state = chip8.save_state();
+    let offset = ram_size_offset(&state);
+    let ram = offset + 8;
+    let mut small = state[..offset].to_vec();
+    small.extend_from_slice(&256u64.to_le_bytes());
+    small.extend_from_slice(&state[ram..ram + 256]);
+    small.extend_from_slice(&state[ram + 4096..]);
+
+    let mut chip8 = Chip8::new(700);
+    assert_eq!(chip8.load_state(&small), Err(Chip8Error::CorruptSaveState));
+    chip8
+        .store_in_ram(include_bytes!("../resources/roms/IBM Logo.ch8"))
+        .expect("the machine is unchanged");
+}
+
+#[test]
+fn ram_of_another_platform_is_rejected() {
+    let state = running_chip8().save_state();
+    let mut xo_chip = Chip8::with_platform(700, Platform::XoChip, Quirks::octo());
+    let platform = xo_chip.save_state()[5];
+    let mut mismatched = state;
+    mismatched[5] = platform;
+    assert_eq!(
+        xo_chip.load_state(&mismatched),
+        Err(Chip8Error::CorruptSaveState)
+    );
+}
+
+#[test]
+fn index_outside_of_the_ram_is_rejected() {
+    let state = running_chip8().save_state();
+    // The index register follows the RAM and the program counter.
+    let index = ram_size_offset(&state) + 8 + 4096 + 8;
+    let with_index = |i: u64| {
+        let mut state = state.clone();
+        state[index..index + 8].copy_from_slice(&i.to_le_bytes());
+        state
+    };
+
+    let mut chip8 = Chip8::new(700);
+    chip8.set_random(SeededRandom::new(1));
+    assert_eq!(chip8.load_state(&with_index(4096)), Ok(()));
+    assert_eq!(chip8.index(), 4096);
+    for i in [4097, u64::MAX] {
+        assert_eq!(
+            chip8.load_state(&with_index(i)),
+            Err(Chip8Error::CorruptSaveState),
+            "index {i:#X}"
+        );
+    }
+}
@@ -0,0 +1,47 @@
+//! Comparison of instruction traces.
+
+use rusty_chip8::trace::{self, Divergence};
+
+fn divergence(left: &str, right: &str, ignored: &[&str]) -> Option<Divergence> {
+    trace::first_divergence(left.as_bytes(), right.as_bytes(), ignored).unwrap()
+}
+
+fn fields(left: &str, right: &str) -> Vec<String> {
+    divergence(left, right, &[])
+        .expect("the traces differ")
+        .fields
+}
+
+#[test]
+fn same_traces_do_not_diverge() {
+    let trace = "CY=0 PC=0200 V0=00 ; LD V0, 1\nCY=1 PC=0202 V0=01\n";
+    let other = "CY=0  PC=0200 V0=00\nCY=1 PC=0202 V0=01 ; ADD V0, 1\n";
+    assert_eq!(divergence(trace, other, &[]), None);
+}
+
+#[test]
+fn fields_are_compared_by_key() {
+    assert_eq!(
+        fields("PC=0200 V0=01 I=0300", "PC=0200 I=0300 V0=02"),
+        ["V0"]
+    );
+    assert_eq!(fields("PC=0200 V0=01 I=0300", "PC=0200 I=0300"), ["V0"]);
+    assert_eq!(
+        fields("PC=0200 I=0300", "PC=0202 V0=01 I=0300 SP=01"),
+        ["PC", "V0", "SP"]
+    );
+    assert_eq!(fields("PC=0200 0200", "PC=0200 0202"), [""]);
+}
+
+#[test]
+fn ignored_fields_and_ended_traces() {
+    let left = "CY=0 PC=0200\nCY=2 PC=0202\n";
+    let right = "CY=0 PC=0200\nCY=1 PC=0202\nCY=2 PC=0204\n";
+    assert_eq!(fields(left, right), ["CY"]);
+
+    let divergence = divergence(left, right, &["CY"]).expect("the right trace is longer");
+    assert_eq!(divergence.line, 3);
+    assert_eq!(divergence.left, None);
+    assert_eq!(divergence.right.as_deref(), Some("CY=2 PC=0204"));
+    assert!(divergence.fields.is_empty());
+}